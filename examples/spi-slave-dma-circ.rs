@@ -0,0 +1,71 @@
+//! SPI slave circular DMA RX, streaming the data clocked in by an external master
+//!
+//! PB13 <- SCK
+//! PB15 <- MOSI
+
+#![allow(clippy::empty_loop)]
+#![no_std]
+#![no_main]
+
+use cortex_m_rt::entry;
+use panic_halt as _;
+
+use cortex_m::{asm, singleton};
+use embedded_hal::spi::{Mode, Phase, Polarity};
+use stm32f1xx_hal::{
+    dma::{Event, Half},
+    pac,
+    prelude::*,
+    spi::{NoMiso, Spi},
+};
+
+pub const MODE: Mode = Mode {
+    phase: Phase::CaptureOnSecondTransition,
+    polarity: Polarity::IdleHigh,
+};
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+
+    let mut flash = dp.FLASH.constrain();
+    let rcc = dp.RCC.constrain();
+
+    let _clocks = rcc.cfgr.freeze(&mut flash.acr);
+
+    let gpiob = dp.GPIOB.split();
+
+    // SPI2, receive only
+    let sck = gpiob.pb13;
+    let mosi = gpiob.pb15;
+
+    let spi2 = Spi::spi2_slave(dp.SPI2, (sck, NoMiso, mosi), MODE);
+
+    let dma = dp.DMA1.split();
+    let mut rx = spi2.with_rx_dma(dma.4);
+
+    // The half and full buffer events can also be handled from the DMA1_CHANNEL4 interrupt
+    rx.channel.listen(Event::HalfTransfer);
+    rx.channel.listen(Event::TransferComplete);
+
+    let buf = singleton!(: [[u8; 64]; 2] = [[0; 64]; 2]).unwrap();
+    let mut circ_buffer = rx.circ_read(buf);
+
+    let mut sum = 0u32;
+    loop {
+        // Process each half while the DMA fills the other one
+        while circ_buffer.readable_half().unwrap() != Half::First {}
+        sum = circ_buffer
+            .peek(|half, _| half.iter().fold(sum, |s, &b| s.wrapping_add(b as u32)))
+            .unwrap();
+
+        while circ_buffer.readable_half().unwrap() != Half::Second {}
+        sum = circ_buffer
+            .peek(|half, _| half.iter().fold(sum, |s, &b| s.wrapping_add(b as u32)))
+            .unwrap();
+
+        if sum == 0 {
+            asm::bkpt();
+        }
+    }
+}
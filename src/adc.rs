@@ -1,5 +1,12 @@
 //! # API for the Analog to Digital converter
-
+//!
+//! ## ADC3
+//!
+//! The high and XL-density STM32F103 have ADC3, transferring its results with DMA2 channel 5.
+//! Its channels 0 to 3 and 10 to 13 are on the same pins as ADC1 and ADC2, channels 4 to 8 are
+//! on PF6 to PF10. ADC3 has no temperature sensor and Vrefint channels.
+
+use core::convert::Infallible;
 use core::marker::PhantomData;
 use embedded_hal::adc::{Channel, OneShot};
 
@@ -13,8 +20,17 @@ use core::sync::atomic::{self, Ordering};
 use cortex_m::asm::delay;
 use embedded_dma::WriteBuffer;
 
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+use crate::pac::adc1::cr1::DUALMOD_A;
 use crate::pac::{self, RCC};
 
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod monitor;
+pub use monitor::{Divider, VoltageMonitor};
+pub mod oversample;
+pub use oversample::OversampledAdc;
+
 /// Continuous mode
 pub struct Continuous;
 /// Scan mode
@@ -23,9 +39,10 @@ pub struct Scan;
 /// ADC configuration
 pub struct Adc<ADC> {
     rb: ADC,
-    sample_time: SampleTime,
+    sample_times: [SampleTime; 18],
     align: Align,
     clocks: Clocks,
+    calibration: u16,
 }
 
 /// ADC sampling time
@@ -168,9 +185,159 @@ adc_pins!(pac::ADC3,
     gpio::PC3<Analog> => 13_u8,
 );
 
+/// ADC interrupt events
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Regular conversion complete
+    EndOfConversion,
+    /// Injected group conversions complete
+    InjectedComplete,
+    /// Conversion result out of the analog watchdog thresholds
+    AnalogWatchdog,
+}
+
+/// Channels guarded by the analog watchdog
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchdogChannels {
+    /// All the converted channels
+    All,
+    /// A single channel, see `WatchdogChannels::channel`
+    Single(u8),
+}
+
+impl WatchdogChannels {
+    /// Guards the channel of `pin`
+    pub fn channel<ADC, PIN: Channel<ADC, ID = u8>>(_pin: &PIN) -> Self {
+        WatchdogChannels::Single(PIN::channel())
+    }
+}
+
 /// Stored ADC config can be restored using the `Adc::restore_cfg` method
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
-pub struct StoredConfig(SampleTime, Align);
+pub struct StoredConfig([SampleTime; 18], Align);
+
+/// Regular sequence of up to 16 conversions of the channels of `ADC`
+///
+/// The sample time is set per channel, a channel converted several times uses the sample time
+/// of its last conversion. Configuring the ADC with the sequence also sets the sample times of
+/// its channels for the following one-shot conversions.
+///
+/// ```rust, ignore
+/// let sequence = Sequence::new()
+///     .convert(&pins.0, SampleTime::T_28)
+///     .convert(&pins.1, SampleTime::T_239);
+/// let adc_dma = adc1.with_sequence_dma(pins, &sequence, dma_ch1);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sequence<ADC> {
+    channels: [u8; 16],
+    sample_times: [SampleTime; 16],
+    len: usize,
+    discontinuous: Option<u8>,
+    _adc: PhantomData<ADC>,
+}
+
+impl<ADC> Sequence<ADC> {
+    /// Creates an empty sequence
+    pub fn new() -> Self {
+        Self {
+            channels: [0; 16],
+            sample_times: [SampleTime::default(); 16],
+            len: 0,
+            discontinuous: None,
+            _adc: PhantomData,
+        }
+    }
+
+    /// Appends a conversion of `pin` with `sample_time`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sequence already has 16 conversions.
+    pub fn convert<PIN>(mut self, _pin: &PIN, sample_time: SampleTime) -> Self
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        assert!(self.len < 16, "too many conversions in the sequence");
+        self.channels[self.len] = PIN::channel();
+        self.sample_times[self.len] = sample_time;
+        self.len += 1;
+        self
+    }
+
+    /// Converts only the next `count` (1 to 8) conversions of the sequence on each trigger
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is not between 1 and 8.
+    pub fn discontinuous(mut self, count: u8) -> Self {
+        assert!((1..=8).contains(&count), "1 to 8 channels per trigger");
+        self.discontinuous = Some(count);
+        self
+    }
+
+    /// Number of conversions in the sequence
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the sequence has no conversion
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a view of the DMA `buffer` of the sequence, giving the results by pin
+    ///
+    /// The buffer holds one or more rounds of the sequence conversions.
+    pub fn results<'a>(&'a self, buffer: &'a [u16]) -> SequenceResults<'a, ADC> {
+        SequenceResults {
+            sequence: self,
+            buffer,
+        }
+    }
+}
+
+/// Results of the conversions of a [`Sequence`], looked up by pin
+///
+/// ```rust, ignore
+/// let (buffer, adc_dma) = adc_dma.read(buffer).wait();
+/// let results = sequence.results(&buffer[..]);
+/// let value = results.get::<PA0<Analog>>();
+/// ```
+pub struct SequenceResults<'a, ADC> {
+    sequence: &'a Sequence<ADC>,
+    buffer: &'a [u16],
+}
+
+impl<'a, ADC> SequenceResults<'a, ADC> {
+    /// Returns the first result of `PIN`, if converted by the sequence
+    pub fn get<PIN>(&self) -> Option<u16>
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        self.all::<PIN>().next()
+    }
+
+    /// Returns the results of `PIN`, in the buffer order
+    pub fn all<PIN>(&self) -> impl Iterator<Item = u16> + 'a
+    where
+        PIN: Channel<ADC, ID = u8>,
+    {
+        let channels = &self.sequence.channels[..self.sequence.len];
+        channels
+            .iter()
+            .cycle()
+            .zip(self.buffer)
+            .filter(|(&chan, _)| chan == PIN::channel())
+            .map(|(_, &result)| result)
+    }
+}
+
+impl<ADC> Default for Sequence<ADC> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 macro_rules! adc_hal {
     ($(
@@ -186,54 +353,117 @@ macro_rules! adc_hal {
                 pub fn $adc(adc: $ADC, clocks: Clocks) -> Self {
                     let mut s = Self {
                         rb: adc,
-                        sample_time: SampleTime::default(),
+                        sample_times: [SampleTime::default(); 18],
                         align: Align::default(),
                         clocks,
+                        calibration: 0,
                     };
                     s.enable_clock();
                     s.power_down();
                     s.reset();
                     s.setup_oneshot();
                     s.power_up();
+                    s.calibrate();
+                    s
+                }
+
+                /// Calibrates the ADC and returns the calibration code
+                ///
+                /// The ADC is powered up first if needed. The reference manual recommends a
+                /// calibration after each power-up, the code of the last calibration is kept and
+                /// returned by `calibration`, for duty-cycled applications to decide when to
+                /// calibrate again.
+                pub fn calibrate(&mut self) -> u16 {
+                    if !self.is_powered() {
+                        self.power_up();
+                    }
 
                     // The manual states that we need to wait two ADC clocks cycles after power-up
                     // before starting calibration, we already delayed in the power-up process, but
                     // if the adc clock is too low that was not enough.
-                    if s.clocks.adcclk() < kHz(2500) {
-                        let two_adc_cycles = s.clocks.sysclk() / s.clocks.adcclk() * 2;
-                        let already_delayed = s.clocks.sysclk() / kHz(800);
+                    if self.clocks.adcclk() < kHz(2500) {
+                        let two_adc_cycles = self.clocks.sysclk() / self.clocks.adcclk() * 2;
+                        let already_delayed = self.clocks.sysclk() / kHz(800);
                         if two_adc_cycles > already_delayed {
                             delay(two_adc_cycles - already_delayed);
                         }
                     }
-                    s.calibrate();
-                    s
+
+                    /* reset calibration */
+                    self.rb.cr2.modify(|_, w| w.rstcal().set_bit());
+                    while self.rb.cr2.read().rstcal().bit_is_set() {}
+
+                    /* calibrate */
+                    self.rb.cr2.modify(|_, w| w.cal().set_bit());
+                    while self.rb.cr2.read().cal().bit_is_set() {}
+
+                    // The calibration code is left in the data register
+                    self.calibration = self.rb.dr.read().data().bits();
+                    self.calibration
+                }
+
+                /// Returns the calibration code of the last calibration
+                pub fn calibration(&self) -> u16 {
+                    self.calibration
+                }
+
+                /// Powers up the ADC, waiting for the stabilization time tSTAB
+                pub fn power_up(&mut self) {
+                    self.rb.cr2.modify(|_, w| w.adon().set_bit());
+
+                    // The reference manual says that a stabilization time is needed after power_up,
+                    // this time can be found in the datasheets.
+                    // Here we are delaying for approximately 1us, considering 1.25 instructions per
+                    // cycle. Do we support a chip which needs more than 1us ?
+                    delay(self.clocks.sysclk() / kHz(800));
+                }
+
+                /// Powers down the ADC, keeping its configuration
+                ///
+                /// The conversions in progress are lost.
+                pub fn power_down(&mut self) {
+                    self.rb.cr2.modify(|_, w| w.adon().clear_bit());
+                }
+
+                /// Returns true if the ADC is powered up
+                pub fn is_powered(&self) -> bool {
+                    self.rb.cr2.read().adon().bit_is_set()
                 }
 
                 /// Save current ADC config
                 pub fn save_cfg(&mut self) -> StoredConfig {
-                    StoredConfig(self.sample_time, self.align)
+                    StoredConfig(self.sample_times, self.align)
                 }
 
                 /// Restore saved ADC config
                 pub fn restore_cfg(&mut self, cfg: StoredConfig) {
-                    self.sample_time = cfg.0;
+                    self.sample_times = cfg.0;
                     self.align = cfg.1;
                 }
 
                 /// Reset the ADC config to default, return existing config
                 pub fn default_cfg(&mut self) -> StoredConfig {
                     let cfg = self.save_cfg();
-                    self.sample_time = SampleTime::default();
+                    self.sample_times = [SampleTime::default(); 18];
                     self.align = Align::default();
                     cfg
                 }
 
-                /// Set ADC sampling time
+                /// Set ADC sampling time of all the channels
                 ///
                 /// Options can be found in [SampleTime](crate::adc::SampleTime).
                 pub fn set_sample_time(&mut self, t_samp: SampleTime) {
-                    self.sample_time = t_samp;
+                    self.sample_times = [t_samp; 18];
+                }
+
+                /// Set ADC sampling time of the channel of `pin`
+                ///
+                /// High-impedance sources need longer sampling times than the other channels.
+                pub fn set_pin_sample_time<PIN>(&mut self, _pin: &PIN, t_samp: SampleTime)
+                where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    self.sample_times[usize::from(PIN::channel())] = t_samp;
                 }
 
                 /// Set the Adc result alignment
@@ -251,23 +481,142 @@ macro_rules! adc_hal {
                     }
                 }
 
+                /// Starts the regular conversions on `trigger`, a timer event or an EXTI line
+                ///
+                /// The one-shot reads need the software trigger, `EXTSEL_A::Swstart`, which is
+                /// restored with `set_software_trigger`.
                 #[inline(always)]
                 pub fn set_external_trigger(&mut self, trigger: crate::pac::$adc::cr2::EXTSEL_A) {
-                    self.rb.cr2.modify(|_, w| w.extsel().variant(trigger))
+                    self.rb
+                        .cr2
+                        .modify(|_, w| w.extsel().variant(trigger).exttrig().set_bit())
                 }
 
-                fn power_up(&mut self) {
-                    self.rb.cr2.modify(|_, w| w.adon().set_bit());
+                /// Starts the regular conversions from software, as needed by the one-shot reads
+                #[inline(always)]
+                pub fn set_software_trigger(&mut self) {
+                    self.set_external_trigger(crate::pac::$adc::cr2::EXTSEL_A::Swstart)
+                }
 
-                    // The reference manual says that a stabilization time is needed after power_up,
-                    // this time can be found in the datasheets.
-                    // Here we are delaying for approximately 1us, considering 1.25 instructions per
-                    // cycle. Do we support a chip which needs more than 1us ?
-                    delay(self.clocks.sysclk() / kHz(800));
+                /// Sets the injected group to the conversions of `sequence`, up to 4
+                ///
+                /// The results are read with `read_injected`, in the sequence order.
+                pub fn set_injected_sequence(&mut self, sequence: &Sequence<$ADC>) {
+                    let len = sequence.len();
+                    assert!(len > 0 && len <= 4, "the injected group has 1 to 4 conversions");
+                    for (&chan, &sample_time) in sequence.channels[..len]
+                        .iter()
+                        .zip(&sequence.sample_times)
+                    {
+                        self.sample_times[usize::from(chan)] = sample_time;
+                        self.set_channel_sample_time(chan, sample_time);
+                    }
+                    // A sequence shorter than 4 conversions ends with JSQ4
+                    let bits = sequence.channels[..len]
+                        .iter()
+                        .enumerate()
+                        .fold(((len - 1) as u32) << 20, |s, (i, c)| {
+                            s | ((*c as u32) << ((4 - len + i) * 5))
+                        });
+                    self.rb.jsqr.write(|w| unsafe { w.bits(bits) });
                 }
 
-                fn power_down(&mut self) {
-                    self.rb.cr2.modify(|_, w| w.adon().clear_bit());
+                /// Selects the trigger starting the injected group conversions
+                ///
+                /// `JEXTSEL_A::Jswstart` starts the conversions from `start_injected`.
+                pub fn set_injected_trigger(&mut self, trigger: crate::pac::$adc::cr2::JEXTSEL_A) {
+                    self.rb
+                        .cr2
+                        .modify(|_, w| w.jextsel().variant(trigger).jexttrig().set_bit());
+                }
+
+                /// Converts the injected group automatically after the regular group
+                pub fn set_auto_injection(&mut self, auto: bool) {
+                    self.rb.cr1.modify(|_, w| w.jauto().bit(auto));
+                }
+
+                /// Starts the injected group conversions, the trigger being `JEXTSEL_A::Jswstart`
+                pub fn start_injected(&mut self) {
+                    self.rb.cr2.modify(|_, w| w.jswstart().set_bit());
+                }
+
+                /// Returns true when the injected group conversions are complete
+                pub fn is_injected_complete(&self) -> bool {
+                    self.rb.sr.read().jeoc().bit_is_set()
+                }
+
+                /// Clears the injected group conversions complete flag
+                pub fn clear_injected_complete(&mut self) {
+                    self.rb.sr.modify(|_, w| w.jeoc().clear_bit());
+                }
+
+                /// Reads the result of the conversion `rank` (0 to 3) of the injected group
+                pub fn read_injected(&self, rank: usize) -> u16 {
+                    #[cfg(feature = "stm32f103")]
+                    {
+                        self.rb.jdr[rank].read().jdata().bits()
+                    }
+                    #[cfg(not(feature = "stm32f103"))]
+                    match rank {
+                        0 => self.rb.jdr1.read().jdata().bits(),
+                        1 => self.rb.jdr2.read().jdata().bits(),
+                        2 => self.rb.jdr3.read().jdata().bits(),
+                        3 => self.rb.jdr4.read().jdata().bits(),
+                        _ => panic!("invalid injected rank"),
+                    }
+                }
+
+                /// Enables the analog watchdog on the regular and injected conversions of
+                /// `channels`, flagging results below `low` or above `high`
+                ///
+                /// The thresholds are 12-bit values, compared to the results before alignment.
+                pub fn watchdog(&mut self, channels: WatchdogChannels, low: u16, high: u16) {
+                    self.rb.ltr.write(|w| w.lt().bits(low));
+                    self.rb.htr.write(|w| w.ht().bits(high));
+                    self.rb.cr1.modify(|_, w| {
+                        match channels {
+                            WatchdogChannels::All => w.awdsgl().clear_bit(),
+                            WatchdogChannels::Single(chan) => {
+                                unsafe { w.awdch().bits(chan) }.awdsgl().set_bit()
+                            }
+                        };
+                        w.awden().set_bit().jawden().set_bit()
+                    });
+                }
+
+                /// Disables the analog watchdog
+                pub fn disable_watchdog(&mut self) {
+                    self.rb
+                        .cr1
+                        .modify(|_, w| w.awden().clear_bit().jawden().clear_bit());
+                }
+
+                /// Returns true if a result was out of the analog watchdog thresholds
+                pub fn is_watchdog_triggered(&self) -> bool {
+                    self.rb.sr.read().awd().bit_is_set()
+                }
+
+                /// Clears the analog watchdog flag
+                pub fn clear_watchdog(&mut self) {
+                    self.rb.sr.modify(|_, w| w.awd().clear_bit());
+                }
+
+                /// Starts listening for an interrupt `event`
+                pub fn listen(&mut self, event: Event) {
+                    match event {
+                        Event::EndOfConversion => self.rb.cr1.modify(|_, w| w.eocie().set_bit()),
+                        Event::InjectedComplete => self.rb.cr1.modify(|_, w| w.jeocie().set_bit()),
+                        Event::AnalogWatchdog => self.rb.cr1.modify(|_, w| w.awdie().set_bit()),
+                    }
+                }
+
+                /// Stops listening for an interrupt `event`
+                pub fn unlisten(&mut self, event: Event) {
+                    match event {
+                        Event::EndOfConversion => self.rb.cr1.modify(|_, w| w.eocie().clear_bit()),
+                        Event::InjectedComplete => self.rb.cr1.modify(|_, w| w.jeocie().clear_bit()),
+                        Event::AnalogWatchdog => self.rb.cr1.modify(|_, w| w.awdie().clear_bit()),
+                    }
                 }
 
                 fn reset(&mut self) {
@@ -285,16 +634,6 @@ macro_rules! adc_hal {
                     <$ADC>::disable(rcc);
                 }
 
-                fn calibrate(&mut self) {
-                    /* reset calibration */
-                    self.rb.cr2.modify(|_, w| w.rstcal().set_bit());
-                    while self.rb.cr2.read().rstcal().bit_is_set() {}
-
-                    /* calibrate */
-                    self.rb.cr2.modify(|_, w| w.cal().set_bit());
-                    while self.rb.cr2.read().cal().bit_is_set() {}
-                }
-
                 fn setup_oneshot(&mut self) {
                     self.rb.cr2.modify(|_, w| w
                         .cont().clear_bit()
@@ -367,9 +706,25 @@ macro_rules! adc_hal {
                     self.rb.cr2.modify(|_, w| w.cont().bit(continuous));
                 }
 
+                fn configure_sequence(&mut self, sequence: &Sequence<$ADC>) {
+                    assert!(!sequence.is_empty(), "empty sequence");
+                    for (&chan, &sample_time) in sequence.channels[..sequence.len]
+                        .iter()
+                        .zip(&sequence.sample_times)
+                    {
+                        self.sample_times[usize::from(chan)] = sample_time;
+                        self.set_channel_sample_time(chan, sample_time);
+                    }
+                    self.set_regular_sequence(&sequence.channels[..sequence.len]);
+                    self.set_discontinuous_mode(sequence.discontinuous);
+                }
+
                 fn set_discontinuous_mode(&mut self, channels_count: Option<u8>) {
                     self.rb.cr1.modify(|_, w| match channels_count {
-                        Some(count) => w.discen().set_bit().discnum().bits(count),
+                        Some(count) => {
+                            assert!((1..=8).contains(&count), "1 to 8 channels per trigger");
+                            w.discen().set_bit().discnum().bits(count - 1)
+                        }
                         None => w.discen().clear_bit(),
                     });
                 }
@@ -388,12 +743,22 @@ macro_rules! adc_hal {
                   to `cr2` just before calling this function
                 */
                 fn convert(&mut self, chan: u8) -> u16 {
+                    self.start_channel_conversion(chan);
+                    while self.rb.cr2.read().swstart().bit_is_set() {}
+                    // ADC wait for conversion results
+                    while self.rb.sr.read().eoc().bit_is_clear() {}
+
+                    let res = self.rb.dr.read().data().bits();
+                    res
+                }
+
+                fn start_channel_conversion(&mut self, chan: u8) {
                     // Dummy read in case something accidentally triggered
                     // a conversion by writing to CR2 without changing any
                     // of the bits
                     self.rb.dr.read().data().bits();
 
-                    self.set_channel_sample_time(chan, self.sample_time);
+                    self.set_channel_sample_time(chan, self.sample_times[usize::from(chan)]);
                     self.rb.sqr3.modify(|_, w| unsafe { w.sq1().bits(chan) });
 
                     // ADC start conversion of regular sequence
@@ -402,12 +767,28 @@ macro_rules! adc_hal {
                             .swstart().set_bit()
                             .align().bit(self.align.into())
                     );
-                    while self.rb.cr2.read().swstart().bit_is_set() {}
-                    // ADC wait for conversion results
-                    while self.rb.sr.read().eoc().bit_is_clear() {}
+                }
 
-                    let res = self.rb.dr.read().data().bits();
-                    res
+                /// Starts a conversion of `pin` without waiting for the result
+                ///
+                /// The end of the conversion is signaled by `Event::EndOfConversion`, the result
+                /// is then read with `read_result`.
+                pub fn start_conversion<PIN>(&mut self, _pin: &mut PIN)
+                where
+                    PIN: Channel<$ADC, ID = u8>,
+                {
+                    self.start_channel_conversion(PIN::channel());
+                }
+
+                /// Reads the result of the conversion started with `start_conversion`
+                ///
+                /// Returns `WouldBlock` while the conversion is in progress.
+                pub fn read_result(&mut self) -> nb::Result<u16, Infallible> {
+                    if self.rb.sr.read().eoc().bit_is_set() {
+                        Ok(self.rb.dr.read().data().bits())
+                    } else {
+                        Err(nb::Error::WouldBlock)
+                    }
                 }
 
                 /// Powers down the ADC, disables the ADC clock and releases the ADC Peripheral
@@ -421,6 +802,7 @@ macro_rules! adc_hal {
             impl ChannelTimeSequence for Adc<$ADC> {
                 #[inline(always)]
                 fn set_channel_sample_time(&mut self, chan: u8, sample_time: SampleTime) {
+                    self.sample_times[usize::from(chan)] = sample_time;
                     self.set_channel_sample_time(chan, sample_time);
                 }
                 #[inline(always)]
@@ -535,6 +917,57 @@ impl Adc<pac::ADC1> {
     pub fn read_vref(&mut self) -> u16 {
         self.read_aux(17u8)
     }
+
+    /// Returns the temperature of the device in hundredths of °C
+    ///
+    /// The temperature sensor voltage is measured against Vrefint, compensating for the
+    /// variations of VDDA, and converted with the typical characteristics of the datasheets.
+    /// As for `read_temp`, the offset varies from chip to chip.
+    pub fn read_temperature_celsius(&mut self) -> i32 {
+        // Typical V_25 and AVG_SLOPE in tenths of mV and tenths of mV/°C
+        const V_25: i32 = 14_300;
+        const AVG_SLOPE: i32 = 43;
+
+        let (val_temp, val_vref) = self.read_aux_calibrated(true);
+        let v_sense = i32::from(val_temp) * 12_000 / i32::from(val_vref);
+
+        (V_25 - v_sense) * 100 / AVG_SLOPE + 2500
+    }
+
+    /// Returns the analog supply voltage VDDA in mV, measured against the typical 1200 mV of
+    /// Vrefint
+    pub fn read_vdda_millivolts(&mut self) -> u32 {
+        let (_, val_vref) = self.read_aux_calibrated(false);
+        1200 * 4095 / u32::from(val_vref)
+    }
+
+    // Converts the temperature sensor, if `temp`, and Vrefint with right alignment and the
+    // 17.1 µs sampling time they need
+    fn read_aux_calibrated(&mut self, temp: bool) -> (u16, u16) {
+        let prev_cfg = self.save_cfg();
+
+        let cycles_x10 = self.clocks.adcclk().raw() / 1000 * 171 / 1000;
+        let sample_time = [
+            (15, SampleTime::T_1),
+            (75, SampleTime::T_7),
+            (135, SampleTime::T_13),
+            (285, SampleTime::T_28),
+            (415, SampleTime::T_41),
+            (555, SampleTime::T_55),
+            (715, SampleTime::T_71),
+        ]
+        .iter()
+        .find(|(cycles, _)| *cycles >= cycles_x10)
+        .map_or(SampleTime::T_239, |(_, t)| *t);
+        self.set_sample_time(sample_time);
+        self.set_align(Align::Right);
+
+        let val_temp = if temp { self.read_aux(16u8) } else { 0 };
+        let val_vref = self.read_aux(17u8);
+
+        self.restore_cfg(prev_cfg);
+        (val_temp, val_vref)
+    }
 }
 
 adc_hal! {
@@ -627,7 +1060,10 @@ macro_rules! adcdma {
         impl<PINS> TransferPayload for AdcDma<$ADCX, PINS, Scan, $dmarxch> {
             fn start(&mut self) {
                 self.channel.start();
-                self.payload.adc.rb.cr2.modify(|_, w| w.adon().set_bit());
+                // With an external trigger, the conversions wait for the first trigger
+                if self.payload.adc.rb.cr2.read().extsel().is_swstart() {
+                    self.payload.adc.rb.cr2.modify(|_, w| w.adon().set_bit());
+                }
             }
             fn stop(&mut self) {
                 self.channel.stop();
@@ -645,7 +1081,8 @@ macro_rules! adcdma {
             {
                 self.rb.cr1.modify(|_, w| w.discen().clear_bit());
                 self.rb.cr2.modify(|_, w| w.align().bit(self.align.into()));
-                self.set_channel_sample_time(PIN::channel(), self.sample_time);
+                let chan = PIN::channel();
+                self.set_channel_sample_time(chan, self.sample_times[usize::from(chan)]);
                 self.rb
                     .sqr3
                     .modify(|_, w| unsafe { w.sq1().bits(PIN::channel()) });
@@ -663,13 +1100,36 @@ macro_rules! adcdma {
             }
 
             pub fn with_scan_dma<PINS>(
-                mut self,
+                self,
                 pins: PINS,
                 dma_ch: $dmarxch,
             ) -> AdcDma<$ADCX, PINS, Scan, $dmarxch>
             where
                 Self: SetChannels<PINS>,
             {
+                self.into_scan_dma(pins, dma_ch, |adc| {
+                    adc.set_samples();
+                    adc.set_sequence();
+                })
+            }
+
+            /// Converts the regular `sequence` in scan mode with DMA, `pins` being the pins
+            /// of the sequence
+            pub fn with_sequence_dma<PINS>(
+                self,
+                pins: PINS,
+                sequence: &Sequence<$ADCX>,
+                dma_ch: $dmarxch,
+            ) -> AdcDma<$ADCX, PINS, Scan, $dmarxch> {
+                self.into_scan_dma(pins, dma_ch, |adc| adc.configure_sequence(sequence))
+            }
+
+            fn into_scan_dma<PINS>(
+                mut self,
+                pins: PINS,
+                dma_ch: $dmarxch,
+                configure: impl FnOnce(&mut Self),
+            ) -> AdcDma<$ADCX, PINS, Scan, $dmarxch> {
                 self.rb.cr2.modify(|_, w| {
                     w.adon()
                         .clear_bit()
@@ -683,8 +1143,7 @@ macro_rules! adcdma {
                 self.rb
                     .cr1
                     .modify(|_, w| w.scan().set_bit().discen().clear_bit());
-                self.set_samples();
-                self.set_sequence();
+                configure(&mut self);
                 self.rb
                     .cr2
                     .modify(|_, w| w.dma().set_bit().adon().set_bit());
@@ -699,6 +1158,46 @@ macro_rules! adcdma {
                     channel: dma_ch,
                 }
             }
+
+            /// Starts continuous conversions of `pins`, filling the halves of `buffer` in turn
+            ///
+            /// The `HalfTransfer` and `TransferComplete` events of `dma_ch`, enabled with
+            /// `listen` before this call, signal the half filled last.
+            pub fn with_dma_circular<PIN, B>(
+                self,
+                pins: PIN,
+                dma_ch: $dmarxch,
+                buffer: &'static mut [B; 2],
+            ) -> CircBuffer<B, AdcDma<$ADCX, PIN, Continuous, $dmarxch>>
+            where
+                PIN: Channel<$ADCX, ID = u8>,
+                &'static mut [B; 2]: WriteBuffer<Word = u16>,
+                B: 'static,
+            {
+                crate::dma::CircReadDma::circ_read(self.with_dma(pins, dma_ch), buffer)
+            }
+
+            /// Starts continuous conversions of the sequence of `pins`, filling the halves of
+            /// `buffer` in turn
+            ///
+            /// The `HalfTransfer` and `TransferComplete` events of `dma_ch`, enabled with
+            /// `listen` before this call, signal the half filled last. The length of the halves
+            /// should be a multiple of the sequence length.
+            pub fn with_scan_dma_circular<PINS, B>(
+                self,
+                pins: PINS,
+                dma_ch: $dmarxch,
+                buffer: &'static mut [B; 2],
+            ) -> CircBuffer<B, AdcDma<$ADCX, PINS, Scan, $dmarxch>>
+            where
+                Self: SetChannels<PINS>,
+                &'static mut [B; 2]: WriteBuffer<Word = u16>,
+                B: 'static,
+            {
+                let mut adc_dma = self.with_scan_dma(pins, dma_ch);
+                adc_dma.payload.adc.set_continuous_mode(true);
+                crate::dma::CircReadDma::circ_read(adc_dma, buffer)
+            }
         }
 
         impl<PINS> AdcDma<$ADCX, PINS, Continuous, $dmarxch>
@@ -727,6 +1226,7 @@ macro_rules! adcdma {
                 payload.adc.rb.cr2.modify(|_, w| w.dma().clear_bit());
                 payload.adc.rb.cr1.modify(|_, w| w.discen().set_bit());
                 payload.adc.rb.cr1.modify(|_, w| w.scan().clear_bit());
+                payload.adc.rb.cr2.modify(|_, w| w.cont().clear_bit());
 
                 (payload.adc, payload.pins, channel)
             }
@@ -754,8 +1254,6 @@ macro_rules! adcdma {
                 self.channel.ch().cr.modify(|_, w| {
                     w.mem2mem()
                         .clear_bit()
-                        .pl()
-                        .medium()
                         .msize()
                         .bits16()
                         .psize()
@@ -792,8 +1290,6 @@ macro_rules! adcdma {
                 self.channel.ch().cr.modify(|_, w| {
                     w.mem2mem()
                         .clear_bit()
-                        .pl()
-                        .medium()
                         .msize()
                         .bits16()
                         .psize()
@@ -825,3 +1321,183 @@ adcdma! {
         dma2::C5,
     )
 }
+
+/// Dual ADC modes converting with ADC1 and ADC2 together
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DualMode {
+    /// Both ADCs convert their regular sequence simultaneously
+    RegularSimultaneous,
+    /// The ADCs convert the same channel in turn, ADC1 starting 7 ADC clock cycles after ADC2
+    FastInterleaved,
+    /// The ADCs convert the same channel in turn, ADC1 starting 14 ADC clock cycles after ADC2
+    SlowInterleaved,
+}
+
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+pub struct DualAdcPayload<PINS> {
+    adc1: Adc<pac::ADC1>,
+    adc2: Adc<pac::ADC2>,
+    pins: PINS,
+}
+
+/// ADC1 and ADC2 in a dual mode with DMA, each word holding the ADC1 result in its low half
+/// and the ADC2 result in its high half
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+pub type DualAdcDma<PINS> = RxDma<DualAdcPayload<PINS>, dma1::C1>;
+
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+impl Adc<pac::ADC1> {
+    /// Converts with ADC1 and `adc2` in the dual `mode`, transferring the results with DMA
+    ///
+    /// `sequence1` and `sequence2` must have the same length, `pins` being the pins of both
+    /// sequences. In the interleaved modes, both sequences convert the same single channel.
+    /// The conversions run continuously once the transfer is started.
+    pub fn with_dual_dma<PINS>(
+        mut self,
+        mut adc2: Adc<pac::ADC2>,
+        mode: DualMode,
+        pins: PINS,
+        sequence1: &Sequence<pac::ADC1>,
+        sequence2: &Sequence<pac::ADC2>,
+        dma_ch: dma1::C1,
+    ) -> DualAdcDma<PINS> {
+        assert_eq!(
+            sequence1.len(),
+            sequence2.len(),
+            "the sequences of the dual ADC must have the same length"
+        );
+        self.rb
+            .cr1
+            .modify(|_, w| w.scan().set_bit().discen().clear_bit());
+        adc2.rb
+            .cr1
+            .modify(|_, w| w.scan().set_bit().discen().clear_bit());
+        self.configure_sequence(sequence1);
+        adc2.configure_sequence(sequence2);
+        adc2.rb.cr2.modify(|_, w| w.align().bit(self.align.into()));
+        self.rb.cr1.modify(|_, w| {
+            w.dualmod().variant(match mode {
+                DualMode::RegularSimultaneous => DUALMOD_A::Regular,
+                DualMode::FastInterleaved => DUALMOD_A::FastInterleaved,
+                DualMode::SlowInterleaved => DUALMOD_A::SlowInterleaved,
+            })
+        });
+        self.rb
+            .cr2
+            .modify(|_, w| w.align().bit(self.align.into()).dma().set_bit());
+
+        RxDma {
+            payload: DualAdcPayload {
+                adc1: self,
+                adc2,
+                pins,
+            },
+            channel: dma_ch,
+        }
+    }
+}
+
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+impl<PINS> Receive for DualAdcDma<PINS> {
+    type RxChannel = dma1::C1;
+    type TransmittedWord = u32;
+}
+
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+impl<PINS> TransferPayload for DualAdcDma<PINS> {
+    fn start(&mut self) {
+        self.channel.start();
+        self.payload.adc2.rb.cr2.modify(|_, w| w.cont().set_bit());
+        self.payload.adc1.rb.cr2.modify(|_, w| w.cont().set_bit());
+        self.payload
+            .adc1
+            .rb
+            .cr2
+            .modify(|_, w| w.swstart().set_bit());
+    }
+    fn stop(&mut self) {
+        self.channel.stop();
+        self.payload.adc1.rb.cr2.modify(|_, w| w.cont().clear_bit());
+        self.payload.adc2.rb.cr2.modify(|_, w| w.cont().clear_bit());
+    }
+}
+
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+impl<PINS> DualAdcDma<PINS> {
+    /// Stops the conversions and returns the ADCs in independent mode, the pins and the DMA
+    /// channel
+    pub fn split(mut self) -> (Adc<pac::ADC1>, Adc<pac::ADC2>, PINS, dma1::C1) {
+        self.stop();
+
+        let DualAdcDma { payload, channel } = self;
+        let DualAdcPayload { adc1, adc2, pins } = payload;
+        adc1.rb
+            .cr1
+            .modify(|_, w| w.dualmod().variant(DUALMOD_A::Independent));
+        adc1.rb.cr2.modify(|_, w| w.dma().clear_bit());
+        adc1.rb
+            .cr1
+            .modify(|_, w| w.scan().clear_bit().discen().set_bit());
+        adc2.rb
+            .cr1
+            .modify(|_, w| w.scan().clear_bit().discen().set_bit());
+
+        (adc1, adc2, pins, channel)
+    }
+
+    fn set_dma_transfer(&mut self, ptr: *mut u32, len: usize, circular: bool) {
+        self.channel
+            .set_peripheral_address(unsafe { &(*pac::ADC1::ptr()).dr as *const _ as u32 }, false);
+        self.channel.set_memory_address(ptr as u32, true);
+        self.channel.set_transfer_length(len);
+
+        atomic::compiler_fence(Ordering::Release);
+
+        self.channel.ch().cr.modify(|_, w| {
+            w.mem2mem()
+                .clear_bit()
+                .msize()
+                .bits32()
+                .psize()
+                .bits32()
+                .circ()
+                .bit(circular)
+                .dir()
+                .clear_bit()
+        });
+    }
+}
+
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+impl<B, PINS> crate::dma::CircReadDma<B, u32> for DualAdcDma<PINS>
+where
+    &'static mut [B; 2]: WriteBuffer<Word = u32>,
+    B: 'static,
+{
+    fn circ_read(mut self, mut buffer: &'static mut [B; 2]) -> CircBuffer<B, Self> {
+        // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+        // until the end of the transfer.
+        let (ptr, len) = unsafe { buffer.write_buffer() };
+        self.set_dma_transfer(ptr, len, true);
+        self.start();
+
+        CircBuffer::new(buffer, self)
+    }
+}
+
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+impl<B, PINS> crate::dma::ReadDma<B, u32> for DualAdcDma<PINS>
+where
+    B: WriteBuffer<Word = u32>,
+{
+    fn read(mut self, mut buffer: B) -> Transfer<W, B, Self> {
+        // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+        // until the end of the transfer.
+        let (ptr, len) = unsafe { buffer.write_buffer() };
+        self.set_dma_transfer(ptr, len, false);
+        self.start();
+
+        Transfer::w(buffer, self)
+    }
+}
@@ -0,0 +1,149 @@
+//! Async ADC conversions driven by the end of conversion interrupt
+//!
+//! [`on_interrupt`] has to be called from the interrupt handler of the ADC instance:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn ADC1_2() {
+//!     adc::asynch::on_interrupt::<pac::ADC1>();
+//! }
+//! ```
+//!
+//! Dropping a conversion future waits for the conversion in progress to complete and discards
+//! its result, so the ADC is ready for the next conversion.
+
+// `poll_fn` is stable since 1.64, the `async` feature requires Rust 1.75
+#![allow(clippy::incompatible_msrv)]
+
+use super::*;
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+use cortex_m::interrupt::{self, Mutex};
+
+/// ADC instance with a task waker
+pub trait WakerInstance {
+    #[doc(hidden)]
+    fn waker() -> &'static Mutex<RefCell<Option<Waker>>>;
+    #[doc(hidden)]
+    fn disable_interrupt();
+    #[doc(hidden)]
+    fn cancel();
+}
+
+/// Wakes the task waiting for `ADC`, to be called from the ADC interrupt handler
+pub fn on_interrupt<ADC: WakerInstance>() {
+    ADC::disable_interrupt();
+    if let Some(waker) = interrupt::free(|cs| ADC::waker().borrow(cs).take()) {
+        waker.wake();
+    }
+}
+
+/// ADC with async conversions
+pub struct AsyncAdc<ADC> {
+    adc: Adc<ADC>,
+}
+
+// Conversion in progress, completed on drop if the future is cancelled
+struct Conversion<'a, ADC: WakerInstance> {
+    adc: &'a mut Adc<ADC>,
+    done: bool,
+}
+
+impl<ADC: WakerInstance> Drop for Conversion<'_, ADC> {
+    fn drop(&mut self) {
+        if !self.done {
+            ADC::cancel();
+        }
+    }
+}
+
+macro_rules! async_adc {
+    ($ADC:ty, $WAKER:ident) => {
+        static $WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
+
+        impl WakerInstance for $ADC {
+            fn waker() -> &'static Mutex<RefCell<Option<Waker>>> {
+                &$WAKER
+            }
+
+            fn disable_interrupt() {
+                unsafe { (*<$ADC>::ptr()).cr1.modify(|_, w| w.eocie().clear_bit()) };
+            }
+
+            fn cancel() {
+                Self::disable_interrupt();
+                let adc = unsafe { &*<$ADC>::ptr() };
+                while adc.sr.read().eoc().bit_is_clear() {}
+                adc.dr.read();
+            }
+        }
+
+        impl Adc<$ADC> {
+            /// Converts into an ADC with async conversions
+            pub fn into_async(self) -> AsyncAdc<$ADC> {
+                AsyncAdc { adc: self }
+            }
+        }
+
+        impl AsyncAdc<$ADC> {
+            /// Returns the blocking ADC
+            pub fn release(self) -> Adc<$ADC> {
+                self.adc
+            }
+
+            /// Converts `pin` with the sample time of its channel
+            pub async fn read<PIN>(&mut self, _pin: &mut PIN) -> u16
+            where
+                PIN: Channel<$ADC, ID = u8>,
+            {
+                self.convert(PIN::channel()).await
+            }
+
+            /// Converts the channels of `sequence` in turn, with their sample times, into
+            /// `results`
+            ///
+            /// The conversions stop at the end of the shorter of `sequence` and `results`.
+            pub async fn read_sequence(&mut self, sequence: &Sequence<$ADC>, results: &mut [u16]) {
+                let slots = sequence.channels[..sequence.len]
+                    .iter()
+                    .zip(&sequence.sample_times);
+                for (&chan, &sample_time) in slots.clone() {
+                    self.adc.sample_times[usize::from(chan)] = sample_time;
+                }
+                for (result, (&chan, _)) in results.iter_mut().zip(slots) {
+                    *result = self.convert(chan).await;
+                }
+            }
+
+            async fn convert(&mut self, chan: u8) -> u16 {
+                self.adc.start_channel_conversion(chan);
+
+                let mut conversion = Conversion {
+                    adc: &mut self.adc,
+                    done: false,
+                };
+                poll_fn(|cx| match conversion.adc.read_result() {
+                    Ok(result) => {
+                        conversion.done = true;
+                        Poll::Ready(result)
+                    }
+                    Err(_) => {
+                        interrupt::free(|cs| {
+                            <$ADC>::waker().borrow(cs).replace(Some(cx.waker().clone()))
+                        });
+                        conversion.adc.listen(Event::EndOfConversion);
+                        Poll::Pending
+                    }
+                })
+                .await
+            }
+        }
+    };
+}
+
+async_adc!(pac::ADC1, ADC1_WAKER);
+#[cfg(any(feature = "stm32f103", feature = "connectivity"))]
+async_adc!(pac::ADC2, ADC2_WAKER);
+#[cfg(all(feature = "stm32f103", any(feature = "high", feature = "xl",),))]
+async_adc!(pac::ADC3, ADC3_WAKER);
@@ -0,0 +1,127 @@
+//! Supply voltage monitoring
+//!
+//! [`VoltageMonitor`] measures VDDA against the internal reference voltage Vrefint, or a
+//! battery through an external resistor divider, and flags the voltages below a threshold with
+//! the analog watchdog.
+//!
+//! ```ignore
+//! // Battery connected to PA1 through a 100k/47k divider
+//! let divider = Divider { top: 100_000, bottom: 47_000 };
+//! let mut monitor = VoltageMonitor::with_divider(adc1, pa1, divider);
+//! monitor.listen_below(3300);
+//! let battery = monitor.millivolts();
+//! ```
+
+use super::*;
+
+/// Resistor divider between the measured voltage and the ADC input
+///
+/// The resistances are in any unit, `top` being connected to the measured voltage and `bottom`
+/// to the ground.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divider {
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Divider {
+    fn input_millivolts(&self, millivolts: u32) -> u32 {
+        (u64::from(millivolts) * u64::from(self.top + self.bottom) / u64::from(self.bottom)) as u32
+    }
+
+    fn output_millivolts(&self, millivolts: u32) -> u32 {
+        (u64::from(millivolts) * u64::from(self.bottom) / u64::from(self.top + self.bottom)) as u32
+    }
+}
+
+/// Monitor of VDDA, or of the voltage on `PIN` through a [`Divider`]
+pub struct VoltageMonitor<PIN = ()> {
+    adc: Adc<pac::ADC1>,
+    pin: PIN,
+    input: Option<(u8, Divider)>,
+}
+
+impl VoltageMonitor {
+    /// Monitors VDDA
+    pub fn new(adc: Adc<pac::ADC1>) -> Self {
+        Self {
+            adc,
+            pin: (),
+            input: None,
+        }
+    }
+}
+
+impl<PIN> VoltageMonitor<PIN> {
+    /// Monitors the voltage on `pin` through `divider`
+    pub fn with_divider(adc: Adc<pac::ADC1>, pin: PIN, divider: Divider) -> Self
+    where
+        PIN: Channel<pac::ADC1, ID = u8>,
+    {
+        Self {
+            adc,
+            pin,
+            input: Some((PIN::channel(), divider)),
+        }
+    }
+
+    /// Measures the monitored voltage in mV
+    ///
+    /// The voltage is compared to the threshold of `listen_below` on each measurement.
+    pub fn millivolts(&mut self) -> u32 {
+        let vdda = self.adc.read_vdda_millivolts();
+        match self.input {
+            None => vdda,
+            Some((chan, divider)) => {
+                let prev_cfg = self.adc.save_cfg();
+                self.adc.set_align(Align::Right);
+                let result = u32::from(self.adc.convert(chan));
+                self.adc.restore_cfg(prev_cfg);
+                divider.input_millivolts(result * vdda / 4095)
+            }
+        }
+    }
+
+    /// Flags the measurements below `millivolts` and enables the `AnalogWatchdog` interrupt
+    ///
+    /// With a divider, the threshold is converted with the VDDA measured by this call.
+    pub fn listen_below(&mut self, millivolts: u32) {
+        let millivolts = millivolts.max(1);
+        match self.input {
+            // Vrefint results increase as VDDA decreases
+            None => {
+                let high = (1200 * 4095 / millivolts).min(4095) as u16;
+                self.adc.watchdog(WatchdogChannels::Single(17), 0, high);
+            }
+            Some((chan, divider)) => {
+                let vdda = self.adc.read_vdda_millivolts();
+                let low = (divider.output_millivolts(millivolts) * 4095 / vdda).min(4095) as u16;
+                self.adc.watchdog(WatchdogChannels::Single(chan), low, 4095);
+            }
+        }
+        self.adc.clear_watchdog();
+        self.adc.listen(Event::AnalogWatchdog);
+    }
+
+    /// Stops flagging the low voltages
+    pub fn unlisten(&mut self) {
+        self.adc.unlisten(Event::AnalogWatchdog);
+        self.adc.disable_watchdog();
+    }
+
+    /// Returns true if a measurement was below the threshold
+    pub fn is_below(&self) -> bool {
+        self.adc.is_watchdog_triggered()
+    }
+
+    /// Clears the low voltage flag
+    pub fn clear_below(&mut self) {
+        self.adc.clear_watchdog();
+    }
+
+    /// Stops the monitoring and returns the ADC and the pin
+    pub fn release(mut self) -> (Adc<pac::ADC1>, PIN) {
+        self.unlisten();
+        (self.adc, self.pin)
+    }
+}
@@ -0,0 +1,147 @@
+//! Software oversampling with DMA
+//!
+//! [`OversampledAdc`] converts a channel `N` times with DMA and accumulates the samples.
+//! Averaging reduces the noise, and oversampling by 4 to the power of `k` adds `k` bits of
+//! resolution, up to 16 bits with 256 samples.
+//!
+//! ```ignore
+//! static mut SAMPLES: [u16; 64] = [0; 64];
+//!
+//! // 64 samples give 3 extra bits
+//! let mut adc = adc1.with_oversampling(ch0, dma_ch1, unsafe { &mut SAMPLES }, 15);
+//! let value = block!(adc.read()).unwrap();
+//! ```
+
+use super::*;
+use crate::dma::ReadDma;
+
+type Samples<const N: usize> = &'static mut [u16; N];
+
+enum State<ADC, PIN, CHANNEL, const N: usize>
+where
+    AdcDma<ADC, PIN, Continuous, CHANNEL>: TransferPayload,
+{
+    Idle(AdcDma<ADC, PIN, Continuous, CHANNEL>, Samples<N>),
+    Busy(Transfer<W, Samples<N>, AdcDma<ADC, PIN, Continuous, CHANNEL>>),
+}
+
+/// Channel of `ADC` converted `N` times with DMA for each result
+pub struct OversampledAdc<ADC, PIN, CHANNEL, const N: usize>
+where
+    AdcDma<ADC, PIN, Continuous, CHANNEL>: TransferPayload,
+{
+    state: Option<State<ADC, PIN, CHANNEL, N>>,
+    shift: u32,
+}
+
+// Shift of the sum of the `N` samples giving a result of `bits` bits
+fn result_shift(samples: usize, bits: u8) -> u32 {
+    assert!(
+        samples.is_power_of_two(),
+        "the sample count is a power of two"
+    );
+    assert!((12..=16).contains(&bits), "results of 12 to 16 bits");
+    let extra_bits = u32::from(bits - 12);
+    let log2 = samples.trailing_zeros();
+    assert!(
+        log2 >= 2 * extra_bits,
+        "4 samples are needed for each extra bit"
+    );
+    log2 - extra_bits
+}
+
+macro_rules! oversampled {
+    ($ADC:ty, $dmarxch:ty) => {
+        impl Adc<$ADC> {
+            /// Converts `pin` `N` times with DMA for each result of `bits` (12 to 16) bits
+            ///
+            /// The conversions are right aligned.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `N` is not a power of two, or has less than 4 samples for each bit
+            /// above 12.
+            pub fn with_oversampling<PIN, const N: usize>(
+                mut self,
+                pin: PIN,
+                dma_ch: $dmarxch,
+                samples: Samples<N>,
+                bits: u8,
+            ) -> OversampledAdc<$ADC, PIN, $dmarxch, N>
+            where
+                PIN: Channel<$ADC, ID = u8>,
+            {
+                let shift = result_shift(N, bits);
+                self.set_align(Align::Right);
+                OversampledAdc {
+                    state: Some(State::Idle(self.with_dma(pin, dma_ch), samples)),
+                    shift,
+                }
+            }
+        }
+
+        impl<PIN, const N: usize> OversampledAdc<$ADC, PIN, $dmarxch, N> {
+            /// Starts the conversions of the next result, if not in progress
+            pub fn start(&mut self) {
+                self.state = match self.state.take() {
+                    Some(State::Idle(adc_dma, samples)) => Some(State::Busy(adc_dma.read(samples))),
+                    state => state,
+                };
+            }
+
+            /// Returns true while the conversions are in progress
+            pub fn is_busy(&self) -> bool {
+                matches!(self.state, Some(State::Busy(_)))
+            }
+
+            /// Reads the result of the conversions, starting them if needed
+            ///
+            /// Returns `WouldBlock` until the `N` conversions are complete.
+            pub fn read(&mut self) -> nb::Result<u16, Infallible> {
+                match self.state.take() {
+                    Some(State::Busy(transfer)) if transfer.is_done() => {
+                        let (samples, adc_dma) = transfer.wait();
+                        let sum: u32 = samples.iter().map(|&s| u32::from(s)).sum();
+                        self.state = Some(State::Idle(adc_dma, samples));
+                        Ok((sum >> self.shift) as u16)
+                    }
+                    state => {
+                        self.state = state;
+                        self.start();
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+            }
+
+            /// Waits for the conversions in progress and returns the ADC with DMA and the sample
+            /// buffer
+            pub fn release(mut self) -> (AdcDma<$ADC, PIN, Continuous, $dmarxch>, Samples<N>) {
+                match self.state.take() {
+                    Some(State::Idle(adc_dma, samples)) => (adc_dma, samples),
+                    Some(State::Busy(transfer)) => {
+                        let (samples, adc_dma) = transfer.wait();
+                        (adc_dma, samples)
+                    }
+                    None => unreachable!(),
+                }
+            }
+        }
+    };
+}
+
+oversampled!(pac::ADC1, dma1::C1);
+#[cfg(all(feature = "stm32f103", any(feature = "high", feature = "xl",),))]
+oversampled!(pac::ADC3, dma2::C5);
+
+#[test]
+fn result_shifts() {
+    assert_eq!(result_shift(4, 12), 2);
+    assert_eq!(result_shift(16, 14), 2);
+    assert_eq!(result_shift(256, 16), 4);
+}
+
+#[test]
+#[should_panic]
+fn result_shift_too_few_samples() {
+    result_shift(8, 14);
+}
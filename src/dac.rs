@@ -1,18 +1,86 @@
 //! # API for the Digital to Analog converter
 //!
-//! Currently only supports writing to the DR of the DAC,
-//! just a basic one-shot conversion.
+//! The DAC is available on the high-density, connectivity line and value line devices, with
+//! channel 1 on PA4 and channel 2 on PA5.
+//!
+//! ```ignore
+//! let mut dac1 = dp.DAC.constrain(gpioa.pa4.into_analog(&mut gpioa.crl));
+//! dac1.enable();
+//! dac1.set_value(2048);
+//! // 8-bit write, converted when the software trigger is pulsed
+//! dac1.set_trigger(Trigger::Software);
+//! dac1.set_value_8bit(200);
+//! dac1.trigger_software();
+//! ```
+//!
+//! ## Playback with DMA
+//!
+//! The samples of a buffer are converted at the rate of a TIM6 or TIM7 update event, for
+//! audio output or arbitrary waveforms. Channel 1 uses DMA2 channel 3, channel 2 DMA2
+//! channel 4, DMA1 channels 3 and 4 on the low and medium-density value line.
+//!
+//! ```ignore
+//! static SINE: [u16; 32] = [/* 12-bit samples */];
+//!
+//! let mut timer = dp.TIM6.counter_hz(&clocks);
+//! let dac_dma = dac1.with_dma(dma2.3, &mut timer, 8.kHz()).unwrap();
+//! // Plays the waveform continuously
+//! let transfer = dac_dma.write_circular(&SINE);
+//! ```
 #![deny(unused_imports)]
 
+use core::sync::atomic::{self, Ordering};
+use embedded_dma::ReadBuffer;
+
+// DMA2 on the high-density value line, AFIO_MAPR2 TIM67_DAC_DMA_REMAP not being set
+#[cfg(all(feature = "stm32f100", not(feature = "high")))]
+use crate::dma::dma1 as dac_dma;
+#[cfg(any(not(feature = "stm32f100"), feature = "high"))]
+use crate::dma::dma2 as dac_dma;
 use crate::{
+    dma::{Transfer, TransferPayload, Transmit, TxDma, R},
     gpio::{Analog, PA4, PA5},
     pac::{DAC, RCC},
     rcc::{Enable, Reset},
+    time::Hertz,
+    timer::{self, CounterHz},
 };
 
+/// DAC channel 1
 pub struct C1;
+/// DAC channel 2
 pub struct C2;
 
+/// Conversion triggers available on all the devices with a DAC
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// TIM6 TRGO
+    Tim6,
+    /// TIM7 TRGO
+    Tim7,
+    /// TIM2 TRGO
+    Tim2,
+    /// TIM4 TRGO
+    Tim4,
+    /// EXTI line 9
+    Exti9,
+    /// `trigger_software`
+    Software,
+}
+
+impl Trigger {
+    fn bits(self) -> u8 {
+        match self {
+            Trigger::Tim6 => 0b000,
+            Trigger::Tim7 => 0b010,
+            Trigger::Tim2 => 0b100,
+            Trigger::Tim4 => 0b101,
+            Trigger::Exti9 => 0b110,
+            Trigger::Software => 0b111,
+        }
+    }
+}
+
 pub trait DacOut<V> {
     fn set_value(&mut self, val: V);
     fn get_value(&mut self) -> V;
@@ -62,7 +130,7 @@ where
 }
 
 macro_rules! dac {
-    ($CX:ident, $en:ident, $cen:ident, $cal_flag:ident, $trim:ident, $mode:ident, $dhrx:ident, $dac_dor:ident, $daccxdhr:ident) => {
+    ($CX:ident, $en:ident, $boff:ident, $ten:ident, $tsel:ident, $wave:ident, $mamp:ident, $swtrig:ident, $dhr12r:ident, $dhr12l:ident, $dhr8r:ident, $dor:ident) => {
         impl DacPin for $CX {
             fn enable(&mut self) {
                 let dac = unsafe { &(*DAC::ptr()) };
@@ -73,17 +141,359 @@ macro_rules! dac {
         impl DacOut<u16> for $CX {
             fn set_value(&mut self, val: u16) {
                 let dac = unsafe { &(*DAC::ptr()) };
-                dac.$dhrx.write(|w| unsafe { w.bits(val as u32) });
+                dac.$dhr12r.write(|w| unsafe { w.bits(val as u32) });
             }
 
             fn get_value(&mut self) -> u16 {
                 let dac = unsafe { &(*DAC::ptr()) };
-                dac.$dac_dor.read().bits() as u16
+                dac.$dor.read().bits() as u16
+            }
+        }
+
+        impl $CX {
+            /// Enables the channel
+            pub fn enable(&mut self) {
+                DacPin::enable(self)
+            }
+
+            /// Disables the channel, the output is left floating
+            pub fn disable(&mut self) {
+                let dac = unsafe { &(*DAC::ptr()) };
+                dac.cr.modify(|_, w| w.$en().clear_bit());
+            }
+
+            /// Enables or disables the output buffer, enabled after reset
+            ///
+            /// The buffer reduces the output impedance to drive external loads.
+            pub fn set_output_buffer(&mut self, enabled: bool) {
+                let dac = unsafe { &(*DAC::ptr()) };
+                dac.cr.modify(|_, w| w.$boff().bit(!enabled));
+            }
+
+            /// Writes the 12-bit right-aligned `val`
+            pub fn set_value(&mut self, val: u16) {
+                DacOut::set_value(self, val)
+            }
+
+            /// Writes the 12-bit `val` left-aligned on 16 bits
+            pub fn set_value_left(&mut self, val: u16) {
+                let dac = unsafe { &(*DAC::ptr()) };
+                dac.$dhr12l.write(|w| unsafe { w.bits(val as u32) });
+            }
+
+            /// Writes the 8-bit `val`
+            pub fn set_value_8bit(&mut self, val: u8) {
+                let dac = unsafe { &(*DAC::ptr()) };
+                dac.$dhr8r.write(|w| unsafe { w.bits(val as u32) });
+            }
+
+            /// Returns the converted value
+            pub fn get_value(&mut self) -> u16 {
+                DacOut::get_value(self)
+            }
+
+            /// Converts the written values on `trigger` instead of one APB1 clock cycle after
+            /// the write
+            pub fn set_trigger(&mut self, trigger: Trigger) {
+                let dac = unsafe { &(*DAC::ptr()) };
+                #[allow(unused_unsafe)]
+                dac.cr
+                    .modify(|_, w| unsafe { w.$tsel().bits(trigger.bits()) }.$ten().set_bit());
+            }
+
+            /// Converts the written values one APB1 clock cycle after the write
+            pub fn disable_trigger(&mut self) {
+                let dac = unsafe { &(*DAC::ptr()) };
+                dac.cr.modify(|_, w| w.$ten().clear_bit());
+            }
+
+            /// Generates `wave` on each trigger, selected with `set_trigger`
+            ///
+            /// The wave is added to the written value, for dithering or signal generation.
+            pub fn set_wave(&mut self, wave: Wave) {
+                let (wave, amplitude) = wave.bits();
+                let dac = unsafe { &(*DAC::ptr()) };
+                #[allow(unused_unsafe)]
+                dac.cr
+                    .modify(|_, w| unsafe { w.$wave().bits(wave).$mamp().bits(amplitude) });
+            }
+
+            /// Converts the written value with `Trigger::Software`
+            pub fn trigger_software(&mut self) {
+                let dac = unsafe { &(*DAC::ptr()) };
+                dac.swtrigr.write(|w| w.$swtrig().set_bit());
             }
         }
     };
 }
 
+/// Amplitude of the generated waves
+///
+/// The triangle wave counts up to the amplitude, the noise unmasks the corresponding bits of
+/// the LFSR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaveAmplitude {
+    A1,
+    A3,
+    A7,
+    A15,
+    A31,
+    A63,
+    A127,
+    A255,
+    A511,
+    A1023,
+    A2047,
+    A4095,
+}
+
+/// Wave generated by the DAC, added to the written value on each trigger
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wave {
+    /// No wave generation
+    Disabled,
+    /// Pseudo-random noise
+    Noise(WaveAmplitude),
+    /// Triangle wave
+    Triangle(WaveAmplitude),
+}
+
+impl Wave {
+    fn bits(self) -> (u8, u8) {
+        match self {
+            Wave::Disabled => (0b00, 0),
+            Wave::Noise(amplitude) => (0b01, amplitude as u8),
+            Wave::Triangle(amplitude) => (0b10, amplitude as u8),
+        }
+    }
+}
+
+/// Timers triggering the DMA playback with their update event
+pub trait DacTimer: timer::Instance + Sized {
+    #[doc(hidden)]
+    const TRIGGER: Trigger;
+    #[doc(hidden)]
+    fn set_update_master(timer: &mut CounterHz<Self>);
+}
+
+macro_rules! dac_timer {
+    ($TIM:ty, $trigger:ident) => {
+        impl DacTimer for $TIM {
+            const TRIGGER: Trigger = Trigger::$trigger;
+
+            fn set_update_master(timer: &mut CounterHz<Self>) {
+                timer.set_master_mode(crate::pac::tim6::cr2::MMS_A::Update);
+            }
+        }
+    };
+}
+
+dac_timer!(crate::pac::TIM6, Tim6);
+#[cfg(any(
+    all(feature = "high", any(feature = "stm32f101", feature = "stm32f103",),),
+    any(feature = "stm32f100", feature = "connectivity",)
+))]
+dac_timer!(crate::pac::TIM7, Tim7);
+
+pub type Dac1Dma = TxDma<C1, dac_dma::C3>;
+pub type Dac2Dma = TxDma<C2, dac_dma::C4>;
+pub type DualDacDma = TxDma<DualDac, dac_dma::C3>;
+
+macro_rules! dacdma {
+    ($CX:ident, $dacdma:ident, $dmach:ty, $dmaen:ident, $dhr:ident, $word:ty, $msize:ident) => {
+        impl $CX {
+            /// Converts the samples written with DMA at `sample_rate`, the update rate of
+            /// `timer`
+            ///
+            /// The samples are 12-bit right aligned.
+            pub fn with_dma<TIM: DacTimer>(
+                mut self,
+                channel: $dmach,
+                timer: &mut CounterHz<TIM>,
+                sample_rate: Hertz,
+            ) -> Result<$dacdma, timer::Error> {
+                TIM::set_update_master(timer);
+                timer.start(sample_rate)?;
+                self.set_trigger(TIM::TRIGGER);
+                let dac = unsafe { &(*DAC::ptr()) };
+                dac.cr.modify(|_, w| w.$dmaen().set_bit());
+                self.enable();
+                Ok(TxDma {
+                    payload: self,
+                    channel,
+                })
+            }
+        }
+
+        impl $dacdma {
+            /// Stops the DMA requests and returns the channel and the DMA channel
+            pub fn release(self) -> ($CX, $dmach) {
+                let dac = unsafe { &(*DAC::ptr()) };
+                dac.cr.modify(|_, w| w.$dmaen().clear_bit());
+                (self.payload, self.channel)
+            }
+
+            /// Plays the samples of `buffer` in a loop
+            ///
+            /// The `wait` of the transfer stops the playback once the buffer has been played at
+            /// least once.
+            pub fn write_circular<B>(mut self, buffer: B) -> Transfer<R, B, Self>
+            where
+                B: ReadBuffer<Word = $word>,
+            {
+                self.set_transfer(&buffer, true);
+                Transfer::r(buffer, self)
+            }
+
+            fn set_transfer<B>(&mut self, buffer: &B, circular: bool)
+            where
+                B: ReadBuffer<Word = $word>,
+            {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.read_buffer() };
+                self.channel.set_peripheral_address(
+                    unsafe { &(*DAC::ptr()).$dhr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w.mem2mem()
+                        .clear_bit()
+                        .msize()
+                        .$msize()
+                        .psize()
+                        .bits32()
+                        .circ()
+                        .bit(circular)
+                        .dir()
+                        .set_bit()
+                });
+                self.start();
+            }
+        }
+
+        impl Transmit for $dacdma {
+            type TxChannel = $dmach;
+            type ReceivedWord = $word;
+        }
+
+        impl TransferPayload for $dacdma {
+            fn start(&mut self) {
+                self.channel.start();
+            }
+            fn stop(&mut self) {
+                self.channel.stop();
+            }
+        }
+
+        impl<B> crate::dma::WriteDma<B, $word> for $dacdma
+        where
+            B: ReadBuffer<Word = $word>,
+        {
+            /// Plays the samples of `buffer` once
+            fn write(mut self, buffer: B) -> Transfer<R, B, Self> {
+                self.set_transfer(&buffer, false);
+                Transfer::r(buffer, self)
+            }
+        }
+    };
+}
+
+dacdma!(C1, Dac1Dma, dac_dma::C3, dmaen1, dhr12r1, u16, bits16);
+dacdma!(C2, Dac2Dma, dac_dma::C4, dmaen2, dhr12r2, u16, bits16);
+dacdma!(
+    DualDac,
+    DualDacDma,
+    dac_dma::C3,
+    dmaen1,
+    dhr12rd,
+    u32,
+    bits32
+);
+
+/// Both DAC channels updated together, for phase-coherent outputs
+///
+/// The two 12-bit right-aligned values are packed in the dual registers, with channel 2 in the
+/// upper half-word, also for the DMA samples. The conversions of both channels are started by
+/// the trigger of `set_trigger`, the DMA playback uses the DMA channel of channel 1.
+pub struct DualDac {
+    c1: C1,
+    c2: C2,
+}
+
+impl DualDac {
+    /// Updates `c1` and `c2` together
+    pub fn new(c1: C1, c2: C2) -> Self {
+        Self { c1, c2 }
+    }
+
+    /// Returns the channels
+    pub fn split(self) -> (C1, C2) {
+        (self.c1, self.c2)
+    }
+
+    /// Enables both channels
+    pub fn enable(&mut self) {
+        self.c1.enable();
+        self.c2.enable();
+    }
+
+    /// Disables both channels
+    pub fn disable(&mut self) {
+        self.c1.disable();
+        self.c2.disable();
+    }
+
+    /// Writes the 12-bit right-aligned values of both channels
+    pub fn set_values(&mut self, c1: u16, c2: u16) {
+        let dac = unsafe { &(*DAC::ptr()) };
+        dac.dhr12rd
+            .write(|w| unsafe { w.bits(u32::from(c1) | u32::from(c2) << 16) });
+    }
+
+    /// Writes the 12-bit values of both channels, left-aligned on 16 bits
+    pub fn set_values_left(&mut self, c1: u16, c2: u16) {
+        let dac = unsafe { &(*DAC::ptr()) };
+        dac.dhr12ld
+            .write(|w| unsafe { w.bits(u32::from(c1) | u32::from(c2) << 16) });
+    }
+
+    /// Writes the 8-bit values of both channels
+    pub fn set_values_8bit(&mut self, c1: u8, c2: u8) {
+        let dac = unsafe { &(*DAC::ptr()) };
+        dac.dhr8rd
+            .write(|w| unsafe { w.bits(u32::from(c1) | u32::from(c2) << 8) });
+    }
+
+    /// Returns the converted values of both channels
+    pub fn get_values(&mut self) -> (u16, u16) {
+        (self.c1.get_value(), self.c2.get_value())
+    }
+
+    /// Converts the written values of both channels on `trigger`
+    pub fn set_trigger(&mut self, trigger: Trigger) {
+        self.c1.set_trigger(trigger);
+        self.c2.set_trigger(trigger);
+    }
+
+    /// Converts the written values one APB1 clock cycle after the write
+    pub fn disable_trigger(&mut self) {
+        self.c1.disable_trigger();
+        self.c2.disable_trigger();
+    }
+
+    /// Converts the written values of both channels with `Trigger::Software`
+    pub fn trigger_software(&mut self) {
+        let dac = unsafe { &(*DAC::ptr()) };
+        dac.swtrigr
+            .write(|w| w.swtrig1().set_bit().swtrig2().set_bit());
+    }
+}
+
 pub trait DacExt {
     fn constrain<PINS>(self, pins: PINS) -> PINS::Output
     where
@@ -99,5 +509,5 @@ impl DacExt for DAC {
     }
 }
 
-dac!(C1, en1, cen1, cal_flag1, otrim1, mode1, dhr12r1, dor1, dacc1dhr);
-dac!(C2, en2, cen2, cal_flag2, otrim2, mode2, dhr12r2, dor2, dacc2dhr);
+dac!(C1, en1, boff1, ten1, tsel1, wave1, mamp1, swtrig1, dhr12r1, dhr12l1, dhr8r1, dor1);
+dac!(C2, en2, boff2, ten2, tsel2, wave2, mamp2, swtrig2, dhr12r2, dhr12l2, dhr8r2, dor2);
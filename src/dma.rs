@@ -1,22 +1,199 @@
 //! # Direct Memory Access
+//!
+//! ## Interrupts
+//!
+//! The channel interrupts are enabled with `listen`, the interrupt handler checks and clears
+//! the flags of the events:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn DMA1_CHANNEL5() {
+//!     // channel is shared with the interrupt handler
+//!     if channel.is_event_triggered(Event::TransferError) {
+//!         channel.clear_event(Event::TransferError);
+//!         // the channel has been disabled by the error
+//!     }
+//!     if channel.is_event_triggered(Event::HalfTransfer) {
+//!         channel.clear_event(Event::HalfTransfer);
+//!         // process the first half of the buffer
+//!     }
+//! }
+//! ```
+//!
+//! `is_done` and `wait` of the transfers poll the flags of `Event::TransferComplete` and
+//! `Event::TransferError`, they are not to be cleared while a transfer is in progress on the
+//! channel.
+//!
+//! ## Transfer errors
+//!
+//! A bus error disables the channel and aborts the transfer. `try_wait` reports it with the
+//! resources of the transfer, the circular transfers with `Error::TransferError`:
+//!
+//! ```ignore
+//! match tx.write(&FRAME).try_wait() {
+//!     Ok((frame, tx)) => {}
+//!     Err(TransferFailure { buffer: frame, payload: tx, .. }) => { /* bad buffer address */ }
+//! }
+//! ```
+//!
+//! ## Double buffering
+//!
+//! The circular transfers fill the two halves of a `CircBuffer` in turn, `next_ready_half`
+//! returns each half once filled for processing while the DMA fills the other one:
+//!
+//! ```ignore
+//! let mut circ = adc_dma.circ_read(unsafe { &mut BUFFER });
+//! loop {
+//!     match circ.next_ready_half() {
+//!         Ok(samples) => process(samples),
+//!         Err(nb::Error::WouldBlock) => {}
+//!         Err(nb::Error::Other(Error::Overrun)) => { /* processing too slow */ }
+//!     }
+//! }
+//! ```
+//!
+//! ## Memory to memory copies
+//!
+//! Any channel copies between memory buffers in the background with `mem_copy`:
+//!
+//! ```ignore
+//! let transfer = channels.1.mem_copy(&FRAME, &mut framebuffer[..]);
+//! // ...
+//! let ((frame, framebuffer), mem_dma) = transfer.wait();
+//! ```
+//!
+//! ## DMA2
+//!
+//! The high-density and connectivity line peripherals are served by DMA2: ADC3 on channel 5,
+//! SPI3 on channels 1 and 2, UART4 on channels 3 and 5, the DAC on channels 3 and 4, and the
+//! update requests of TIM5 to TIM8 as given by `timer::UpdateDma`.
+//!
+//! ## Priorities
+//!
+//! The requests of concurrent streams are served by priority, set on the channel before its
+//! transfers:
+//!
+//! ```ignore
+//! let mut channels = dp.DMA1.split();
+//! channels.5.set_priority(Priority::VeryHigh);
+//! let rx = serial.rx.with_dma(channels.5);
+//! ```
+//!
+//! `configure` also sets the increments and the word sizes of a channel programmed directly.
+//!
+//! ## Chained transfers
+//!
+//! A transfer converted with `into_chain` queues the next buffers in a [`TransferChain`], each
+//! one started from the transfer complete interrupt once the previous one is complete.
 #![allow(dead_code)]
 
 use core::{
     marker::PhantomData,
+    mem, ptr,
     sync::atomic::{compiler_fence, Ordering},
 };
 use embedded_dma::{ReadBuffer, WriteBuffer};
 
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod chain;
+pub use chain::TransferChain;
+
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Error {
     Overrun,
+    /// Bus error on an access, the channel has been disabled
+    TransferError,
+}
+
+/// Transfer aborted by a transfer error, with its resources
+#[derive(Debug)]
+pub struct TransferFailure<BUFFER, PAYLOAD> {
+    pub error: Error,
+    pub buffer: BUFFER,
+    pub payload: PAYLOAD,
 }
 
+/// DMA channel events
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Event {
+    /// Half of the data transferred
     HalfTransfer,
+    /// All the data transferred
     TransferComplete,
+    /// Bus error on an access of the transfer, the channel is disabled
+    TransferError,
+}
+
+/// Priority of a channel, the requests of channels with equal priorities are served in the
+/// order of the channel numbers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+    VeryHigh,
+}
+
+/// Size of the words read or written by a channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WordSize {
+    Bits8,
+    Bits16,
+    Bits32,
+}
+
+/// Configuration of a channel
+///
+/// The peripherals set the increments and the word sizes of their transfers, the priority set
+/// on the channel is kept. The channels have the medium priority once split.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelConfig {
+    pub priority: Priority,
+    pub memory_increment: bool,
+    pub peripheral_increment: bool,
+    pub memory_size: WordSize,
+    pub peripheral_size: WordSize,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            priority: Priority::Medium,
+            memory_increment: true,
+            peripheral_increment: false,
+            memory_size: WordSize::Bits8,
+            peripheral_size: WordSize::Bits8,
+        }
+    }
+}
+
+impl ChannelConfig {
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn memory_increment(mut self, inc: bool) -> Self {
+        self.memory_increment = inc;
+        self
+    }
+
+    pub fn peripheral_increment(mut self, inc: bool) -> Self {
+        self.peripheral_increment = inc;
+        self
+    }
+
+    pub fn memory_size(mut self, size: WordSize) -> Self {
+        self.memory_size = size;
+        self
+    }
+
+    pub fn peripheral_size(mut self, size: WordSize) -> Self {
+        self.peripheral_size = size;
+        self
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -59,6 +236,106 @@ pub trait TransferPayload {
     fn stop(&mut self);
 }
 
+/// DMA channel, implemented by the channels of DMA1 and DMA2
+pub trait DmaChannel: crate::Sealed {
+    /// Starts the DMA transfer
+    fn start(&mut self);
+    /// Stops the DMA transfer
+    fn stop(&mut self);
+    /// Returns `true` if there's a transfer in progress
+    fn in_progress(&self) -> bool;
+    /// Number of words remaining to be transferred
+    fn get_ndtr(&self) -> u32;
+    /// Memory `address` of the transfer, incremented after each word if `inc`
+    fn set_memory_address(&mut self, address: u32, inc: bool);
+    /// Number of words to transfer
+    fn set_transfer_length(&mut self, len: usize);
+    /// Priority of the channel against the other channels of the controller
+    fn set_priority(&mut self, priority: Priority);
+    /// Applies `config` to the channel
+    fn configure(&mut self, config: &ChannelConfig);
+    /// Enables the interrupt of `event`
+    fn listen(&mut self, event: Event);
+    /// Disables the interrupt of `event`
+    fn unlisten(&mut self, event: Event);
+    /// Returns true if the flag of `event` is set
+    fn is_event_triggered(&self, event: Event) -> bool;
+    /// Clears the flag of `event`
+    fn clear_event(&mut self, event: Event);
+    #[doc(hidden)]
+    fn ch(&mut self) -> &crate::pac::dma1::CH;
+    #[doc(hidden)]
+    unsafe fn steal() -> Self;
+}
+
+/// Payload of a [`Transfer`], owning the DMA channel driving it
+///
+/// Gives the channel to the methods common to all the transfers, the transfers themselves are
+/// still started by the peripherals.
+pub trait TransferChannel: TransferPayload {
+    type Channel: DmaChannel;
+    fn channel(&self) -> &Self::Channel;
+    fn channel_mut(&mut self) -> &mut Self::Channel;
+}
+
+impl<PAYLOAD, CH: DmaChannel> TransferChannel for RxDma<PAYLOAD, CH>
+where
+    Self: TransferPayload,
+{
+    type Channel = CH;
+    fn channel(&self) -> &CH {
+        &self.channel
+    }
+    fn channel_mut(&mut self) -> &mut CH {
+        &mut self.channel
+    }
+}
+
+impl<PAYLOAD, CH: DmaChannel> TransferChannel for TxDma<PAYLOAD, CH>
+where
+    Self: TransferPayload,
+{
+    type Channel = CH;
+    fn channel(&self) -> &CH {
+        &self.channel
+    }
+    fn channel_mut(&mut self) -> &mut CH {
+        &mut self.channel
+    }
+}
+
+impl<PAYLOAD, RXCH: DmaChannel, TXCH> TransferChannel for RxTxDma<PAYLOAD, RXCH, TXCH>
+where
+    Self: TransferPayload,
+{
+    type Channel = RXCH;
+    fn channel(&self) -> &RXCH {
+        &self.rxchannel
+    }
+    fn channel_mut(&mut self) -> &mut RXCH {
+        &mut self.rxchannel
+    }
+}
+
+impl<CH: DmaChannel> TransferPayload for MemDma<CH> {
+    fn start(&mut self) {
+        self.channel.start();
+    }
+    fn stop(&mut self) {
+        self.channel.stop();
+    }
+}
+
+impl<CH: DmaChannel> TransferChannel for MemDma<CH> {
+    type Channel = CH;
+    fn channel(&self) -> &CH {
+        &self.channel
+    }
+    fn channel_mut(&mut self) -> &mut CH {
+        &mut self.channel
+    }
+}
+
 pub struct Transfer<MODE, BUFFER, PAYLOAD>
 where
     PAYLOAD: TransferPayload,
@@ -94,6 +371,8 @@ where
     }
 }
 
+/// Dropping a transfer stops its channel, the buffer and the payload are dropped with it. Use
+/// `wait` or `stop` to get them back.
 impl<MODE, BUFFER, PAYLOAD> Drop for Transfer<MODE, BUFFER, PAYLOAD>
 where
     PAYLOAD: TransferPayload,
@@ -104,6 +383,222 @@ where
     }
 }
 
+impl<MODE, BUFFER, PAYLOAD> Transfer<MODE, BUFFER, PAYLOAD>
+where
+    PAYLOAD: TransferChannel,
+{
+    /// Returns true once all the data has been transferred, or the transfer has been aborted
+    /// by a transfer error
+    pub fn is_done(&self) -> bool {
+        let channel = self.payload.channel();
+        !channel.in_progress() || channel.is_event_triggered(Event::TransferError)
+    }
+
+    /// Returns true if a transfer error has aborted the transfer
+    pub fn is_aborted(&self) -> bool {
+        self.payload
+            .channel()
+            .is_event_triggered(Event::TransferError)
+    }
+
+    /// Number of words remaining to be transferred
+    pub fn remaining(&self) -> usize {
+        self.payload.channel().get_ndtr() as usize
+    }
+
+    /// Waits for the end of the transfer and returns the buffer and the payload
+    ///
+    /// A transfer aborted by a transfer error ends early, see `try_wait`.
+    pub fn wait(self) -> (BUFFER, PAYLOAD) {
+        while !self.is_done() {}
+
+        compiler_fence(Ordering::Acquire);
+
+        self.stop()
+    }
+
+    /// Waits for the end of the transfer and returns the buffer and the payload, or
+    /// `Error::TransferError` with them if the transfer has been aborted
+    pub fn try_wait(self) -> Result<(BUFFER, PAYLOAD), TransferFailure<BUFFER, PAYLOAD>> {
+        while !self.is_done() {}
+
+        compiler_fence(Ordering::Acquire);
+
+        let aborted = self.is_aborted();
+        let (buffer, payload) = self.stop();
+        if aborted {
+            Err(TransferFailure {
+                error: Error::TransferError,
+                buffer,
+                payload,
+            })
+        } else {
+            Ok((buffer, payload))
+        }
+    }
+
+    /// Stops the transfer, complete or not, and returns the buffer and the payload
+    pub fn stop(mut self) -> (BUFFER, PAYLOAD) {
+        self.payload.stop();
+
+        // we need a read here to make the Acquire fence effective
+        // we do *not* need this if `dma.stop` does a RMW operation
+        unsafe {
+            ptr::read_volatile(&0);
+        }
+
+        // we need a fence here for the same reason we need one in `Transfer.wait`
+        compiler_fence(Ordering::Acquire);
+
+        // `Transfer` needs to have a `Drop` implementation, because we accept
+        // managed buffers that can free their memory on drop. Because of that
+        // we can't move out of the `Transfer`'s fields, so we use `ptr::read`
+        // and `mem::forget`.
+        //
+        // NOTE(unsafe) There is no panic branch between getting the resources
+        // and forgetting `self`.
+        unsafe {
+            let buffer = ptr::read(&self.buffer);
+            let payload = ptr::read(&self.payload);
+            mem::forget(self);
+            (buffer, payload)
+        }
+    }
+}
+
+impl<BUFFER, PAYLOAD, CH> Transfer<W, BUFFER, RxDma<PAYLOAD, CH>>
+where
+    RxDma<PAYLOAD, CH>: TransferPayload,
+    CH: DmaChannel,
+{
+    /// Returns the part of the buffer already received
+    pub fn peek<T>(&self) -> &[T]
+    where
+        BUFFER: AsRef<[T]>,
+    {
+        let pending = self.remaining();
+
+        let slice = self.buffer.as_ref();
+        let capacity = slice.len();
+
+        &slice[..(capacity - pending)]
+    }
+}
+
+impl<RXBUFFER, TXBUFFER, PAYLOAD, RXCH, TXCH>
+    Transfer<W, (RXBUFFER, TXBUFFER), RxTxDma<PAYLOAD, RXCH, TXCH>>
+where
+    RxTxDma<PAYLOAD, RXCH, TXCH>: TransferPayload,
+    RXCH: DmaChannel,
+{
+    /// Returns the part of the receive buffer already received
+    pub fn peek<T>(&self) -> &[T]
+    where
+        RXBUFFER: AsRef<[T]>,
+    {
+        let pending = self.remaining();
+
+        let slice = self.buffer.0.as_ref();
+        let capacity = slice.len();
+
+        &slice[..(capacity - pending)]
+    }
+}
+
+impl<B, PAYLOAD, CH> CircBuffer<B, RxDma<PAYLOAD, CH>>
+where
+    RxDma<PAYLOAD, CH>: TransferPayload,
+    CH: DmaChannel,
+{
+    /// Peeks into the readable half of the buffer
+    pub fn peek<R, F>(&mut self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&B, Half) -> R,
+    {
+        let half_being_read = self.readable_half()?;
+
+        let buf = match half_being_read {
+            Half::First => &self.buffer[0],
+            Half::Second => &self.buffer[1],
+        };
+
+        // XXX does this need a compiler barrier?
+        let ret = f(buf, half_being_read);
+
+        let channel = &self.payload.channel;
+        let first_half_is_done = channel.is_event_triggered(Event::HalfTransfer);
+        let second_half_is_done = channel.is_event_triggered(Event::TransferComplete);
+
+        if (half_being_read == Half::First && second_half_is_done)
+            || (half_being_read == Half::Second && first_half_is_done)
+        {
+            Err(Error::Overrun)
+        } else {
+            Ok(ret)
+        }
+    }
+
+    /// Returns the `Half` of the buffer that can be read
+    pub fn readable_half(&mut self) -> Result<Half, Error> {
+        let channel = &mut self.payload.channel;
+        if channel.is_event_triggered(Event::TransferError) {
+            return Err(Error::TransferError);
+        }
+        let first_half_is_done = channel.is_event_triggered(Event::HalfTransfer);
+        let second_half_is_done = channel.is_event_triggered(Event::TransferComplete);
+
+        if first_half_is_done && second_half_is_done {
+            return Err(Error::Overrun);
+        }
+
+        let last_read_half = self.readable_half;
+
+        Ok(match last_read_half {
+            Half::First => {
+                if second_half_is_done {
+                    channel.clear_event(Event::TransferComplete);
+
+                    self.readable_half = Half::Second;
+                    Half::Second
+                } else {
+                    last_read_half
+                }
+            }
+            Half::Second => {
+                if first_half_is_done {
+                    channel.clear_event(Event::HalfTransfer);
+
+                    self.readable_half = Half::First;
+                    Half::First
+                } else {
+                    last_read_half
+                }
+            }
+        })
+    }
+
+    /// Returns the half of the buffer filled since the last call
+    ///
+    /// The DMA fills the other half meanwhile, the half has to be processed before the DMA
+    /// completes the other one, which is reported as `Error::Overrun` by the next call.
+    /// Returns `WouldBlock` while the next half is not filled.
+    pub fn next_ready_half(&mut self) -> nb::Result<&mut B, Error> {
+        let last_read_half = self.readable_half;
+        match self.readable_half()? {
+            half if half == last_read_half => Err(nb::Error::WouldBlock),
+            Half::First => Ok(&mut self.buffer[0]),
+            Half::Second => Ok(&mut self.buffer[1]),
+        }
+    }
+
+    /// Stops the transfer and returns the underlying buffer and RxDma
+    pub fn stop(mut self) -> (&'static mut [B; 2], RxDma<PAYLOAD, CH>) {
+        self.payload.stop();
+
+        (self.buffer, self.payload)
+    }
+}
+
 /// Read transfer
 pub struct R;
 
@@ -116,18 +611,24 @@ macro_rules! dma {
             $chX:ident,
             $htifX:ident,
             $tcifX:ident,
+            $teifX:ident,
             $chtifX:ident,
             $ctcifX:ident,
+            $cteifX:ident,
             $cgifX:ident
         ),)+
     }),)+) => {
         $(
             pub mod $dmaX {
-                use core::{sync::atomic::{self, Ordering}, ptr, mem, convert::TryFrom};
+                use core::{sync::atomic::{self, Ordering}, mem, convert::TryFrom};
 
                 use crate::pac::{RCC, $DMAX, dma1};
 
-                use crate::dma::{CircBuffer, DmaExt, Error, Event, Half, Transfer, W, RxDma, TxDma, RxTxDma, TransferPayload};
+                use crate::dma::{
+                    ChannelConfig, DmaChannel, DmaExt, Event, Transfer, TransferPayload, MemDma,
+                    MemTransfer, MemWord, Priority, WordSize,
+                };
+                use embedded_dma::{ReadBuffer, WriteBuffer};
                 use crate::rcc::Enable;
 
                 #[allow(clippy::manual_non_exhaustive)]
@@ -161,6 +662,37 @@ macro_rules! dma {
                             self.ch().ndtr.write(|w| w.ndt().bits(u16::try_from(len).unwrap()));
                         }
 
+                        /// Priority of the channel against the other channels of the controller
+                        pub fn set_priority(&mut self, priority: Priority) {
+                            self.ch().cr.modify(|_, w| match priority {
+                                Priority::Low => w.pl().low(),
+                                Priority::Medium => w.pl().medium(),
+                                Priority::High => w.pl().high(),
+                                Priority::VeryHigh => w.pl().very_high(),
+                            });
+                        }
+
+                        /// Applies the priority, the increments and the word sizes of `config`
+                        pub fn configure(&mut self, config: &ChannelConfig) {
+                            self.set_priority(config.priority);
+                            self.ch().cr.modify(|_, w| {
+                                match config.memory_size {
+                                    WordSize::Bits8 => w.msize().bits8(),
+                                    WordSize::Bits16 => w.msize().bits16(),
+                                    WordSize::Bits32 => w.msize().bits32(),
+                                };
+                                match config.peripheral_size {
+                                    WordSize::Bits8 => w.psize().bits8(),
+                                    WordSize::Bits16 => w.psize().bits16(),
+                                    WordSize::Bits32 => w.psize().bits32(),
+                                };
+                                w.minc()
+                                    .bit(config.memory_increment)
+                                    .pinc()
+                                    .bit(config.peripheral_increment)
+                            });
+                        }
+
                         /// Starts the DMA transfer
                         pub fn start(&mut self) {
                             self.ch().cr.modify(|_, w| w.en().set_bit() );
@@ -179,15 +711,20 @@ macro_rules! dma {
                     }
 
                     impl $CX {
+                        /// Enables the interrupt of `event`
                         pub fn listen(&mut self, event: Event) {
                             match event {
                                 Event::HalfTransfer => self.ch().cr.modify(|_, w| w.htie().set_bit()),
                                 Event::TransferComplete => {
                                     self.ch().cr.modify(|_, w| w.tcie().set_bit())
                                 }
+                                Event::TransferError => {
+                                    self.ch().cr.modify(|_, w| w.teie().set_bit())
+                                }
                             }
                         }
 
+                        /// Disables the interrupt of `event`
                         pub fn unlisten(&mut self, event: Event) {
                             match event {
                                 Event::HalfTransfer => {
@@ -196,9 +733,36 @@ macro_rules! dma {
                                 Event::TransferComplete => {
                                     self.ch().cr.modify(|_, w| w.tcie().clear_bit())
                                 }
+                                Event::TransferError => {
+                                    self.ch().cr.modify(|_, w| w.teie().clear_bit())
+                                }
+                            }
+                        }
+
+                        /// Returns true if the flag of `event` is set
+                        pub fn is_event_triggered(&self, event: Event) -> bool {
+                            let isr = self.isr();
+                            match event {
+                                Event::HalfTransfer => isr.$htifX().bit_is_set(),
+                                Event::TransferComplete => isr.$tcifX().bit_is_set(),
+                                Event::TransferError => isr.$teifX().bit_is_set(),
                             }
                         }
 
+                        /// Clears the flag of `event`, to be done in the interrupt handler
+                        pub fn clear_event(&mut self, event: Event) {
+                            self.ifcr().write(|w| match event {
+                                Event::HalfTransfer => w.$chtifX().set_bit(),
+                                Event::TransferComplete => w.$ctcifX().set_bit(),
+                                Event::TransferError => w.$cteifX().set_bit(),
+                            });
+                        }
+
+                        /// Clears the flags of all the events
+                        pub fn clear_events(&mut self) {
+                            self.ifcr().write(|w| w.$cgifX().set_bit());
+                        }
+
                         pub fn ch(&mut self) -> &dma1::CH {
                             unsafe { &(*$DMAX::ptr()).$chX }
                         }
@@ -216,230 +780,110 @@ macro_rules! dma {
                             // NOTE(unsafe) atomic read with no side effects
                             unsafe { &(*$DMAX::ptr())}.$chX.ndtr.read().bits()
                         }
-                    }
 
-                    impl<B, PAYLOAD> CircBuffer<B, RxDma<PAYLOAD, $CX>>
-                    where
-                        RxDma<PAYLOAD, $CX>: TransferPayload,
-                    {
-                        /// Peeks into the readable half of the buffer
-                        pub fn peek<R, F>(&mut self, f: F) -> Result<R, Error>
-                            where
-                            F: FnOnce(&B, Half) -> R,
+                        /// Copies `src` to the start of `dst` in the background
+                        ///
+                        /// The end of the copy can be signaled by `Event::TransferComplete`.
+                        ///
+                        /// # Panics
+                        ///
+                        /// Panics if `dst` is shorter than `src`, or if `src` is longer than 65535
+                        /// words, the longest transfer of a channel.
+                        pub fn mem_copy<SRC, DST, WORD>(mut self, src: SRC, mut dst: DST) -> MemTransfer<SRC, DST, $CX>
+                        where
+                            SRC: ReadBuffer<Word = WORD>,
+                            DST: WriteBuffer<Word = WORD>,
+                            WORD: MemWord,
                         {
-                            let half_being_read = self.readable_half()?;
-
-                            let buf = match half_being_read {
-                                Half::First => &self.buffer[0],
-                                Half::Second => &self.buffer[1],
-                            };
-
-                            // XXX does this need a compiler barrier?
-                            let ret = f(buf, half_being_read);
-
-
-                            let isr = self.payload.channel.isr();
-                            let first_half_is_done = isr.$htifX().bit_is_set();
-                            let second_half_is_done = isr.$tcifX().bit_is_set();
-
-                            if (half_being_read == Half::First && second_half_is_done) ||
-                                (half_being_read == Half::Second && first_half_is_done) {
-                                Err(Error::Overrun)
-                            } else {
-                                Ok(ret)
-                            }
+                            // NOTE(unsafe) We own the buffers now and we won't call other `&mut`
+                            // on them until the end of the transfer.
+                            let (src_ptr, len) = unsafe { src.read_buffer() };
+                            let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+                            assert!(dst_len >= len, "destination shorter than the source");
+                            assert!(len <= u16::MAX as usize, "source longer than 65535 words");
+
+                            self.set_peripheral_address(src_ptr as u32, true);
+                            self.set_memory_address(dst_ptr as u32, true);
+                            self.set_transfer_length(len);
+
+                            atomic::compiler_fence(Ordering::Release);
+                            self.ch().cr.modify(|_, w| {
+                                match mem::size_of::<WORD>() {
+                                    1 => w.msize().bits8().psize().bits8(),
+                                    2 => w.msize().bits16().psize().bits16(),
+                                    _ => w.msize().bits32().psize().bits32(),
+                                };
+                                w.mem2mem()
+                                    .set_bit()
+                                    .circ()
+                                    .clear_bit()
+                                    .dir()
+                                    .clear_bit()
+                            });
+                            let mut payload = MemDma { channel: self };
+                            payload.start();
+
+                            Transfer::w((src, dst), payload)
                         }
+                    }
 
-                        /// Returns the `Half` of the buffer that can be read
-                        pub fn readable_half(&mut self) -> Result<Half, Error> {
-                            let isr = self.payload.channel.isr();
-                            let first_half_is_done = isr.$htifX().bit_is_set();
-                            let second_half_is_done = isr.$tcifX().bit_is_set();
-
-                            if first_half_is_done && second_half_is_done {
-                                return Err(Error::Overrun);
-                            }
-
-                            let last_read_half = self.readable_half;
-
-                            Ok(match last_read_half {
-                                Half::First => {
-                                    if second_half_is_done {
-                                        self.payload.channel.ifcr().write(|w| w.$ctcifX().set_bit());
+                    impl crate::Sealed for $CX {}
 
-                                        self.readable_half = Half::Second;
-                                        Half::Second
-                                    } else {
-                                        last_read_half
-                                    }
-                                }
-                                Half::Second => {
-                                    if first_half_is_done {
-                                        self.payload.channel.ifcr().write(|w| w.$chtifX().set_bit());
-
-                                        self.readable_half = Half::First;
-                                        Half::First
-                                    } else {
-                                        last_read_half
-                                    }
-                                }
-                            })
+                    impl DmaChannel for $CX {
+                        fn start(&mut self) {
+                            <$CX>::start(self)
                         }
 
-                        /// Stops the transfer and returns the underlying buffer and RxDma
-                        pub fn stop(mut self) -> (&'static mut [B; 2], RxDma<PAYLOAD, $CX>) {
-                            self.payload.stop();
-
-                            (self.buffer, self.payload)
+                        fn stop(&mut self) {
+                            <$CX>::stop(self)
                         }
-                    }
 
-                    impl<BUFFER, PAYLOAD, MODE> Transfer<MODE, BUFFER, RxDma<PAYLOAD, $CX>>
-                    where
-                        RxDma<PAYLOAD, $CX>: TransferPayload,
-                    {
-                        pub fn is_done(&self) -> bool {
-                            !self.payload.channel.in_progress()
+                        fn in_progress(&self) -> bool {
+                            <$CX>::in_progress(self)
                         }
 
-                        pub fn wait(mut self) -> (BUFFER, RxDma<PAYLOAD, $CX>) {
-                            while !self.is_done() {}
-
-                            atomic::compiler_fence(Ordering::Acquire);
-
-                            self.payload.stop();
-
-                            // we need a read here to make the Acquire fence effective
-                            // we do *not* need this if `dma.stop` does a RMW operation
-                            unsafe { ptr::read_volatile(&0); }
-
-                            // we need a fence here for the same reason we need one in `Transfer.wait`
-                            atomic::compiler_fence(Ordering::Acquire);
-
-                            // `Transfer` needs to have a `Drop` implementation, because we accept
-                            // managed buffers that can free their memory on drop. Because of that
-                            // we can't move out of the `Transfer`'s fields, so we use `ptr::read`
-                            // and `mem::forget`.
-                            //
-                            // NOTE(unsafe) There is no panic branch between getting the resources
-                            // and forgetting `self`.
-                            unsafe {
-                                let buffer = ptr::read(&self.buffer);
-                                let payload = ptr::read(&self.payload);
-                                mem::forget(self);
-                                (buffer, payload)
-                            }
+                        fn get_ndtr(&self) -> u32 {
+                            <$CX>::get_ndtr(self)
                         }
-                    }
 
-                    impl<BUFFER, PAYLOAD, MODE> Transfer<MODE, BUFFER, TxDma<PAYLOAD, $CX>>
-                    where
-                        TxDma<PAYLOAD, $CX>: TransferPayload,
-                    {
-                        pub fn is_done(&self) -> bool {
-                            !self.payload.channel.in_progress()
+                        fn set_memory_address(&mut self, address: u32, inc: bool) {
+                            <$CX>::set_memory_address(self, address, inc)
                         }
 
-                        pub fn wait(mut self) -> (BUFFER, TxDma<PAYLOAD, $CX>) {
-                            while !self.is_done() {}
-
-                            atomic::compiler_fence(Ordering::Acquire);
-
-                            self.payload.stop();
-
-                            // we need a read here to make the Acquire fence effective
-                            // we do *not* need this if `dma.stop` does a RMW operation
-                            unsafe { ptr::read_volatile(&0); }
-
-                            // we need a fence here for the same reason we need one in `Transfer.wait`
-                            atomic::compiler_fence(Ordering::Acquire);
-
-                            // `Transfer` needs to have a `Drop` implementation, because we accept
-                            // managed buffers that can free their memory on drop. Because of that
-                            // we can't move out of the `Transfer`'s fields, so we use `ptr::read`
-                            // and `mem::forget`.
-                            //
-                            // NOTE(unsafe) There is no panic branch between getting the resources
-                            // and forgetting `self`.
-                            unsafe {
-                                let buffer = ptr::read(&self.buffer);
-                                let payload = ptr::read(&self.payload);
-                                mem::forget(self);
-                                (buffer, payload)
-                            }
+                        fn set_transfer_length(&mut self, len: usize) {
+                            <$CX>::set_transfer_length(self, len)
                         }
-                    }
 
-                    impl<BUFFER, PAYLOAD, MODE, TXC> Transfer<MODE, BUFFER, RxTxDma<PAYLOAD, $CX, TXC>>
-                    where
-                        RxTxDma<PAYLOAD, $CX, TXC>: TransferPayload,
-                    {
-                        pub fn is_done(&self) -> bool {
-                            !self.payload.rxchannel.in_progress()
+                        fn set_priority(&mut self, priority: Priority) {
+                            <$CX>::set_priority(self, priority)
                         }
 
-                        pub fn wait(mut self) -> (BUFFER, RxTxDma<PAYLOAD, $CX, TXC>) {
-                            while !self.is_done() {}
-
-                            atomic::compiler_fence(Ordering::Acquire);
-
-                            self.payload.stop();
-
-                            // we need a read here to make the Acquire fence effective
-                            // we do *not* need this if `dma.stop` does a RMW operation
-                            unsafe { ptr::read_volatile(&0); }
-
-                            // we need a fence here for the same reason we need one in `Transfer.wait`
-                            atomic::compiler_fence(Ordering::Acquire);
-
-                            // `Transfer` needs to have a `Drop` implementation, because we accept
-                            // managed buffers that can free their memory on drop. Because of that
-                            // we can't move out of the `Transfer`'s fields, so we use `ptr::read`
-                            // and `mem::forget`.
-                            //
-                            // NOTE(unsafe) There is no panic branch between getting the resources
-                            // and forgetting `self`.
-                            unsafe {
-                                let buffer = ptr::read(&self.buffer);
-                                let payload = ptr::read(&self.payload);
-                                mem::forget(self);
-                                (buffer, payload)
-                            }
+                        fn configure(&mut self, config: &ChannelConfig) {
+                            <$CX>::configure(self, config)
                         }
-                    }
-
 
-                    impl<BUFFER, PAYLOAD> Transfer<W, BUFFER, RxDma<PAYLOAD, $CX>>
-                    where
-                        RxDma<PAYLOAD, $CX>: TransferPayload,
-                    {
-                        pub fn peek<T>(&self) -> &[T]
-                        where
-                            BUFFER: AsRef<[T]>,
-                        {
-                            let pending = self.payload.channel.get_ndtr() as usize;
+                        fn listen(&mut self, event: Event) {
+                            <$CX>::listen(self, event)
+                        }
 
-                            let slice = self.buffer.as_ref();
-                            let capacity = slice.len();
+                        fn unlisten(&mut self, event: Event) {
+                            <$CX>::unlisten(self, event)
+                        }
 
-                            &slice[..(capacity - pending)]
+                        fn is_event_triggered(&self, event: Event) -> bool {
+                            <$CX>::is_event_triggered(self, event)
                         }
-                    }
 
-                    impl<RXBUFFER, TXBUFFER, PAYLOAD, TXC> Transfer<W, (RXBUFFER, TXBUFFER), RxTxDma<PAYLOAD, $CX, TXC>>
-                    where
-                        RxTxDma<PAYLOAD, $CX, TXC>: TransferPayload,
-                    {
-                        pub fn peek<T>(&self) -> &[T]
-                        where
-                            RXBUFFER: AsRef<[T]>,
-                        {
-                            let pending = self.payload.rxchannel.get_ndtr() as usize;
+                        fn clear_event(&mut self, event: Event) {
+                            <$CX>::clear_event(self, event)
+                        }
 
-                            let slice = self.buffer.0.as_ref();
-                            let capacity = slice.len();
+                        fn ch(&mut self) -> &dma1::CH {
+                            <$CX>::ch(self)
+                        }
 
-                            &slice[..(capacity - pending)]
+                        unsafe fn steal() -> Self {
+                            $CX { _0: () }
                         }
                     }
                 )+
@@ -451,9 +895,10 @@ macro_rules! dma {
                         let rcc = unsafe { &(*RCC::ptr()) };
                         $DMAX::enable(rcc);
 
-                        // reset the DMA control registers (stops all on-going transfers)
+                        // reset the DMA control registers (stops all on-going transfers), with the
+                        // medium priority level
                         $(
-                            self.$chX.cr.reset();
+                            self.$chX.cr.write(|w| w.pl().medium());
                         )+
 
                         Channels((), $($CX { _0: () }),+)
@@ -468,70 +913,92 @@ dma! {
     DMA1: (dma1, {
         C1: (
             ch1,
-            htif1, tcif1,
-            chtif1, ctcif1, cgif1
+            htif1, tcif1, teif1,
+            chtif1, ctcif1, cteif1, cgif1
         ),
         C2: (
             ch2,
-            htif2, tcif2,
-            chtif2, ctcif2, cgif2
+            htif2, tcif2, teif2,
+            chtif2, ctcif2, cteif2, cgif2
         ),
         C3: (
             ch3,
-            htif3, tcif3,
-            chtif3, ctcif3, cgif3
+            htif3, tcif3, teif3,
+            chtif3, ctcif3, cteif3, cgif3
         ),
         C4: (
             ch4,
-            htif4, tcif4,
-            chtif4, ctcif4, cgif4
+            htif4, tcif4, teif4,
+            chtif4, ctcif4, cteif4, cgif4
         ),
         C5: (
             ch5,
-            htif5, tcif5,
-            chtif5, ctcif5, cgif5
+            htif5, tcif5, teif5,
+            chtif5, ctcif5, cteif5, cgif5
         ),
         C6: (
             ch6,
-            htif6, tcif6,
-            chtif6, ctcif6, cgif6
+            htif6, tcif6, teif6,
+            chtif6, ctcif6, cteif6, cgif6
         ),
         C7: (
             ch7,
-            htif7, tcif7,
-            chtif7, ctcif7, cgif7
+            htif7, tcif7, teif7,
+            chtif7, ctcif7, cteif7, cgif7
         ),
     }),
 
     DMA2: (dma2, {
         C1: (
             ch1,
-            htif1, tcif1,
-            chtif1, ctcif1, cgif1
+            htif1, tcif1, teif1,
+            chtif1, ctcif1, cteif1, cgif1
         ),
         C2: (
             ch2,
-            htif2, tcif2,
-            chtif2, ctcif2, cgif2
+            htif2, tcif2, teif2,
+            chtif2, ctcif2, cteif2, cgif2
         ),
         C3: (
             ch3,
-            htif3, tcif3,
-            chtif3, ctcif3, cgif3
+            htif3, tcif3, teif3,
+            chtif3, ctcif3, cteif3, cgif3
         ),
         C4: (
             ch4,
-            htif4, tcif4,
-            chtif4, ctcif4, cgif4
+            htif4, tcif4, teif4,
+            chtif4, ctcif4, cteif4, cgif4
         ),
         C5: (
             ch5,
-            htif5, tcif5,
-            chtif5, ctcif5, cgif5
+            htif5, tcif5, teif5,
+            chtif5, ctcif5, cteif5, cgif5
         ),
     }),
 }
 
+/// Memory to memory copier of the DMA channel `CH`
+pub struct MemDma<CH> {
+    pub channel: CH,
+}
+
+/// Copy from a `SRC` to a `DST` buffer started with `mem_copy`
+pub type MemTransfer<SRC, DST, CH> = Transfer<W, (SRC, DST), MemDma<CH>>;
+
+/// Words of the memory to memory copies, `u8`, `u16` or `u32`
+pub trait MemWord: crate::Sealed {}
+
+impl MemWord for u8 {}
+impl MemWord for u16 {}
+impl crate::Sealed for u32 {}
+impl MemWord for u32 {}
+
+/// Words of the constant tables written into 16-bit peripheral registers, `u8` or `u16`
+pub trait TableWord: MemWord {}
+
+impl TableWord for u8 {}
+impl TableWord for u16 {}
+
 /// DMA Receiver
 pub struct RxDma<PAYLOAD, RXCH> {
     pub(crate) payload: PAYLOAD,
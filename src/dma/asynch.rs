@@ -0,0 +1,142 @@
+//! Async DMA transfers driven by the channel interrupts
+//!
+//! [`on_interrupt`] has to be called from the interrupt handler of the channel:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn DMA1_CHANNEL5() {
+//!     dma::asynch::on_interrupt::<dma1::C5>();
+//! }
+//!
+//! let (buffer, rx) = rx.read(buffer).wait_async().await;
+//! ```
+//!
+//! The interrupts of the channel are disabled until the next poll, the event flags are left
+//! for the transfers.
+
+// `poll_fn` is stable since 1.64, the `async` feature requires Rust 1.75
+#![allow(clippy::incompatible_msrv)]
+
+use super::*;
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+use cortex_m::interrupt::{self, Mutex};
+
+/// DMA channel with a task waker
+pub trait WakerChannel: DmaChannel {
+    #[doc(hidden)]
+    fn waker() -> &'static Mutex<RefCell<Option<Waker>>>;
+}
+
+/// Wakes the task waiting for the channel `CH`, to be called from the channel interrupt handler
+pub fn on_interrupt<CH: WakerChannel>() {
+    // NOTE(unsafe) only the interrupt enable bits of the channel are modified
+    let mut channel = unsafe { CH::steal() };
+    for event in [
+        Event::HalfTransfer,
+        Event::TransferComplete,
+        Event::TransferError,
+    ] {
+        channel.unlisten(event);
+    }
+    if let Some(waker) = interrupt::free(|cs| CH::waker().borrow(cs).take()) {
+        waker.wake();
+    }
+}
+
+fn register<CH: WakerChannel>(channel: &mut CH, waker: &Waker, events: &[Event]) {
+    interrupt::free(|cs| CH::waker().borrow(cs).replace(Some(waker.clone())));
+    for &event in events {
+        channel.listen(event);
+    }
+}
+
+impl<MODE, BUFFER, PAYLOAD> Transfer<MODE, BUFFER, PAYLOAD>
+where
+    PAYLOAD: TransferChannel,
+    PAYLOAD::Channel: WakerChannel,
+{
+    /// Waits for the end of the transfer and returns the buffer and the payload
+    ///
+    /// A transfer error stops the transfer early, it is flagged by `Event::TransferError` on
+    /// the channel.
+    pub async fn wait_async(mut self) -> (BUFFER, PAYLOAD) {
+        poll_fn(|cx| {
+            let channel = self.payload.channel_mut();
+            if !channel.in_progress() || channel.is_event_triggered(Event::TransferError) {
+                Poll::Ready(())
+            } else {
+                register(
+                    channel,
+                    cx.waker(),
+                    &[Event::TransferComplete, Event::TransferError],
+                );
+                Poll::Pending
+            }
+        })
+        .await;
+        self.stop()
+    }
+}
+
+impl<B, PAYLOAD, CH> CircBuffer<B, RxDma<PAYLOAD, CH>>
+where
+    RxDma<PAYLOAD, CH>: TransferPayload,
+    CH: WakerChannel,
+{
+    /// Waits for the next half of the buffer to be filled and returns it
+    pub async fn next_half(&mut self) -> Result<Half, Error> {
+        let last_read_half = self.readable_half;
+        poll_fn(|cx| {
+            let channel = &mut self.payload.channel;
+            if channel.is_event_triggered(Event::HalfTransfer)
+                || channel.is_event_triggered(Event::TransferComplete)
+            {
+                Poll::Ready(())
+            } else {
+                register(
+                    channel,
+                    cx.waker(),
+                    &[Event::HalfTransfer, Event::TransferComplete],
+                );
+                Poll::Pending
+            }
+        })
+        .await;
+        match self.readable_half()? {
+            // The flag of the half we read last is set again, the other half was missed
+            half if half == last_read_half => Err(Error::Overrun),
+            half => Ok(half),
+        }
+    }
+}
+
+macro_rules! waker_channels {
+    ($($CH:ty: $WAKER:ident,)+) => {
+        $(
+            static $WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
+
+            impl WakerChannel for $CH {
+                fn waker() -> &'static Mutex<RefCell<Option<Waker>>> {
+                    &$WAKER
+                }
+            }
+        )+
+    };
+}
+
+waker_channels! {
+    dma1::C1: DMA1_C1_WAKER,
+    dma1::C2: DMA1_C2_WAKER,
+    dma1::C3: DMA1_C3_WAKER,
+    dma1::C4: DMA1_C4_WAKER,
+    dma1::C5: DMA1_C5_WAKER,
+    dma1::C6: DMA1_C6_WAKER,
+    dma1::C7: DMA1_C7_WAKER,
+    dma2::C1: DMA2_C1_WAKER,
+    dma2::C2: DMA2_C2_WAKER,
+    dma2::C3: DMA2_C3_WAKER,
+    dma2::C4: DMA2_C4_WAKER,
+    dma2::C5: DMA2_C5_WAKER,
+}
@@ -0,0 +1,217 @@
+//! Chained DMA transfers
+//!
+//! A [`TransferChain`] queues buffers behind a transfer, the next queued buffer is programmed
+//! into the channel as soon as the current one is complete, from the transfer complete
+//! interrupt, so long or fragmented streams need no intervention of the main loop.
+//!
+//! ```ignore
+//! let mut chain: TransferChain<_, _, _, 4> = tx.write(&HEADER[..]).into_chain();
+//! chain.push(&BODY[..]).ok();
+//!
+//! #[interrupt]
+//! fn DMA1_CHANNEL4() {
+//!     // chain is shared with the interrupt handler
+//!     if chain.handle_interrupt().is_err() {
+//!         // the chain was aborted by a transfer error
+//!     }
+//! }
+//!
+//! while let Some(buffer) = chain.pop_done() {
+//!     // the buffer has been transferred
+//! }
+//! ```
+
+use super::*;
+use heapless::Deque;
+
+/// Buffer of a transfer in the `MODE` direction, `R` from memory or `W` to memory
+pub trait ChainBuffer<MODE> {
+    #[doc(hidden)]
+    unsafe fn dma_buffer(&mut self) -> (u32, usize);
+}
+
+impl<B: ReadBuffer> ChainBuffer<R> for B {
+    unsafe fn dma_buffer(&mut self) -> (u32, usize) {
+        let (ptr, len) = self.read_buffer();
+        (ptr as u32, len)
+    }
+}
+
+impl<B: WriteBuffer> ChainBuffer<W> for B {
+    unsafe fn dma_buffer(&mut self) -> (u32, usize) {
+        let (ptr, len) = self.write_buffer();
+        (ptr as u32, len)
+    }
+}
+
+/// Transfers of up to `N` buffers in turn on the channel of `PAYLOAD`
+///
+/// The buffers keep the peripheral, direction and word size of the transfer the chain was
+/// created from. At most `N` buffers are held by the chain, transferred or not.
+pub struct TransferChain<MODE, BUFFER, PAYLOAD, const N: usize>
+where
+    PAYLOAD: TransferChannel,
+{
+    payload: PAYLOAD,
+    current: Option<BUFFER>,
+    queued: Deque<BUFFER, N>,
+    done: Deque<BUFFER, N>,
+    aborted: bool,
+    _mode: PhantomData<MODE>,
+}
+
+impl<MODE, BUFFER, PAYLOAD> Transfer<MODE, BUFFER, PAYLOAD>
+where
+    PAYLOAD: TransferChannel,
+    BUFFER: ChainBuffer<MODE>,
+{
+    /// Converts into a chain queueing the next buffers behind this transfer
+    ///
+    /// The chain needs the transfer complete and transfer error interrupts, which are enabled.
+    pub fn into_chain<const N: usize>(self) -> TransferChain<MODE, BUFFER, PAYLOAD, N> {
+        assert!(N > 0, "the chain holds at least one buffer");
+        // NOTE(unsafe) There is no panic branch between getting the resources and forgetting
+        // `self`, the transfer goes on in the chain.
+        let (buffer, mut payload) = unsafe {
+            let buffer = ptr::read(&self.buffer);
+            let payload = ptr::read(&self.payload);
+            mem::forget(self);
+            (buffer, payload)
+        };
+        payload.channel_mut().listen(Event::TransferComplete);
+        payload.channel_mut().listen(Event::TransferError);
+        TransferChain {
+            payload,
+            current: Some(buffer),
+            queued: Deque::new(),
+            done: Deque::new(),
+            aborted: false,
+            _mode: PhantomData,
+        }
+    }
+}
+
+impl<MODE, BUFFER, PAYLOAD, const N: usize> TransferChain<MODE, BUFFER, PAYLOAD, N>
+where
+    PAYLOAD: TransferChannel,
+    BUFFER: ChainBuffer<MODE>,
+{
+    fn len(&self) -> usize {
+        self.queued.len() + self.done.len() + usize::from(self.current.is_some())
+    }
+
+    fn start_next(&mut self) {
+        if let Some(mut buffer) = self.queued.pop_front() {
+            // NOTE(unsafe) The buffer is owned by the chain until the end of its transfer
+            let (ptr, len) = unsafe { buffer.dma_buffer() };
+            let channel = self.payload.channel_mut();
+            channel.set_memory_address(ptr, true);
+            channel.set_transfer_length(len);
+            compiler_fence(Ordering::Release);
+            channel.start();
+            self.current = Some(buffer);
+        }
+    }
+
+    /// Queues `buffer`, started at once if the chain is idle
+    ///
+    /// Returns the buffer back if the chain already holds `N` buffers or has been aborted.
+    pub fn push(&mut self, buffer: BUFFER) -> Result<(), BUFFER> {
+        if self.aborted || self.len() == N {
+            return Err(buffer);
+        }
+        self.queued.push_back(buffer).ok();
+        if self.current.is_none() {
+            self.start_next();
+        }
+        Ok(())
+    }
+
+    /// Starts the next queued buffer once the current one is complete, to be called from the
+    /// interrupt handler of the channel
+    ///
+    /// On a transfer error the chain is aborted: the current buffer is moved to the
+    /// transferred buffers, whole or not, the queued buffers are not started and
+    /// `Error::TransferError` is returned. The buffers are then returned by `release`.
+    pub fn handle_interrupt(&mut self) -> Result<(), Error> {
+        if self.current.is_none() {
+            return Ok(());
+        }
+        if self
+            .payload
+            .channel()
+            .is_event_triggered(Event::TransferError)
+        {
+            // The channel has been disabled by the error
+            self.payload.channel_mut().stop();
+            compiler_fence(Ordering::Acquire);
+            if let Some(buffer) = self.current.take() {
+                self.done.push_back(buffer).ok();
+            }
+            self.aborted = true;
+            return Err(Error::TransferError);
+        }
+        if self.payload.channel().in_progress() {
+            return Ok(());
+        }
+        // Clears the flags and disables the channel to reprogram it
+        self.payload.channel_mut().stop();
+        compiler_fence(Ordering::Acquire);
+        if let Some(buffer) = self.current.take() {
+            self.done.push_back(buffer).ok();
+        }
+        self.start_next();
+        Ok(())
+    }
+
+    /// Returns the oldest transferred buffer
+    pub fn pop_done(&mut self) -> Option<BUFFER> {
+        self.done.pop_front()
+    }
+
+    /// Returns true if no buffer is being transferred
+    pub fn is_idle(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// Returns true if the chain has been aborted by a transfer error
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Stops the transfers and returns the payload and all the buffers, transferred first
+    pub fn release(mut self) -> (PAYLOAD, Deque<BUFFER, N>) {
+        self.payload.channel_mut().unlisten(Event::TransferComplete);
+        self.payload.channel_mut().unlisten(Event::TransferError);
+        self.payload.stop();
+        compiler_fence(Ordering::Acquire);
+        let mut buffers = Deque::new();
+        // NOTE(unsafe) There is no panic branch between getting the resources and forgetting
+        // `self`.
+        let (payload, current, mut queued, mut done) = unsafe {
+            let payload = ptr::read(&self.payload);
+            let current = ptr::read(&self.current);
+            let queued = ptr::read(&self.queued);
+            let done = ptr::read(&self.done);
+            mem::forget(self);
+            (payload, current, queued, done)
+        };
+        let all = core::iter::from_fn(|| done.pop_front())
+            .chain(current)
+            .chain(core::iter::from_fn(|| queued.pop_front()));
+        for buffer in all {
+            buffers.push_back(buffer).ok();
+        }
+        (payload, buffers)
+    }
+}
+
+impl<MODE, BUFFER, PAYLOAD, const N: usize> Drop for TransferChain<MODE, BUFFER, PAYLOAD, N>
+where
+    PAYLOAD: TransferChannel,
+{
+    fn drop(&mut self) {
+        self.payload.stop();
+        compiler_fence(Ordering::SeqCst);
+    }
+}
@@ -217,7 +217,7 @@ mod sealed {
 }
 
 use sealed::Interruptable;
-use sealed::PinMode;
+pub(crate) use sealed::PinMode;
 
 impl<MODE> Interruptable for Input<MODE> {}
 impl Interruptable for Dynamic {}
@@ -762,6 +762,13 @@ where
         Pin::new()
     }
 
+    /// Configures the pin to operate in mode `M`, e.g. `Input<Floating>` or `Output<OpenDrain>`
+    #[inline]
+    pub fn into_mode<M: PinMode>(mut self, cr: &mut <Self as HL>::Cr) -> Pin<P, N, M> {
+        self.mode::<M>(cr);
+        Pin::new()
+    }
+
     /// Configures the pin to operate as a floating input pin
     #[inline]
     pub fn into_floating_input(mut self, cr: &mut <Self as HL>::Cr) -> Pin<P, N, Input<Floating>> {
@@ -931,6 +938,27 @@ where
     impl_temp_input!(as_pull_down_input, Input<PullDown>);
 }
 
+impl<const P: char, const N: u8, MODE: PinMode> Pin<P, N, MODE>
+where
+    Self: HL,
+{
+    /// Temporarily changes the pin to an open-drain output set to `state`, and gives `cr` back
+    /// to `f` to change other pins of the port. The pin is switched back to `MODE` afterwards.
+    #[inline]
+    pub(crate) fn with_open_drain_output<R>(
+        &mut self,
+        cr: &mut <Self as HL>::Cr,
+        state: PinState,
+        f: impl FnOnce(&mut Pin<P, N, Output<OpenDrain>>, &mut <Self as HL>::Cr) -> R,
+    ) -> R {
+        self._set_state(state);
+        self.mode::<Output<OpenDrain>>(cr);
+        let ret = f(&mut Pin::new(), cr);
+        self.mode::<MODE>(cr);
+        ret
+    }
+}
+
 impl<const P: char, const N: u8, MODE> Pin<P, N, MODE>
 where
     Self: HL,
@@ -5,8 +5,9 @@
 // https://www.st.com/content/ccc/resource/technical/document/application_note/5d/ae/a3/6f/08/69/4e/9b/CD00209826.pdf/files/CD00209826.pdf/jcr:content/translations/en.CD00209826.pdf
 
 use crate::afio::MAPR;
-use crate::gpio::{self, Alternate, OpenDrain};
-use crate::hal::blocking::i2c::{Read, Write, WriteRead};
+use crate::gpio::{self, Alternate, OpenDrain, Output, PinState};
+use crate::hal::blocking::delay::DelayUs;
+use crate::hal::blocking::i2c::{Read, Write, WriteIter, WriteIterRead, WriteRead};
 use crate::pac::{DWT, I2C1, I2C2, RCC};
 use crate::rcc::{BusClock, Clocks, Enable, Reset};
 use crate::time::{kHz, Hertz};
@@ -14,6 +15,10 @@ use core::ops::Deref;
 
 pub mod blocking;
 pub use blocking::BlockingI2c;
+pub mod nonblocking;
+pub use nonblocking::NonBlockingI2c;
+pub mod shared;
+pub use shared::{I2cBus, I2cProxy};
 
 /// I2C error
 #[derive(Debug, Eq, PartialEq)]
@@ -27,9 +32,52 @@ pub enum Error {
     Acknowledge,
     /// Overrun/underrun
     Overrun,
-    // Pec, // SMBUS mode only
+    /// PEC error in reception (SMBus mode only)
+    Pec,
     Timeout,
-    // Alert, // SMBUS mode only
+    /// SMBus alert (SMBus mode only)
+    Alert,
+}
+
+impl embedded_hal_one::i2c::Error for Error {
+    fn kind(&self) -> embedded_hal_one::i2c::ErrorKind {
+        use embedded_hal_one::i2c::{ErrorKind, NoAcknowledgeSource};
+        match self {
+            Self::Bus => ErrorKind::Bus,
+            Self::Arbitration => ErrorKind::ArbitrationLoss,
+            Self::Acknowledge => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+            Self::Overrun => ErrorKind::Overrun,
+            Self::Pec | Self::Timeout | Self::Alert => ErrorKind::Other,
+        }
+    }
+}
+
+/// I2C configuration error
+#[derive(Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// Bus frequency above 100 kHz in standard mode or above 400 kHz in fast mode, or too high
+    /// to be generated from PCLK1
+    FrequencyTooHigh,
+    /// Bus frequency too low to be generated from PCLK1
+    FrequencyTooLow,
+    /// PCLK1 below 2 MHz in standard mode or below 4 MHz in fast mode
+    Pclk1TooLow,
+}
+
+/// I2C slave address
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Address {
+    /// 7-bit address
+    SevenBit(u8),
+    /// 10-bit address
+    TenBit(u16),
+}
+
+impl From<u8> for Address {
+    fn from(addr: u8) -> Self {
+        Self::SevenBit(addr)
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -49,6 +97,53 @@ pub enum Mode {
     },
 }
 
+/// SMBus role of the interface
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SmbusType {
+    /// SMBus device
+    Device,
+    /// SMBus host
+    Host,
+}
+
+/// Interrupt event
+///
+/// The events share three interrupt enable bits: `Sb`, `Addr`, `Add10`, `Stopf` and `Btf`
+/// are covered by the event interrupt, `Rxne` and `Txe` additionally need the buffer
+/// interrupt, and the remaining events are covered by the error interrupt. Unlistening an
+/// event also disables the events sharing its enable bit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// START condition generated
+    Sb,
+    /// Address sent or matched
+    Addr,
+    /// 10-bit header sent
+    Add10,
+    /// STOP condition detected (slave mode)
+    Stopf,
+    /// Byte transfer finished
+    Btf,
+    /// New data has been received
+    Rxne,
+    /// New data can be sent
+    Txe,
+    /// Bus error
+    Berr,
+    /// Arbitration loss
+    Arlo,
+    /// Acknowledge failure
+    Af,
+    /// Overrun/underrun
+    Ovr,
+    /// PEC error in reception
+    Pecerr,
+    /// Timeout or Tlow error
+    Timeout,
+    /// SMBus alert
+    Smbalert,
+}
+
 impl Mode {
     pub fn standard(frequency: Hertz) -> Self {
         Mode::Standard { frequency }
@@ -67,6 +162,33 @@ impl Mode {
             Mode::Fast { frequency, .. } => frequency,
         }
     }
+
+    /// Checks that the mode can be generated from `pclk1` (RM0008 26.6.8)
+    fn check(&self, pclk1: Hertz) -> Result<(), ConfigError> {
+        let (max_freq, min_pclk1, ccr_divider, min_ccr) = match self {
+            Mode::Standard { .. } => (kHz(100), 2_000_000, 2, 4),
+            Mode::Fast {
+                duty_cycle: DutyCycle::Ratio2to1,
+                ..
+            } => (kHz(400), 4_000_000, 3, 1),
+            Mode::Fast {
+                duty_cycle: DutyCycle::Ratio16to9,
+                ..
+            } => (kHz(400), 4_000_000, 25, 1),
+        };
+        let freq = self.get_frequency();
+        if freq > max_freq {
+            Err(ConfigError::FrequencyTooHigh)
+        } else if pclk1.raw() < min_pclk1 {
+            Err(ConfigError::Pclk1TooLow)
+        } else if freq.raw() == 0 || pclk1.raw() / (freq.raw() * ccr_divider) > 0xfff {
+            Err(ConfigError::FrequencyTooLow)
+        } else if pclk1.raw() / (freq.raw() * ccr_divider) < min_ccr {
+            Err(ConfigError::FrequencyTooHigh)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl From<Hertz> for Mode {
@@ -85,6 +207,13 @@ impl From<Hertz> for Mode {
 /// Helper trait to ensure that the correct I2C pins are used for the corresponding interface
 pub trait Pins<I2C> {
     const REMAP: bool;
+    /// Port B configuration register of the SCL and SDA pins
+    type Cr;
+
+    /// Clocks the bus out with the pins temporarily as open-drain outputs, see
+    /// [`I2c::recover`]. Returns `true` once SDA is released.
+    #[doc(hidden)]
+    fn clock_out(&mut self, cr: &mut Self::Cr, delay: &mut impl DelayUs<u16>) -> bool;
 }
 
 impl Pins<I2C1>
@@ -94,6 +223,14 @@ impl Pins<I2C1>
     )
 {
     const REMAP: bool = false;
+    type Cr = gpio::Cr<'B', false>;
+
+    fn clock_out(&mut self, cr: &mut Self::Cr, delay: &mut impl DelayUs<u16>) -> bool {
+        let (scl, sda) = self;
+        scl.with_open_drain_output(cr, PinState::High, |scl, cr| {
+            sda.with_open_drain_output(cr, PinState::High, |sda, _| clock_out(scl, sda, delay))
+        })
+    }
 }
 
 impl Pins<I2C1>
@@ -103,6 +240,14 @@ impl Pins<I2C1>
     )
 {
     const REMAP: bool = true;
+    type Cr = gpio::Cr<'B', true>;
+
+    fn clock_out(&mut self, cr: &mut Self::Cr, delay: &mut impl DelayUs<u16>) -> bool {
+        let (scl, sda) = self;
+        scl.with_open_drain_output(cr, PinState::High, |scl, cr| {
+            sda.with_open_drain_output(cr, PinState::High, |sda, _| clock_out(scl, sda, delay))
+        })
+    }
 }
 
 impl Pins<I2C2>
@@ -112,6 +257,63 @@ impl Pins<I2C2>
     )
 {
     const REMAP: bool = false;
+    type Cr = gpio::Cr<'B', true>;
+
+    fn clock_out(&mut self, cr: &mut Self::Cr, delay: &mut impl DelayUs<u16>) -> bool {
+        let (scl, sda) = self;
+        scl.with_open_drain_output(cr, PinState::High, |scl, cr| {
+            sda.with_open_drain_output(cr, PinState::High, |sda, _| clock_out(scl, sda, delay))
+        })
+    }
+}
+
+macro_rules! release_with_mode {
+    ($I2C:ident: $SCL:ident, $SDA:ident) => {
+        impl
+            I2c<
+                $I2C,
+                (
+                    gpio::$SCL<Alternate<OpenDrain>>,
+                    gpio::$SDA<Alternate<OpenDrain>>,
+                ),
+            >
+        {
+            /// Releases the I2C peripheral and the pins switched to `MODE`, e.g. to reuse
+            /// them as general purpose I/O
+            pub fn release_with_mode<MODE: gpio::PinMode>(
+                self,
+                cr: &mut <(
+                    gpio::$SCL<Alternate<OpenDrain>>,
+                    gpio::$SDA<Alternate<OpenDrain>>,
+                ) as Pins<$I2C>>::Cr,
+            ) -> ($I2C, (gpio::$SCL<MODE>, gpio::$SDA<MODE>)) {
+                let (i2c, (scl, sda)) = self.release();
+                (i2c, (scl.into_mode(cr), sda.into_mode(cr)))
+            }
+        }
+    };
+}
+
+release_with_mode!(I2C1: PB6, PB7);
+release_with_mode!(I2C1: PB8, PB9);
+release_with_mode!(I2C2: PB10, PB11);
+
+/// Transfer error counters
+#[cfg(feature = "i2c-stats")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// Missing acknowledges
+    pub nacks: u32,
+    /// Arbitration losses
+    pub arbitration_losses: u32,
+    /// Bus errors
+    pub bus_errors: u32,
+    /// Overruns/underruns
+    pub overruns: u32,
+    /// Timeouts
+    pub timeouts: u32,
+    /// Restarted START conditions and transfers
+    pub retries: u32,
 }
 
 /// I2C peripheral operating in master mode
@@ -120,6 +322,8 @@ pub struct I2c<I2C, PINS> {
     pins: PINS,
     mode: Mode,
     pclk1: Hertz,
+    #[cfg(feature = "i2c-stats")]
+    stats: Stats,
 }
 
 pub trait Instance:
@@ -145,6 +349,23 @@ impl<PINS> I2c<I2C1, PINS> {
         mapr.modify_mapr(|_, w| w.i2c1_remap().bit(PINS::REMAP));
         I2c::<I2C1, _>::configure(i2c, pins, mode, clocks)
     }
+
+    /// Creates a generic I2C1 object like [`I2c::i2c1`], returning an error if `mode`
+    /// cannot be generated with the current clock configuration
+    pub fn try_i2c1<M: Into<Mode>>(
+        i2c: I2C1,
+        pins: PINS,
+        mapr: &mut MAPR,
+        mode: M,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError>
+    where
+        PINS: Pins<I2C1>,
+    {
+        let mode = mode.into();
+        mode.check(I2C1::clock(&clocks))?;
+        Ok(Self::i2c1(i2c, pins, mapr, mode, clocks))
+    }
 }
 
 impl<PINS> I2c<I2C2, PINS> {
@@ -155,6 +376,22 @@ impl<PINS> I2c<I2C2, PINS> {
     {
         I2c::<I2C2, _>::configure(i2c, pins, mode, clocks)
     }
+
+    /// Creates a generic I2C2 object like [`I2c::i2c2`], returning an error if `mode`
+    /// cannot be generated with the current clock configuration
+    pub fn try_i2c2<M: Into<Mode>>(
+        i2c: I2C2,
+        pins: PINS,
+        mode: M,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError>
+    where
+        PINS: Pins<I2C2>,
+    {
+        let mode = mode.into();
+        mode.check(I2C2::clock(&clocks))?;
+        Ok(Self::i2c2(i2c, pins, mode, clocks))
+    }
 }
 
 impl<I2C, PINS> I2c<I2C, PINS>
@@ -177,6 +414,8 @@ where
             pins,
             mode,
             pclk1,
+            #[cfg(feature = "i2c-stats")]
+            stats: Stats::default(),
         };
         i2c.init();
         i2c
@@ -190,7 +429,6 @@ where
     /// Initializes I2C. Configures the `I2C_TRISE`, `I2C_CRX`, and `I2C_CCR` registers
     /// according to the system frequency and I2C mode.
     fn init(&mut self) {
-        let freq = self.mode.get_frequency();
         let pclk1_mhz = self.pclk1.to_MHz() as u16;
 
         self.i2c
@@ -198,6 +436,16 @@ where
             .write(|w| unsafe { w.freq().bits(pclk1_mhz as u8) });
         self.i2c.cr1.write(|w| w.pe().clear_bit());
 
+        self.set_timing();
+
+        self.i2c.cr1.modify(|_, w| w.pe().set_bit());
+    }
+
+    /// Configures the `I2C_TRISE` and `I2C_CCR` registers according to the I2C mode
+    fn set_timing(&mut self) {
+        let freq = self.mode.get_frequency();
+        let pclk1_mhz = self.pclk1.to_MHz() as u16;
+
         match self.mode {
             Mode::Standard { .. } => {
                 self.i2c
@@ -222,15 +470,46 @@ where
                 });
             }
         };
+    }
 
+    /// Changes the bus speed, keeping the rest of the configuration including the enabled
+    /// interrupts
+    ///
+    /// Must only be called while no transfer is in progress.
+    pub fn reconfigure<M: Into<Mode>>(&mut self, mode: M) -> Result<(), ConfigError> {
+        let mode = mode.into();
+        mode.check(self.pclk1)?;
+        self.mode = mode;
+
+        self.i2c.cr1.modify(|_, w| w.pe().clear_bit());
+        self.set_timing();
         self.i2c.cr1.modify(|_, w| w.pe().set_bit());
+        Ok(())
     }
 
-    /// Perform an I2C software reset
+    /// Perform an I2C software reset, keeping the configuration, the enabled interrupts and
+    /// the own addresses
     fn reset(&mut self) {
+        // ALERT, NOSTRETCH, ENGC, ENPEC, ENARP, SMBTYPE and SMBUS
+        let cr1 = self.i2c.cr1.read().bits() & 0x20fa;
+        // DMAEN, ITBUFEN, ITEVTEN and ITERREN
+        let cr2 = self.i2c.cr2.read().bits() & 0x0f00;
+        let oar1 = self.i2c.oar1.read().bits();
+        let oar2 = self.i2c.oar2.read().bits();
+
         self.i2c.cr1.write(|w| w.pe().set_bit().swrst().set_bit());
         self.i2c.cr1.reset();
         self.init();
+
+        self.i2c.cr1.modify(|_, w| w.pe().clear_bit());
+        self.i2c.oar1.write(|w| unsafe { w.bits(oar1) });
+        self.i2c.oar2.write(|w| unsafe { w.bits(oar2) });
+        self.i2c
+            .cr2
+            .modify(|r, w| unsafe { w.bits(r.bits() | cr2) });
+        self.i2c
+            .cr1
+            .modify(|r, w| unsafe { w.bits(r.bits() | cr1) }.pe().set_bit());
     }
 
     /// Generate START condition
@@ -246,11 +525,197 @@ where
             .write(|w| w.dr().bits(addr << 1 | (u8::from(read))));
     }
 
+    /// Sends the header byte of a 10-bit address (`11110xx` followed by the direction bit)
+    fn send_header_10bit(&self, addr: u16, read: bool) {
+        self.i2c.dr.write(|w| {
+            w.dr()
+                .bits(0xf0 | ((addr >> 7) as u8 & 0x06) | u8::from(read))
+        });
+    }
+
+    /// Sends the 8 least significant bits of a 10-bit address
+    fn send_addr_10bit_low(&self, addr: u16) {
+        self.i2c.dr.write(|w| w.dr().bits(addr as u8));
+    }
+
     /// Generate STOP condition
     fn send_stop(&self) {
         self.i2c.cr1.modify(|_, w| w.stop().set_bit());
     }
 
+    /// Switches the interface to SMBus mode
+    pub fn enable_smbus(&mut self, smbus_type: SmbusType) {
+        self.i2c.cr1.modify(|_, w| {
+            w.smbus()
+                .set_bit()
+                .smbtype()
+                .bit(smbus_type == SmbusType::Host)
+        });
+    }
+
+    /// Switches the interface back to I2C mode
+    pub fn disable_smbus(&mut self) {
+        self.i2c.cr1.modify(|_, w| {
+            w.smbus()
+                .clear_bit()
+                .smbtype()
+                .clear_bit()
+                .alert()
+                .clear_bit()
+        });
+    }
+
+    /// Enables hardware packet error checking
+    ///
+    /// When enabled, [`BlockingI2c`] appends the PEC byte to the last write of a transaction
+    /// and checks the PEC byte received after the last read of a transaction.
+    pub fn enable_pec(&mut self, enable: bool) {
+        self.i2c.cr1.modify(|_, w| w.enpec().bit(enable));
+    }
+
+    /// Returns `true` if hardware packet error checking is enabled
+    pub fn is_pec_enabled(&self) -> bool {
+        self.i2c.cr1.read().enpec().bit_is_set()
+    }
+
+    /// Returns the internally calculated PEC value
+    pub fn pec(&self) -> u8 {
+        self.i2c.sr2.read().pec().bits()
+    }
+
+    /// Drives the SMBA pin low (`true`) or releases it (`false`) in SMBus mode
+    pub fn set_alert(&mut self, alert: bool) {
+        self.i2c.cr1.modify(|_, w| w.alert().bit(alert));
+    }
+
+    /// Enables (default) or disables clock stretching in slave mode (`NOSTRETCH`)
+    ///
+    /// Clock stretching must stay enabled in master mode.
+    pub fn set_clock_stretching(&mut self, enable: bool) {
+        self.i2c.cr1.modify(|_, w| w.nostretch().bit(!enable));
+    }
+
+    /// Enables responding to the general call address 0x00
+    pub fn enable_general_call(&mut self, enable: bool) {
+        self.i2c.cr1.modify(|_, w| w.engc().bit(enable));
+    }
+
+    /// Sets the secondary 7-bit own address, enabling dual addressing, or disables dual
+    /// addressing with `None`
+    pub fn set_own_address2(&mut self, addr: Option<u8>) {
+        self.i2c.oar2.write(|w| match addr {
+            Some(addr) => w.add2().bits(addr).endual().dual(),
+            None => w.endual().single(),
+        });
+    }
+
+    /// Starts listening for an interrupt `event`
+    pub fn listen(&mut self, event: Event) {
+        self.i2c.cr2.modify(|_, w| match event {
+            Event::Sb | Event::Addr | Event::Add10 | Event::Stopf | Event::Btf => {
+                w.itevten().set_bit()
+            }
+            Event::Rxne | Event::Txe => w.itevten().set_bit().itbufen().set_bit(),
+            _ => w.iterren().set_bit(),
+        });
+    }
+
+    /// Stops listening for an interrupt `event`
+    pub fn unlisten(&mut self, event: Event) {
+        self.i2c.cr2.modify(|_, w| match event {
+            Event::Sb | Event::Addr | Event::Add10 | Event::Stopf | Event::Btf => {
+                w.itevten().clear_bit()
+            }
+            Event::Rxne | Event::Txe => w.itbufen().clear_bit(),
+            _ => w.iterren().clear_bit(),
+        });
+    }
+
+    /// Returns `true` if the flag of `event` is set
+    pub fn is_event_set(&self, event: Event) -> bool {
+        let sr1 = self.i2c.sr1.read();
+        match event {
+            Event::Sb => sr1.sb().bit_is_set(),
+            Event::Addr => sr1.addr().bit_is_set(),
+            Event::Add10 => sr1.add10().bit_is_set(),
+            Event::Stopf => sr1.stopf().bit_is_set(),
+            Event::Btf => sr1.btf().bit_is_set(),
+            Event::Rxne => sr1.rx_ne().bit_is_set(),
+            Event::Txe => sr1.tx_e().bit_is_set(),
+            Event::Berr => sr1.berr().bit_is_set(),
+            Event::Arlo => sr1.arlo().bit_is_set(),
+            Event::Af => sr1.af().bit_is_set(),
+            Event::Ovr => sr1.ovr().bit_is_set(),
+            Event::Pecerr => sr1.pecerr().bit_is_set(),
+            Event::Timeout => sr1.timeout().bit_is_set(),
+            Event::Smbalert => sr1.smbalert().bit_is_set(),
+        }
+    }
+
+    /// Clears the flag of `event`
+    ///
+    /// `Sb`, `Add10`, `Btf`, `Rxne` and `Txe` are only cleared by accessing the data
+    /// register and are not affected.
+    pub fn clear_event(&mut self, event: Event) {
+        match event {
+            Event::Addr => {
+                self.i2c.sr1.read();
+                self.i2c.sr2.read();
+            }
+            Event::Stopf => {
+                self.i2c.sr1.read();
+                self.i2c.cr1.modify(|_, w| w);
+            }
+            Event::Sb | Event::Add10 | Event::Btf | Event::Rxne | Event::Txe => {}
+            _ => self.i2c.sr1.modify(|_, w| match event {
+                Event::Berr => w.berr().clear_bit(),
+                Event::Arlo => w.arlo().clear_bit(),
+                Event::Af => w.af().clear_bit(),
+                Event::Ovr => w.ovr().clear_bit(),
+                Event::Pecerr => w.pecerr().clear_bit(),
+                Event::Timeout => w.timeout().clear_bit(),
+                _ => w.smbalert().clear_bit(),
+            }),
+        }
+    }
+
+    /// Returns the transfer error counters
+    #[cfg(feature = "i2c-stats")]
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Clears the transfer error counters
+    #[cfg(feature = "i2c-stats")]
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Counts `error` in the statistics
+    #[inline(always)]
+    fn record_error(&mut self, _error: &Error) {
+        #[cfg(feature = "i2c-stats")]
+        match _error {
+            Error::Acknowledge => self.stats.nacks = self.stats.nacks.wrapping_add(1),
+            Error::Arbitration => {
+                self.stats.arbitration_losses = self.stats.arbitration_losses.wrapping_add(1)
+            }
+            Error::Bus => self.stats.bus_errors = self.stats.bus_errors.wrapping_add(1),
+            Error::Overrun => self.stats.overruns = self.stats.overruns.wrapping_add(1),
+            Error::Timeout => self.stats.timeouts = self.stats.timeouts.wrapping_add(1),
+            _ => {}
+        }
+    }
+
+    /// Counts a restarted START condition or transfer in the statistics
+    #[inline(always)]
+    fn record_retry(&mut self) {
+        #[cfg(feature = "i2c-stats")]
+        {
+            self.stats.retries = self.stats.retries.wrapping_add(1);
+        }
+    }
+
     /// Releases the I2C peripheral and associated pins
     pub fn release(self) -> (I2C, PINS) {
         (self.i2c, self.pins)
@@ -261,3 +726,70 @@ where
         self.release()
     }
 }
+
+impl<I2C, PINS> I2c<I2C, PINS>
+where
+    I2C: Instance,
+    PINS: Pins<I2C>,
+{
+    /// Frees a bus where a slave holds SDA low, e.g. after a reset of the MCU in the
+    /// middle of a transfer
+    ///
+    /// The SCL and SDA pins are temporarily switched to general purpose open-drain outputs
+    /// and up to 9 clock pulses are generated until the slave releases SDA. A STOP condition
+    /// is then generated and the peripheral is reset, keeping its configuration, the enabled
+    /// interrupts and the own addresses.
+    ///
+    /// `cr` is the port B configuration register of the pins, `delay` is used to generate
+    /// the clock pulses at about 100 kHz.
+    ///
+    /// Returns `Error::Bus` if SDA is still held low afterwards.
+    pub fn recover(
+        &mut self,
+        cr: &mut PINS::Cr,
+        delay: &mut impl DelayUs<u16>,
+    ) -> Result<(), Error> {
+        self.i2c.cr1.modify(|_, w| w.pe().clear_bit());
+        let released = self.pins.clock_out(cr, delay);
+        self.reset();
+
+        if released {
+            Ok(())
+        } else {
+            Err(Error::Bus)
+        }
+    }
+}
+
+/// Generates up to 9 clock pulses until the slave releases SDA, then a STOP condition
+///
+/// Returns `true` if SDA is high afterwards.
+fn clock_out<const SCL: u8, const SDA: u8>(
+    scl: &mut gpio::Pin<'B', SCL, Output<OpenDrain>>,
+    sda: &mut gpio::Pin<'B', SDA, Output<OpenDrain>>,
+    delay: &mut impl DelayUs<u16>,
+) -> bool {
+    delay.delay_us(5);
+
+    for _ in 0..9 {
+        if sda.is_high() {
+            break;
+        }
+        scl.set_low();
+        delay.delay_us(5);
+        scl.set_high();
+        delay.delay_us(5);
+    }
+
+    // STOP condition: SDA rising while SCL is high
+    scl.set_low();
+    delay.delay_us(5);
+    sda.set_low();
+    delay.delay_us(5);
+    scl.set_high();
+    delay.delay_us(5);
+    sda.set_high();
+    delay.delay_us(5);
+
+    sda.is_high()
+}
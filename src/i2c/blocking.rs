@@ -1,22 +1,137 @@
 use super::*;
+use crate::timer::{self, Counter, SysCounter};
+use embedded_hal_one::i2c::{Operation, TenBitAddress};
+use fugit::ExtU32;
 
 /// embedded-hal compatible blocking I2C implementation
 ///
-/// **NOTE**: Before using blocking I2C, you need to enable the DWT cycle counter using the
-/// [DWT::enable_cycle_counter] method.
-pub struct BlockingI2c<I2C, PINS> {
+/// Timeouts are measured by a [`TimeoutProvider`], the DWT cycle counter by default.
+///
+/// **NOTE**: Before using blocking I2C with [`DwtTimeout`], you need to enable the DWT cycle
+/// counter using the [DWT::enable_cycle_counter] method.
+pub struct BlockingI2c<I2C, PINS, T = DwtTimeout> {
     nb: I2c<I2C, PINS>,
     start_retries: u8,
-    timeouts: DwtTimeouts,
+    timeouts: Timeouts,
+    timer: T,
+    arbitration_retries: u8,
+    arbitration_backoff: u32,
 }
 
+/// Timeouts in microseconds
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct DwtTimeouts {
+pub struct Timeouts {
     start: u32,
     addr: u32,
     data: u32,
 }
 
+#[deprecated(note = "renamed to `Timeouts`")]
+pub type DwtTimeouts = Timeouts;
+
+/// Time base used by [`BlockingI2c`] to detect timeouts
+pub trait TimeoutProvider {
+    /// Starts a new timeout of `us` microseconds
+    fn start(&mut self, us: u32);
+
+    /// Returns `true` once the timeout started last has expired
+    fn expired(&mut self) -> bool;
+}
+
+/// Timeouts measured with the DWT cycle counter
+///
+/// The timeouts are limited to 2^32 cycles, about 59 s at 72 MHz, longer timeouts are
+/// shortened to this range.
+///
+/// **NOTE**: The cycle counter must be enabled using the [DWT::enable_cycle_counter] method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DwtTimeout {
+    sysclk_mhz: u32,
+    started: u32,
+    cycles: u32,
+}
+
+impl DwtTimeout {
+    pub fn new(clocks: Clocks) -> Self {
+        Self {
+            sysclk_mhz: clocks.sysclk().to_MHz(),
+            started: 0,
+            cycles: 0,
+        }
+    }
+}
+
+impl TimeoutProvider for DwtTimeout {
+    fn start(&mut self, us: u32) {
+        self.cycles = us.saturating_mul(self.sysclk_mhz);
+        self.started = DWT::cycle_count();
+    }
+
+    fn expired(&mut self) -> bool {
+        DWT::cycle_count().wrapping_sub(self.started) >= self.cycles
+    }
+}
+
+/// Timeouts counted in polling iterations, for when no hardware time base is available
+///
+/// The duration of an iteration depends on the clock configuration and the optimization
+/// level, so `iterations_per_us` usually needs to be found by measurement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoopTimeout {
+    iterations_per_us: u32,
+    left: u32,
+}
+
+impl LoopTimeout {
+    pub fn new(iterations_per_us: u32) -> Self {
+        Self {
+            iterations_per_us,
+            left: 0,
+        }
+    }
+}
+
+impl TimeoutProvider for LoopTimeout {
+    fn start(&mut self, us: u32) {
+        self.left = us.saturating_mul(self.iterations_per_us);
+    }
+
+    fn expired(&mut self) -> bool {
+        if self.left == 0 {
+            true
+        } else {
+            self.left -= 1;
+            false
+        }
+    }
+}
+
+/// Timeouts from 1 µs up to the period range of the timer, 65536 µs for a 16-bit timer,
+/// longer timeouts are shortened to the range
+impl<TIM: timer::Instance> TimeoutProvider for Counter<TIM, 1_000_000> {
+    fn start(&mut self, us: u32) {
+        let us = us.clamp(1, TIM::max_auto_reload() + 1);
+        Counter::start(self, us.micros()).ok();
+    }
+
+    fn expired(&mut self) -> bool {
+        self.wait().is_ok()
+    }
+}
+
+/// Timeouts from 1 µs up to the 24-bit range of SysTick, 2^24 clock cycles, longer timeouts
+/// are shortened to the range
+impl TimeoutProvider for SysCounter<1_000_000> {
+    fn start(&mut self, us: u32) {
+        let max = (1 << 24) / (self.clk.raw() / 1_000_000).max(1);
+        SysCounter::start(self, us.clamp(1, max).micros()).ok();
+    }
+
+    fn expired(&mut self) -> bool {
+        self.wait().is_ok()
+    }
+}
+
 impl<PINS> BlockingI2c<I2C1, PINS> {
     /// Creates a blocking I2C1 object on pins PB6 and PB7 or PB8 and PB9 using the embedded-hal `BlockingI2c` trait.
     #[allow(clippy::too_many_arguments)]
@@ -87,27 +202,39 @@ impl<I2C, PINS> I2c<I2C, PINS> {
         data_timeout_us: u32,
         clocks: Clocks,
     ) -> BlockingI2c<I2C, PINS> {
-        let sysclk_mhz = clocks.sysclk().to_MHz();
-        BlockingI2c {
-            nb: self,
+        self.blocking_with_timeout(
+            DwtTimeout::new(clocks),
+            start_timeout_us,
             start_retries,
-            timeouts: DwtTimeouts {
-                start: start_timeout_us * sysclk_mhz,
-                addr: addr_timeout_us * sysclk_mhz,
-                data: data_timeout_us * sysclk_mhz,
-            },
-        }
+            addr_timeout_us,
+            data_timeout_us,
+        )
     }
+
     pub fn blocking_default(self, clocks: Clocks) -> BlockingI2c<I2C, PINS> {
-        let sysclk_mhz = clocks.sysclk().to_MHz();
+        self.blocking(1000, 10, 1000, 1000, clocks)
+    }
+
+    /// Generates a blocking I2C instance measuring its timeouts with `timer`
+    pub fn blocking_with_timeout<T: TimeoutProvider>(
+        self,
+        timer: T,
+        start_timeout_us: u32,
+        start_retries: u8,
+        addr_timeout_us: u32,
+        data_timeout_us: u32,
+    ) -> BlockingI2c<I2C, PINS, T> {
         BlockingI2c {
             nb: self,
-            start_retries: 10,
-            timeouts: DwtTimeouts {
-                start: 1000 * sysclk_mhz,
-                addr: 1000 * sysclk_mhz,
-                data: 1000 * sysclk_mhz,
+            start_retries,
+            timeouts: Timeouts {
+                start: start_timeout_us,
+                addr: addr_timeout_us,
+                data: data_timeout_us,
             },
+            timer,
+            arbitration_retries: 0,
+            arbitration_backoff: 0,
         }
     }
 }
@@ -128,6 +255,15 @@ macro_rules! wait_for_flag {
         } else if sr1.ovr().bit_is_set() {
             $i2c.sr1.write(|w| w.ovr().clear_bit());
             Err(Error::Overrun.into())
+        } else if sr1.pecerr().bit_is_set() {
+            $i2c.sr1.write(|w| w.pecerr().clear_bit());
+            Err(Error::Pec.into())
+        } else if sr1.timeout().bit_is_set() {
+            $i2c.sr1.write(|w| w.timeout().clear_bit());
+            Err(Error::Timeout.into())
+        } else if sr1.smbalert().bit_is_set() {
+            $i2c.sr1.write(|w| w.smbalert().clear_bit());
+            Err(Error::Alert.into())
         } else if sr1.$flag().bit_is_set() {
             Ok(())
         } else {
@@ -155,11 +291,10 @@ macro_rules! busy_wait {
     }};
 }
 
-macro_rules! busy_wait_cycles {
-    ($nb_expr:expr, $cycles:expr) => {{
-        let started = DWT::cycle_count();
-        let cycles = $cycles;
-        busy_wait!($nb_expr, DWT::cycle_count().wrapping_sub(started) >= cycles)
+macro_rules! busy_wait_timeout {
+    ($nb_expr:expr, $timer:expr, $us:expr) => {{
+        $timer.start($us);
+        busy_wait!($nb_expr, $timer.expired())
     }};
 }
 
@@ -188,9 +323,10 @@ where
     }
 }
 
-impl<I2C, PINS> BlockingI2c<I2C, PINS>
+impl<I2C, PINS, T> BlockingI2c<I2C, PINS, T>
 where
     I2C: Instance,
+    T: TimeoutProvider,
 {
     /// Check if START condition is generated. If the condition is not generated, this
     /// method returns `WouldBlock` so the program can act accordingly
@@ -217,8 +353,14 @@ where
         let mut last_ret = Ok(());
         while retries_left > 0 {
             self.nb.send_start();
-            last_ret = busy_wait_cycles!(self.wait_after_sent_start(), self.timeouts.start);
-            if last_ret.is_err() {
+            last_ret = busy_wait_timeout!(
+                self.wait_after_sent_start(),
+                self.timer,
+                self.timeouts.start
+            );
+            if let Err(e) = &last_ret {
+                self.nb.record_error(e);
+                self.nb.record_retry();
                 self.nb.reset();
             } else {
                 break;
@@ -228,79 +370,167 @@ where
         last_ret
     }
 
-    fn send_addr_and_wait(&mut self, addr: u8, read: bool) -> Result<(), Error> {
+    fn send_addr_and_wait(&mut self, addr: Address, read: bool) -> Result<(), Error> {
         self.nb.i2c.sr1.read();
-        self.nb.send_addr(addr, read);
-        let ret = busy_wait_cycles!(wait_for_flag!(self.nb.i2c, addr), self.timeouts.addr);
+        let ret = match addr {
+            Address::SevenBit(addr) => {
+                self.nb.send_addr(addr, read);
+                busy_wait_timeout!(
+                    wait_for_flag!(self.nb.i2c, addr),
+                    self.timer,
+                    self.timeouts.addr
+                )
+            }
+            Address::TenBit(addr) => self.send_addr_10bit_and_wait(addr, read),
+        };
         if ret == Err(Error::Acknowledge) {
             self.nb.send_stop();
         }
         ret
     }
 
-    fn write_bytes_and_wait(&mut self, bytes: &[u8]) -> Result<(), Error> {
+    /// Sends the 10-bit address for writing. For reading the address is then followed by a
+    /// repeated START condition and the header byte with the read bit set (RM0008 26.3.3).
+    fn send_addr_10bit_and_wait(&mut self, addr: u16, read: bool) -> Result<(), Error> {
+        self.nb.send_header_10bit(addr, false);
+        busy_wait_timeout!(
+            wait_for_flag!(self.nb.i2c, add10),
+            self.timer,
+            self.timeouts.addr
+        )?;
+        self.nb.send_addr_10bit_low(addr);
+        busy_wait_timeout!(
+            wait_for_flag!(self.nb.i2c, addr),
+            self.timer,
+            self.timeouts.addr
+        )?;
+
+        if read {
+            self.nb.i2c.sr1.read();
+            self.nb.i2c.sr2.read();
+            self.nb.send_start();
+            busy_wait_timeout!(
+                self.wait_after_sent_start(),
+                self.timer,
+                self.timeouts.start
+            )?;
+            self.nb.send_header_10bit(addr, true);
+            busy_wait_timeout!(
+                wait_for_flag!(self.nb.i2c, addr),
+                self.timer,
+                self.timeouts.addr
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Sends `bytes` after the address has been acknowledged. If this is the `last` part of
+    /// the transaction and PEC is enabled, the PEC byte is sent after the data.
+    fn write_bytes_iter_and_wait(
+        &mut self,
+        bytes: impl IntoIterator<Item = u8>,
+        last: bool,
+    ) -> Result<(), Error> {
         self.nb.i2c.sr1.read();
         self.nb.i2c.sr2.read();
 
-        self.nb.i2c.dr.write(|w| w.dr().bits(bytes[0]));
+        let mut bytes = bytes.into_iter();
+        if let Some(byte) = bytes.next() {
+            self.nb.i2c.dr.write(|w| w.dr().bits(byte));
 
-        for byte in &bytes[1..] {
-            busy_wait_cycles!(wait_for_flag!(self.nb.i2c, tx_e), self.timeouts.data)?;
-            self.nb.i2c.dr.write(|w| w.dr().bits(*byte));
+            for byte in bytes {
+                busy_wait_timeout!(
+                    wait_for_flag!(self.nb.i2c, tx_e),
+                    self.timer,
+                    self.timeouts.data
+                )?;
+                self.nb.i2c.dr.write(|w| w.dr().bits(byte));
+            }
+            if last && self.nb.is_pec_enabled() {
+                busy_wait_timeout!(
+                    wait_for_flag!(self.nb.i2c, tx_e),
+                    self.timer,
+                    self.timeouts.data
+                )?;
+                self.nb.i2c.cr1.modify(|_, w| w.pec().set_bit());
+            }
+            busy_wait_timeout!(
+                wait_for_flag!(self.nb.i2c, btf),
+                self.timer,
+                self.timeouts.data
+            )?;
         }
-        busy_wait_cycles!(wait_for_flag!(self.nb.i2c, btf), self.timeouts.data)?;
 
         Ok(())
     }
 
-    fn write_without_stop(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+    fn write_without_stop(&mut self, addr: Address, bytes: &[u8], last: bool) -> Result<(), Error> {
+        self.write_iter_without_stop(addr, bytes.iter().copied(), last)
+    }
+
+    fn write_iter_without_stop(
+        &mut self,
+        addr: Address,
+        bytes: impl IntoIterator<Item = u8>,
+        last: bool,
+    ) -> Result<(), Error> {
         self.send_start_and_wait()?;
         self.send_addr_and_wait(addr, false)?;
 
-        let ret = self.write_bytes_and_wait(bytes);
+        let ret = self.write_bytes_iter_and_wait(bytes, last);
         if ret == Err(Error::Acknowledge) {
             self.nb.send_stop();
         }
         ret
     }
-}
-
-impl<I2C, PINS> Write for BlockingI2c<I2C, PINS>
-where
-    I2C: Instance,
-{
-    type Error = Error;
-
-    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
-        self.write_without_stop(addr, bytes)?;
-        self.nb.send_stop();
-        busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
 
-        Ok(())
+    /// Generates a STOP condition after the last byte if `stop` is set, or a repeated START
+    /// condition otherwise
+    fn send_stop_or_restart(&mut self, stop: bool) {
+        if stop {
+            self.nb.send_stop();
+        } else {
+            self.nb.send_start();
+        }
     }
-}
-
-impl<I2C, PINS> Read for BlockingI2c<I2C, PINS>
-where
-    I2C: Instance,
-{
-    type Error = Error;
 
-    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
-        self.send_start_and_wait()?;
-        self.send_addr_and_wait(addr, true)?;
-
-        match buffer.len() {
+    /// Receives `len` bytes from the slave into `buffer` after the address has been
+    /// acknowledged. The transfer is finished with a STOP condition if `stop` is set,
+    /// and with a repeated START condition otherwise.
+    ///
+    /// If PEC is enabled, the PEC byte is received and checked after the data when the
+    /// transfer is finished with a STOP condition.
+    fn read_bytes_and_wait<'b>(
+        &mut self,
+        mut buffer: impl Iterator<Item = &'b mut u8>,
+        len: usize,
+        stop: bool,
+    ) -> Result<(), Error> {
+        let pec = stop && self.nb.is_pec_enabled();
+        // The received PEC byte is dropped as it is checked by the hardware
+        let len = len + usize::from(pec);
+        let mut store = |byte| {
+            if let Some(b) = buffer.next() {
+                *b = byte;
+            }
+        };
+        match len {
             1 => {
                 self.nb.i2c.cr1.modify(|_, w| w.ack().clear_bit());
                 self.nb.i2c.sr1.read();
                 self.nb.i2c.sr2.read();
-                self.nb.send_stop();
+                self.send_stop_or_restart(stop);
 
-                busy_wait_cycles!(wait_for_flag!(self.nb.i2c, rx_ne), self.timeouts.data)?;
-                buffer[0] = self.nb.i2c.dr.read().dr().bits();
+                busy_wait_timeout!(
+                    wait_for_flag!(self.nb.i2c, rx_ne),
+                    self.timer,
+                    self.timeouts.data
+                )?;
+                store(self.nb.i2c.dr.read().dr().bits());
 
-                busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                if stop {
+                    busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+                }
                 self.nb.i2c.cr1.modify(|_, w| w.ack().set_bit());
             }
             2 => {
@@ -310,14 +540,23 @@ where
                     .modify(|_, w| w.pos().set_bit().ack().set_bit());
                 self.nb.i2c.sr1.read();
                 self.nb.i2c.sr2.read();
-                self.nb.i2c.cr1.modify(|_, w| w.ack().clear_bit());
+                self.nb
+                    .i2c
+                    .cr1
+                    .modify(|_, w| w.ack().clear_bit().pec().bit(pec));
 
-                busy_wait_cycles!(wait_for_flag!(self.nb.i2c, btf), self.timeouts.data)?;
-                self.nb.send_stop();
-                buffer[0] = self.nb.i2c.dr.read().dr().bits();
-                buffer[1] = self.nb.i2c.dr.read().dr().bits();
+                busy_wait_timeout!(
+                    wait_for_flag!(self.nb.i2c, btf),
+                    self.timer,
+                    self.timeouts.data
+                )?;
+                self.send_stop_or_restart(stop);
+                store(self.nb.i2c.dr.read().dr().bits());
+                store(self.nb.i2c.dr.read().dr().bits());
 
-                busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                if stop {
+                    busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+                }
                 self.nb
                     .i2c
                     .cr1
@@ -329,47 +568,520 @@ where
                 self.nb.i2c.sr1.read();
                 self.nb.i2c.sr2.read();
 
-                let (first_bytes, last_two_bytes) = buffer.split_at_mut(buffer_len - 3);
-                for byte in first_bytes {
-                    busy_wait_cycles!(wait_for_flag!(self.nb.i2c, rx_ne), self.timeouts.data)?;
-                    *byte = self.nb.i2c.dr.read().dr().bits();
+                for _ in 0..(buffer_len - 3) {
+                    busy_wait_timeout!(
+                        wait_for_flag!(self.nb.i2c, rx_ne),
+                        self.timer,
+                        self.timeouts.data
+                    )?;
+                    store(self.nb.i2c.dr.read().dr().bits());
                 }
 
-                busy_wait_cycles!(wait_for_flag!(self.nb.i2c, btf), self.timeouts.data)?;
-                self.nb.i2c.cr1.modify(|_, w| w.ack().clear_bit());
-                last_two_bytes[0] = self.nb.i2c.dr.read().dr().bits();
-                self.nb.send_stop();
-                last_two_bytes[1] = self.nb.i2c.dr.read().dr().bits();
-                busy_wait_cycles!(wait_for_flag!(self.nb.i2c, rx_ne), self.timeouts.data)?;
-                last_two_bytes[2] = self.nb.i2c.dr.read().dr().bits();
+                busy_wait_timeout!(
+                    wait_for_flag!(self.nb.i2c, btf),
+                    self.timer,
+                    self.timeouts.data
+                )?;
+                self.nb
+                    .i2c
+                    .cr1
+                    .modify(|_, w| w.ack().clear_bit().pec().bit(pec));
+                store(self.nb.i2c.dr.read().dr().bits());
+                self.send_stop_or_restart(stop);
+                store(self.nb.i2c.dr.read().dr().bits());
+                busy_wait_timeout!(
+                    wait_for_flag!(self.nb.i2c, rx_ne),
+                    self.timer,
+                    self.timeouts.data
+                )?;
+                store(self.nb.i2c.dr.read().dr().bits());
 
-                busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+                if stop {
+                    busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+                }
                 self.nb.i2c.cr1.modify(|_, w| w.ack().set_bit());
             }
         }
 
+        if pec && self.nb.i2c.sr1.read().pecerr().bit_is_set() {
+            self.nb.i2c.sr1.write(|w| w.pecerr().clear_bit());
+            return Err(Error::Pec);
+        }
+
         Ok(())
     }
 }
 
-impl<I2C, PINS> WriteRead for BlockingI2c<I2C, PINS>
+impl<I2C, PINS, T> BlockingI2c<I2C, PINS, T>
 where
     I2C: Instance,
+    T: TimeoutProvider,
 {
-    type Error = Error;
+    /// Writes `bytes` to the slave with address `addr`
+    pub fn write(&mut self, addr: impl Into<Address>, bytes: &[u8]) -> Result<(), Error> {
+        let addr = addr.into();
+        self.retry_on_arbitration(|i2c| i2c.write_once(addr, bytes))
+    }
 
-    fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+    /// Reads enough bytes from the slave with address `addr` to fill `buffer`
+    pub fn read(&mut self, addr: impl Into<Address>, buffer: &mut [u8]) -> Result<(), Error> {
+        let addr = addr.into();
+        self.retry_on_arbitration(|i2c| i2c.read_once(addr, buffer))
+    }
+
+    /// Writes `bytes` to the slave with address `addr` and then reads enough bytes to fill
+    /// `buffer` in a single transaction
+    pub fn write_read(
+        &mut self,
+        addr: impl Into<Address>,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        let addr = addr.into();
+        self.retry_on_arbitration(|i2c| i2c.write_read_once(addr, bytes, buffer))
+    }
+
+    /// Writes the bytes yielded by `bytes` to the slave with address `addr`
+    ///
+    /// As the iterator is consumed, the transfer is not restarted after an arbitration loss.
+    pub fn write_iter(
+        &mut self,
+        addr: impl Into<Address>,
+        bytes: impl IntoIterator<Item = u8>,
+    ) -> Result<(), Error> {
+        let ret = self.write_iter_once(addr.into(), bytes);
+        self.record(ret)
+    }
+
+    /// Writes the bytes yielded by `bytes` to the slave with address `addr` and then reads
+    /// enough bytes to fill `buffer` in a single transaction
+    ///
+    /// As the iterator is consumed, the transfer is not restarted after an arbitration loss.
+    pub fn write_iter_read(
+        &mut self,
+        addr: impl Into<Address>,
+        bytes: impl IntoIterator<Item = u8>,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        let ret = self.write_iter_read_once(addr.into(), bytes, buffer);
+        self.record(ret)
+    }
+
+    /// Probes the 7-bit addresses 0x08 to 0x77 with an address-only write
+    ///
+    /// Returns a bitmap where bit `n` is set if a slave acknowledged address `n`.
+    pub fn scan(&mut self) -> Result<u128, Error> {
+        let mut found = 0;
+        for addr in 0x08..0x78 {
+            match self.write(addr, &[]) {
+                Ok(()) => found |= 1 << addr,
+                Err(Error::Acknowledge) => {
+                    // The STOP condition was requested after the acknowledge failure
+                    busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(found)
+    }
+
+    /// Sets how many times a transfer is restarted after the arbitration was lost to
+    /// another master, waiting `backoff_us` before each new attempt
+    ///
+    /// Defaults to no retries, so that `Error::Arbitration` is returned directly.
+    pub fn set_arbitration_retries(&mut self, retries: u8, backoff_us: u32) {
+        self.arbitration_retries = retries;
+        self.arbitration_backoff = backoff_us;
+    }
+
+    /// Runs `f` again while it fails with `Error::Arbitration` and retries are left
+    fn retry_on_arbitration(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut retries_left = self.arbitration_retries;
+        loop {
+            let ret = f(self);
+            match self.record(ret) {
+                Err(Error::Arbitration) if retries_left > 0 => {
+                    retries_left -= 1;
+                    self.nb.record_retry();
+                    self.timer.start(self.arbitration_backoff);
+                    while !self.timer.expired() {}
+                }
+                ret => break ret,
+            }
+        }
+    }
+
+    /// Sets the maximum duration a slave may stretch the clock
+    ///
+    /// Replaces the timeouts of the start, address and data phases. A transfer where the slave
+    /// holds SCL low for longer fails with `Error::Timeout` and the peripheral is reset to
+    /// release the bus.
+    pub fn set_stretch_timeout(&mut self, timeout_us: u32) {
+        self.timeouts = Timeouts {
+            start: timeout_us,
+            addr: timeout_us,
+            data: timeout_us,
+        };
+    }
+
+    /// Counts the error of a transfer in the statistics and resets the peripheral after a
+    /// timeout, as the bus stays busy otherwise. The configuration is kept across the reset.
+    fn record(&mut self, ret: Result<(), Error>) -> Result<(), Error> {
+        if let Err(e) = &ret {
+            self.nb.record_error(e);
+            if *e == Error::Timeout {
+                self.nb.reset();
+            }
+        }
+        ret
+    }
+
+    fn write_iter_once(
+        &mut self,
+        addr: Address,
+        bytes: impl IntoIterator<Item = u8>,
+    ) -> Result<(), Error> {
+        self.write_iter_without_stop(addr, bytes, true)?;
+        self.nb.send_stop();
+        busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+
+        Ok(())
+    }
+
+    fn write_iter_read_once(
+        &mut self,
+        addr: Address,
+        bytes: impl IntoIterator<Item = u8>,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        self.write_iter_without_stop(addr, bytes, buffer.is_empty())?;
+
+        if !buffer.is_empty() {
+            self.read_once(addr, buffer)
+        } else {
+            self.nb.send_stop();
+            busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+            Ok(())
+        }
+    }
+
+    fn write_once(&mut self, addr: Address, bytes: &[u8]) -> Result<(), Error> {
+        self.write_without_stop(addr, bytes, true)?;
+        self.nb.send_stop();
+        busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+
+        Ok(())
+    }
+
+    fn read_once(&mut self, addr: Address, buffer: &mut [u8]) -> Result<(), Error> {
+        self.send_start_and_wait()?;
+        self.send_addr_and_wait(addr, true)?;
+
+        let len = buffer.len();
+        self.read_bytes_and_wait(buffer.iter_mut(), len, true)
+    }
+
+    fn write_read_once(
+        &mut self,
+        addr: Address,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
         if !bytes.is_empty() {
-            self.write_without_stop(addr, bytes)?;
+            self.write_without_stop(addr, bytes, buffer.is_empty())?;
         }
 
         if !buffer.is_empty() {
-            self.read(addr, buffer)?;
+            self.read_once(addr, buffer)?;
         } else if !bytes.is_empty() {
             self.nb.send_stop();
-            busy_wait_cycles!(self.wait_for_stop(), self.timeouts.data)?;
+            busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<I2C, PINS, T> BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    /// Changes the bus speed. See [`I2c::reconfigure`].
+    pub fn reconfigure<M: Into<Mode>>(&mut self, mode: M) -> Result<(), ConfigError> {
+        self.nb.reconfigure(mode)
+    }
+
+    /// Switches the interface to SMBus mode
+    pub fn enable_smbus(&mut self, smbus_type: SmbusType) {
+        self.nb.enable_smbus(smbus_type);
+    }
+
+    /// Switches the interface back to I2C mode
+    pub fn disable_smbus(&mut self) {
+        self.nb.disable_smbus();
+    }
+
+    /// Enables hardware packet error checking
+    ///
+    /// When enabled, the PEC byte is appended to the last write of a transaction and the
+    /// PEC byte received after the last read of a transaction is checked.
+    pub fn enable_pec(&mut self, enable: bool) {
+        self.nb.enable_pec(enable);
+    }
+
+    /// Drives the SMBA pin low (`true`) or releases it (`false`) in SMBus mode
+    pub fn set_alert(&mut self, alert: bool) {
+        self.nb.set_alert(alert);
+    }
+
+    /// Returns the transfer error counters
+    #[cfg(feature = "i2c-stats")]
+    pub fn stats(&self) -> &Stats {
+        self.nb.stats()
+    }
+
+    /// Clears the transfer error counters
+    #[cfg(feature = "i2c-stats")]
+    pub fn reset_stats(&mut self) {
+        self.nb.reset_stats();
+    }
+}
+
+impl<I2C, PINS, T> BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+    PINS: Pins<I2C>,
+{
+    /// Frees a bus where a slave holds SDA low. See [`I2c::recover`].
+    pub fn recover(
+        &mut self,
+        cr: &mut PINS::Cr,
+        delay: &mut impl DelayUs<u16>,
+    ) -> Result<(), Error> {
+        self.nb.recover(cr, delay)
+    }
+}
+
+impl<I2C, PINS, T> Write for BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    type Error = Error;
+
+    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write(addr, bytes)
+    }
+}
+
+impl<I2C, PINS, T> Read for BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    type Error = Error;
+
+    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.read(addr, buffer)
+    }
+}
+
+impl<I2C, PINS, T> WriteIter for BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    type Error = Error;
+
+    fn write<B>(&mut self, addr: u8, bytes: B) -> Result<(), Self::Error>
+    where
+        B: IntoIterator<Item = u8>,
+    {
+        self.write_iter(addr, bytes)
+    }
+}
+
+impl<I2C, PINS, T> WriteIterRead for BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    type Error = Error;
+
+    fn write_iter_read<B>(
+        &mut self,
+        addr: u8,
+        bytes: B,
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error>
+    where
+        B: IntoIterator<Item = u8>,
+    {
+        self.write_iter_read(addr, bytes, buffer)
+    }
+}
+
+impl<I2C, PINS, T> WriteRead for BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    type Error = Error;
+
+    fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.write_read(addr, bytes, buffer)
+    }
+}
+
+impl<I2C, PINS, T> BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    /// Executes a sequence of operations as one I2C transaction
+    ///
+    /// Adjacent operations of the same type are merged into one transfer, different
+    /// operations are separated by a repeated START condition and the transaction is ended
+    /// with a STOP condition. Empty read operations are skipped.
+    fn transaction_slice(
+        &mut self,
+        addr: Address,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        self.retry_on_arbitration(|i2c| i2c.transaction_slice_once(addr, operations))
+    }
+
+    fn transaction_slice_once(
+        &mut self,
+        addr: Address,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        // Empty reads are skipped, as a read of at least one byte is needed to end the
+        // transaction after a START
+        let is_empty_read =
+            |op: &Operation<'_>| matches!(op, Operation::Read(buffer) if buffer.is_empty());
+        let mut first = true;
+        let mut ops = &mut operations[..];
+
+        loop {
+            let skip = ops.iter().take_while(|op| is_empty_read(op)).count();
+            let (_, rest) = ops.split_at_mut(skip);
+            ops = rest;
+            if ops.is_empty() {
+                break;
+            }
+
+            let is_read = matches!(ops[0], Operation::Read(_));
+            let group_len = ops
+                .iter()
+                .position(|op| !is_empty_read(op) && matches!(op, Operation::Read(_)) != is_read)
+                .unwrap_or(ops.len());
+            let (group, rest) = ops.split_at_mut(group_len);
+            ops = rest;
+            let last = ops.iter().all(is_empty_read);
+
+            let read_len: usize = group
+                .iter()
+                .map(|op| match op {
+                    Operation::Read(buffer) => buffer.len(),
+                    Operation::Write(_) => 0,
+                })
+                .sum();
+
+            if first {
+                self.send_start_and_wait()?;
+                first = false;
+            } else {
+                // The repeated START was requested at the end of the previous group
+                busy_wait_timeout!(
+                    self.wait_after_sent_start(),
+                    self.timer,
+                    self.timeouts.start
+                )?;
+            }
+            self.send_addr_and_wait(addr, is_read)?;
+
+            if is_read {
+                let buffer = group.iter_mut().flat_map(|op| match op {
+                    Operation::Read(buffer) => buffer.iter_mut(),
+                    Operation::Write(_) => [].iter_mut(),
+                });
+                self.read_bytes_and_wait(buffer, read_len, last)?;
+            } else {
+                let bytes = group.iter().flat_map(|op| match op {
+                    Operation::Write(bytes) => bytes.iter().copied(),
+                    Operation::Read(_) => [].iter().copied(),
+                });
+                let ret = self.write_bytes_iter_and_wait(bytes, last);
+                if ret == Err(Error::Acknowledge) {
+                    self.nb.send_stop();
+                }
+                ret?;
+
+                if last {
+                    self.nb.send_stop();
+                    busy_wait_timeout!(self.wait_for_stop(), self.timer, self.timeouts.data)?;
+                } else {
+                    self.nb.send_start();
+                }
+            }
         }
 
         Ok(())
     }
 }
+
+impl<I2C, PINS, T> embedded_hal_one::i2c::ErrorType for BlockingI2c<I2C, PINS, T> {
+    type Error = Error;
+}
+
+impl<I2C, PINS, T> embedded_hal_one::i2c::I2c for BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        self.read(addr, buffer)
+    }
+
+    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.transaction_slice(addr.into(), &mut [Operation::Write(bytes)])
+    }
+
+    fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction_slice(
+            addr.into(),
+            &mut [Operation::Write(bytes), Operation::Read(buffer)],
+        )
+    }
+
+    fn transaction(
+        &mut self,
+        addr: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction_slice(addr.into(), operations)
+    }
+}
+
+impl<I2C, PINS, T> embedded_hal_one::i2c::I2c<TenBitAddress> for BlockingI2c<I2C, PINS, T>
+where
+    I2C: Instance,
+    T: TimeoutProvider,
+{
+    fn transaction(
+        &mut self,
+        addr: u16,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction_slice(Address::TenBit(addr), operations)
+    }
+}
@@ -0,0 +1,437 @@
+//! Interrupt-driven I2C master
+//!
+//! The transfer is started from the application and then advanced one step at a time by
+//! [`NonBlockingI2c::handle_event_interrupt`] and [`NonBlockingI2c::handle_error_interrupt`],
+//! which must be called from the `I2Cx_EV` and `I2Cx_ER` interrupt handlers respectively.
+//! Completion is checked with [`NonBlockingI2c::poll`].
+//!
+//! The buffers passed to the transfer methods must be `'static` because the peripheral keeps
+//! working on them after the method has returned.
+
+use super::*;
+
+/// Phase of the ongoing transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// No transfer in progress
+    Idle,
+    /// Waiting for the (repeated) START condition before sending the address for writing
+    StartWrite,
+    /// Header of a 10-bit address for writing sent, waiting for ADD10
+    Add10Write,
+    /// Address for writing sent, waiting for ADDR
+    AddrWrite,
+    /// Sending data bytes
+    Write,
+    /// Waiting for the (repeated) START condition before sending the address for reading
+    StartRead,
+    /// Header of a 10-bit address sent before reading, waiting for ADD10
+    Add10Read,
+    /// Full 10-bit address sent before reading, waiting for ADDR to generate a repeated START
+    AddrRestart,
+    /// Address for reading sent, waiting for ADDR
+    AddrRead,
+    /// Receiving data bytes
+    Read,
+}
+
+/// Interrupt-driven I2C master
+pub struct NonBlockingI2c<I2C, PINS> {
+    nb: I2c<I2C, PINS>,
+    state: State,
+    addr: Address,
+    /// The low byte of a 10-bit address has been acknowledged in this transaction
+    addr_10bit_sent: bool,
+    tx: &'static [u8],
+    tx_idx: usize,
+    rx: Option<&'static mut [u8]>,
+    rx_idx: usize,
+    result: Option<Result<(), Error>>,
+}
+
+impl<I2C, PINS> I2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    /// Generates an interrupt-driven I2C instance from a universal I2C object
+    pub fn non_blocking(self) -> NonBlockingI2c<I2C, PINS> {
+        NonBlockingI2c {
+            nb: self,
+            state: State::Idle,
+            addr: Address::SevenBit(0),
+            addr_10bit_sent: false,
+            tx: &[],
+            tx_idx: 0,
+            rx: None,
+            rx_idx: 0,
+            result: None,
+        }
+    }
+}
+
+impl<I2C, PINS> NonBlockingI2c<I2C, PINS>
+where
+    I2C: Instance,
+{
+    /// Starts writing `bytes` to the slave with address `addr`
+    ///
+    /// Returns `WouldBlock` if a transfer is still in progress.
+    pub fn write(
+        &mut self,
+        addr: impl Into<Address>,
+        bytes: &'static [u8],
+    ) -> nb::Result<(), Error> {
+        self.start(addr.into(), bytes, None)
+    }
+
+    /// Starts reading `buffer.len()` bytes from the slave with address `addr`
+    ///
+    /// Returns `WouldBlock` if a transfer is still in progress. Once the transfer is done
+    /// the buffer can be retrieved with [`NonBlockingI2c::take_read_buffer`].
+    pub fn read(
+        &mut self,
+        addr: impl Into<Address>,
+        buffer: &'static mut [u8],
+    ) -> nb::Result<(), Error> {
+        self.start(addr.into(), &[], Some(buffer))
+    }
+
+    /// Starts writing `bytes` to the slave with address `addr` and then reading
+    /// `buffer.len()` bytes back after a repeated START
+    ///
+    /// Returns `WouldBlock` if a transfer is still in progress. Once the transfer is done
+    /// the buffer can be retrieved with [`NonBlockingI2c::take_read_buffer`].
+    pub fn write_read(
+        &mut self,
+        addr: impl Into<Address>,
+        bytes: &'static [u8],
+        buffer: &'static mut [u8],
+    ) -> nb::Result<(), Error> {
+        self.start(addr.into(), bytes, Some(buffer))
+    }
+
+    fn start(
+        &mut self,
+        addr: Address,
+        bytes: &'static [u8],
+        buffer: Option<&'static mut [u8]>,
+    ) -> nb::Result<(), Error> {
+        // Wait for the STOP condition of the previous transfer to be sent
+        if self.is_busy() || self.nb.i2c.cr1.read().stop().bit_is_set() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.addr = addr;
+        self.addr_10bit_sent = false;
+        self.tx = bytes;
+        self.tx_idx = 0;
+        self.rx = buffer;
+        self.rx_idx = 0;
+        self.result = None;
+
+        let write_first = !bytes.is_empty() || self.rx.as_deref().map_or(true, <[u8]>::is_empty);
+        self.state = if write_first {
+            State::StartWrite
+        } else {
+            State::StartRead
+        };
+
+        self.nb.i2c.cr2.modify(|_, w| {
+            w.itevten()
+                .set_bit()
+                .iterren()
+                .set_bit()
+                .itbufen()
+                .set_bit()
+        });
+        self.nb.send_start();
+        Ok(())
+    }
+
+    /// Returns `true` if a transfer is in progress
+    pub fn is_busy(&self) -> bool {
+        self.state != State::Idle
+    }
+
+    /// Returns the phase of the ongoing transfer
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Checks for completion of the last transfer
+    ///
+    /// Returns `WouldBlock` while the transfer is in progress and the transfer result once
+    /// it has been completed.
+    pub fn poll(&mut self) -> nb::Result<(), Error> {
+        if self.is_busy() {
+            return Err(nb::Error::WouldBlock);
+        }
+        match self.result.take() {
+            Some(Err(e)) => Err(nb::Error::Other(e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the buffer of the last read transfer when no transfer is in progress
+    pub fn take_read_buffer(&mut self) -> Option<&'static mut [u8]> {
+        if self.is_busy() {
+            None
+        } else {
+            self.rx.take()
+        }
+    }
+
+    /// Aborts the ongoing transfer, generating a STOP condition
+    pub fn abort(&mut self) {
+        if self.is_busy() {
+            self.nb.send_stop();
+            self.finish(Err(Error::Bus));
+        }
+    }
+
+    fn rx_len(&self) -> usize {
+        self.rx.as_deref().map_or(0, <[u8]>::len)
+    }
+
+    fn read_byte(&mut self) {
+        let byte = self.nb.i2c.dr.read().dr().bits();
+        if let Some(buffer) = self.rx.as_deref_mut() {
+            buffer[self.rx_idx] = byte;
+        }
+        self.rx_idx += 1;
+    }
+
+    fn finish(&mut self, result: Result<(), Error>) {
+        if let Err(e) = &result {
+            self.nb.record_error(e);
+        }
+        self.nb.i2c.cr2.modify(|_, w| {
+            w.itevten()
+                .clear_bit()
+                .iterren()
+                .clear_bit()
+                .itbufen()
+                .clear_bit()
+        });
+        self.nb
+            .i2c
+            .cr1
+            .modify(|_, w| w.pos().clear_bit().ack().set_bit());
+        self.state = State::Idle;
+        self.result = Some(result);
+    }
+
+    fn stop_and_finish(&mut self) {
+        self.nb.send_stop();
+        self.finish(Ok(()));
+    }
+
+    /// Advances the transfer state machine
+    ///
+    /// Must be called from the `I2Cx_EV` interrupt handler.
+    pub fn handle_event_interrupt(&mut self) {
+        let sr1 = self.nb.i2c.sr1.read();
+
+        match self.state {
+            State::Idle => {}
+            State::StartWrite | State::StartRead => {
+                if sr1.sb().bit_is_set() {
+                    let read = self.state == State::StartRead;
+                    self.state = match self.addr {
+                        Address::SevenBit(addr) => {
+                            self.nb.send_addr(addr, read);
+                            if read {
+                                State::AddrRead
+                            } else {
+                                State::AddrWrite
+                            }
+                        }
+                        Address::TenBit(addr) if read && self.addr_10bit_sent => {
+                            self.nb.send_header_10bit(addr, true);
+                            State::AddrRead
+                        }
+                        Address::TenBit(addr) => {
+                            self.nb.send_header_10bit(addr, false);
+                            if read {
+                                State::Add10Read
+                            } else {
+                                State::Add10Write
+                            }
+                        }
+                    };
+                }
+            }
+            State::Add10Write | State::Add10Read => {
+                if sr1.add10().bit_is_set() {
+                    if let Address::TenBit(addr) = self.addr {
+                        self.nb.send_addr_10bit_low(addr);
+                    }
+                    self.state = if self.state == State::Add10Read {
+                        State::AddrRestart
+                    } else {
+                        State::AddrWrite
+                    };
+                }
+            }
+            State::AddrRestart => {
+                if sr1.addr().bit_is_set() {
+                    self.nb.i2c.sr2.read();
+                    self.addr_10bit_sent = true;
+                    self.state = State::StartRead;
+                    self.nb.send_start();
+                }
+            }
+            State::AddrWrite => {
+                if sr1.addr().bit_is_set() {
+                    self.nb.i2c.sr2.read();
+                    self.addr_10bit_sent = true;
+                    if self.tx.is_empty() {
+                        self.tx_done();
+                    } else {
+                        self.state = State::Write;
+                    }
+                }
+            }
+            State::Write => {
+                if self.tx_idx < self.tx.len() {
+                    if sr1.tx_e().bit_is_set() {
+                        self.nb.i2c.dr.write(|w| w.dr().bits(self.tx[self.tx_idx]));
+                        self.tx_idx += 1;
+                        if self.tx_idx == self.tx.len() {
+                            // Only BTF is of interest from now on
+                            self.nb.i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                        }
+                    }
+                } else if sr1.btf().bit_is_set() {
+                    self.tx_done();
+                }
+            }
+            State::AddrRead => {
+                if sr1.addr().bit_is_set() {
+                    match self.rx_len() {
+                        1 => {
+                            self.nb.i2c.cr1.modify(|_, w| w.ack().clear_bit());
+                            self.nb.i2c.sr2.read();
+                            self.nb.send_stop();
+                        }
+                        2 => {
+                            self.nb
+                                .i2c
+                                .cr1
+                                .modify(|_, w| w.pos().set_bit().ack().clear_bit());
+                            self.nb.i2c.sr2.read();
+                            self.nb.i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                        }
+                        len => {
+                            self.nb.i2c.cr1.modify(|_, w| w.ack().set_bit());
+                            self.nb.i2c.sr2.read();
+                            if len == 3 {
+                                self.nb.i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                            }
+                        }
+                    }
+                    self.state = State::Read;
+                }
+            }
+            State::Read => {
+                let len = self.rx_len();
+                let remaining = len - self.rx_idx;
+                match (len, remaining) {
+                    (1, _) | (_, 1) => {
+                        if sr1.rx_ne().bit_is_set() {
+                            self.read_byte();
+                            self.finish(Ok(()));
+                        }
+                    }
+                    (2, _) => {
+                        if sr1.btf().bit_is_set() {
+                            self.nb.send_stop();
+                            self.read_byte();
+                            self.read_byte();
+                            self.finish(Ok(()));
+                        }
+                    }
+                    (_, 3) => {
+                        if sr1.btf().bit_is_set() {
+                            self.nb.i2c.cr1.modify(|_, w| w.ack().clear_bit());
+                            self.read_byte();
+                            self.nb.send_stop();
+                            self.read_byte();
+                            // Wait for the last byte
+                            self.nb.i2c.cr2.modify(|_, w| w.itbufen().set_bit());
+                        }
+                    }
+                    _ => {
+                        if sr1.rx_ne().bit_is_set() {
+                            self.read_byte();
+                            if len - self.rx_idx == 3 {
+                                // Only BTF is of interest for the last three bytes
+                                self.nb.i2c.cr2.modify(|_, w| w.itbufen().clear_bit());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn tx_done(&mut self) {
+        if self.rx_len() > 0 {
+            self.state = State::StartRead;
+            self.nb.i2c.cr2.modify(|_, w| w.itbufen().set_bit());
+            self.nb.send_start();
+        } else {
+            self.stop_and_finish();
+        }
+    }
+
+    /// Handles the error flags and aborts the transfer
+    ///
+    /// Must be called from the `I2Cx_ER` interrupt handler.
+    pub fn handle_error_interrupt(&mut self) {
+        let sr1 = self.nb.i2c.sr1.read();
+
+        let err = if sr1.berr().bit_is_set() {
+            self.nb.i2c.sr1.write(|w| w.berr().clear_bit());
+            Error::Bus
+        } else if sr1.arlo().bit_is_set() {
+            // The interface switches back to slave mode by itself, no STOP is needed
+            self.nb.i2c.sr1.write(|w| w.arlo().clear_bit());
+            self.finish(Err(Error::Arbitration));
+            return;
+        } else if sr1.af().bit_is_set() {
+            self.nb.i2c.sr1.write(|w| w.af().clear_bit());
+            Error::Acknowledge
+        } else if sr1.ovr().bit_is_set() {
+            self.nb.i2c.sr1.write(|w| w.ovr().clear_bit());
+            Error::Overrun
+        } else if sr1.pecerr().bit_is_set() {
+            self.nb.i2c.sr1.write(|w| w.pecerr().clear_bit());
+            Error::Pec
+        } else if sr1.timeout().bit_is_set() {
+            self.nb.i2c.sr1.write(|w| w.timeout().clear_bit());
+            Error::Timeout
+        } else if sr1.smbalert().bit_is_set() {
+            self.nb.i2c.sr1.write(|w| w.smbalert().clear_bit());
+            Error::Alert
+        } else {
+            return;
+        };
+
+        if self.is_busy() {
+            self.nb.send_stop();
+            self.finish(Err(err));
+        }
+    }
+
+    /// Returns the transfer error counters
+    #[cfg(feature = "i2c-stats")]
+    pub fn stats(&self) -> &Stats {
+        self.nb.stats()
+    }
+
+    /// Releases the I2C peripheral and associated pins
+    pub fn release(self) -> (I2C, PINS) {
+        self.nb.release()
+    }
+}
@@ -0,0 +1,119 @@
+//! Sharing one I2C bus between several drivers
+//!
+//! ```ignore
+//! let bus = I2cBus::new(i2c);
+//! let mut sensor1 = Sensor1::new(bus.acquire());
+//! let mut sensor2 = Sensor2::new(bus.acquire());
+//! ```
+
+use crate::hal::blocking::i2c::{Read, Write, WriteIter, WriteIterRead, WriteRead};
+use core::cell::RefCell;
+use cortex_m::interrupt::{self, Mutex};
+use embedded_hal_one::i2c::{AddressMode, ErrorType, Operation};
+
+/// Owner of a shared I2C bus, usually a [`BlockingI2c`](super::BlockingI2c)
+///
+/// Each transfer made through an [`I2cProxy`] runs in a critical section, so the bus can
+/// also be shared with interrupt handlers when stored in a `static`.
+pub struct I2cBus<BUS> {
+    bus: Mutex<RefCell<BUS>>,
+}
+
+impl<BUS> I2cBus<BUS> {
+    pub const fn new(bus: BUS) -> Self {
+        Self {
+            bus: Mutex::new(RefCell::new(bus)),
+        }
+    }
+
+    /// Returns a new handle to the bus
+    pub fn acquire(&self) -> I2cProxy<'_, BUS> {
+        I2cProxy { bus: &self.bus }
+    }
+}
+
+/// Handle to a shared I2C bus implementing the blocking I2C traits
+pub struct I2cProxy<'a, BUS> {
+    bus: &'a Mutex<RefCell<BUS>>,
+}
+
+impl<'a, BUS> Clone for I2cProxy<'a, BUS> {
+    fn clone(&self) -> Self {
+        Self { bus: self.bus }
+    }
+}
+
+impl<'a, BUS> I2cProxy<'a, BUS> {
+    fn lock<R>(&mut self, f: impl FnOnce(&mut BUS) -> R) -> R {
+        interrupt::free(|cs| f(&mut self.bus.borrow(cs).borrow_mut()))
+    }
+}
+
+impl<'a, BUS: Write> Write for I2cProxy<'a, BUS> {
+    type Error = BUS::Error;
+
+    fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.write(addr, bytes))
+    }
+}
+
+impl<'a, BUS: Read> Read for I2cProxy<'a, BUS> {
+    type Error = BUS::Error;
+
+    fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.read(addr, buffer))
+    }
+}
+
+impl<'a, BUS: WriteRead> WriteRead for I2cProxy<'a, BUS> {
+    type Error = BUS::Error;
+
+    fn write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.write_read(addr, bytes, buffer))
+    }
+}
+
+impl<'a, BUS: WriteIter> WriteIter for I2cProxy<'a, BUS> {
+    type Error = BUS::Error;
+
+    fn write<B>(&mut self, addr: u8, bytes: B) -> Result<(), Self::Error>
+    where
+        B: IntoIterator<Item = u8>,
+    {
+        self.lock(|bus| bus.write(addr, bytes))
+    }
+}
+
+impl<'a, BUS: WriteIterRead> WriteIterRead for I2cProxy<'a, BUS> {
+    type Error = BUS::Error;
+
+    fn write_iter_read<B>(
+        &mut self,
+        addr: u8,
+        bytes: B,
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error>
+    where
+        B: IntoIterator<Item = u8>,
+    {
+        self.lock(|bus| bus.write_iter_read(addr, bytes, buffer))
+    }
+}
+
+impl<'a, BUS: ErrorType> ErrorType for I2cProxy<'a, BUS> {
+    type Error = BUS::Error;
+}
+
+impl<'a, A, BUS> embedded_hal_one::i2c::I2c<A> for I2cProxy<'a, BUS>
+where
+    A: AddressMode,
+    BUS: embedded_hal_one::i2c::I2c<A>,
+{
+    fn transaction(
+        &mut self,
+        addr: A,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.transaction(addr, operations))
+    }
+}
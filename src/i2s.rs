@@ -0,0 +1,636 @@
+/*!
+  # Inter-IC Sound
+
+  High-density and connectivity line devices can run `SPI2` and `SPI3` as I2S audio interfaces.
+  To construct the I2S instances, use the `I2s::i2sX` functions.
+
+  The pin parameter is a tuple containing `(ws, ck, sd)`, or `(ws, ck, sd, mck)` to also output
+  the master clock. `ws`, `ck` and `mck` should be configured as `Alternate<...>`, `sd` as
+  `Alternate<...>` when transmitting (`MasterTx`) or `Input<...>` when receiving (`MasterRx`).
+
+  - `I2S2` can use `(PB12, PB13, PB15)` with master clock on `PC6`
+  - `I2S3` can use `(PA15, PB3, PB5)` or only in connectivity line devices `(PA4, PC10, PC12)`,
+    with master clock on `PC7`
+
+  Samples are exchanged as half-words, alternating left and right channel. 24- and 32-bit
+  samples take two half-words, most significant first, 24-bit data being left aligned.
+
+  The I2S clock is taken from SYSCLK, the sample rate actually achieved can be read with
+  `sample_rate`.
+
+  ## Initialisation example
+
+  ```rust
+    let pins = (
+        gpiob.pb12.into_alternate_push_pull(&mut gpiob.crh),
+        gpiob.pb13.into_alternate_push_pull(&mut gpiob.crh),
+        gpiob.pb15.into_alternate_push_pull(&mut gpiob.crh),
+    );
+
+    let config = Config::default()
+        .standard(Standard::Philips)
+        .data_format(DataFormat::Data16Channel16)
+        .sample_rate(48.kHz());
+    let mut i2s: I2s<_, _, _, MasterTx> = I2s::i2s2(dp.SPI2, pins, config, clocks);
+    i2s.write(&[left, right]);
+  ```
+*/
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::ptr;
+
+use crate::pac::{self, RCC};
+use crate::spi::{Instance, Remap};
+
+#[cfg(feature = "connectivity")]
+use crate::afio::MAPR;
+use crate::dma::{dma1, dma2};
+use crate::dma::{
+    CircBuffer, Priority, Receive, RxDma, Transfer, TransferPayload, Transmit, TxDma, R, W,
+};
+use crate::gpio::{self, Alternate, Input};
+use crate::rcc::Clocks;
+use crate::time::Hertz;
+
+use core::sync::atomic::{self, Ordering};
+use embedded_dma::{ReadBuffer, WriteBuffer};
+
+/// I2S error
+#[derive(Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Overrun occurred
+    Overrun,
+}
+
+/// Audio standard
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Standard {
+    /// Philips I2S, data delayed by one clock after WS
+    Philips,
+    /// MSB justified (left justified)
+    Msb,
+    /// LSB justified (right justified)
+    Lsb,
+}
+
+/// Data and channel lengths
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DataFormat {
+    /// 16-bit data in a 16-bit channel
+    Data16Channel16,
+    /// 16-bit data in a 32-bit channel
+    Data16Channel32,
+    /// 24-bit data in a 32-bit channel
+    Data24Channel32,
+    /// 32-bit data in a 32-bit channel
+    Data32Channel32,
+}
+
+/// I2S settings
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub standard: Standard,
+    pub data_format: DataFormat,
+    pub sample_rate: Hertz,
+    /// Idle level of the CK line
+    pub clock_idle_high: bool,
+}
+
+impl Config {
+    pub fn standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    pub fn data_format(mut self, data_format: DataFormat) -> Self {
+        self.data_format = data_format;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is zero.
+    pub fn sample_rate(mut self, sample_rate: Hertz) -> Self {
+        assert!(sample_rate.raw() > 0, "zero sample rate");
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn clock_idle_high(mut self, clock_idle_high: bool) -> Self {
+        self.clock_idle_high = clock_idle_high;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            standard: Standard::Philips,
+            data_format: DataFormat::Data16Channel16,
+            sample_rate: Hertz::Hz(48_000),
+            clock_idle_high: false,
+        }
+    }
+}
+
+/// Master transmitter
+pub struct MasterTx;
+/// Master receiver
+pub struct MasterRx;
+
+mod sealed {
+    pub trait Direction {
+        const RX: bool;
+    }
+    pub trait Ws<REMAP> {}
+    pub trait Ck<REMAP> {}
+    pub trait SdTx<REMAP> {}
+    pub trait SdRx<REMAP> {}
+    pub trait Mck<SPI> {}
+}
+pub use sealed::Direction;
+use sealed::{Ck, Mck, SdRx, SdTx, Ws};
+
+impl Direction for MasterTx {
+    const RX: bool = false;
+}
+impl Direction for MasterRx {
+    const RX: bool = true;
+}
+
+pub trait Pins<REMAP, DIR> {
+    /// Whether the master clock is output
+    const MCK: bool;
+}
+
+impl<REMAP, WS, CK, SD> Pins<REMAP, MasterTx> for (WS, CK, SD)
+where
+    WS: Ws<REMAP>,
+    CK: Ck<REMAP>,
+    SD: SdTx<REMAP>,
+{
+    const MCK: bool = false;
+}
+
+impl<REMAP, WS, CK, SD> Pins<REMAP, MasterRx> for (WS, CK, SD)
+where
+    WS: Ws<REMAP>,
+    CK: Ck<REMAP>,
+    SD: SdRx<REMAP>,
+{
+    const MCK: bool = false;
+}
+
+impl<REMAP, WS, CK, SD, MCK> Pins<REMAP, MasterTx> for (WS, CK, SD, MCK)
+where
+    REMAP: Remap,
+    WS: Ws<REMAP>,
+    CK: Ck<REMAP>,
+    SD: SdTx<REMAP>,
+    MCK: Mck<REMAP::Periph>,
+{
+    const MCK: bool = true;
+}
+
+impl<REMAP, WS, CK, SD, MCK> Pins<REMAP, MasterRx> for (WS, CK, SD, MCK)
+where
+    REMAP: Remap,
+    WS: Ws<REMAP>,
+    CK: Ck<REMAP>,
+    SD: SdRx<REMAP>,
+    MCK: Mck<REMAP::Periph>,
+{
+    const MCK: bool = true;
+}
+
+macro_rules! remap {
+    ($name:ty, $WS:ident, $CK:ident, $SD:ident) => {
+        impl<MODE> Ws<$name> for gpio::$WS<Alternate<MODE>> {}
+        impl<MODE> Ck<$name> for gpio::$CK<Alternate<MODE>> {}
+        impl<MODE> SdTx<$name> for gpio::$SD<Alternate<MODE>> {}
+        impl<MODE> SdRx<$name> for gpio::$SD<Input<MODE>> {}
+    };
+}
+
+remap!(crate::spi::Spi2NoRemap, PB12, PB13, PB15);
+remap!(crate::spi::Spi3NoRemap, PA15, PB3, PB5);
+#[cfg(feature = "connectivity")]
+remap!(crate::spi::Spi3Remap, PA4, PC10, PC12);
+
+impl<MODE> Mck<pac::SPI2> for gpio::PC6<Alternate<MODE>> {}
+impl<MODE> Mck<pac::SPI3> for gpio::PC7<Alternate<MODE>> {}
+
+pub struct I2s<SPI, REMAP, PINS, DIR> {
+    spi: SPI,
+    pins: PINS,
+    sample_rate: Hertz,
+    _remap: PhantomData<REMAP>,
+    _dir: PhantomData<DIR>,
+}
+
+impl<REMAP, PINS, DIR> I2s<pac::SPI2, REMAP, PINS, DIR> {
+    /**
+      Constructs an I2S instance using SPI2 as audio master.
+
+      The pin parameter tuple (ws, ck, sd) should be `(PB12, PB13, PB15)`, optionally followed
+      by `PC6` to output the master clock.
+    */
+    pub fn i2s2(spi: pac::SPI2, pins: PINS, config: Config, clocks: Clocks) -> Self
+    where
+        REMAP: Remap<Periph = pac::SPI2>,
+        PINS: Pins<REMAP, DIR>,
+        DIR: Direction,
+    {
+        I2s::configure(spi, pins, config, clocks)
+    }
+}
+
+impl<REMAP, PINS, DIR> I2s<pac::SPI3, REMAP, PINS, DIR> {
+    /**
+      Constructs an I2S instance using SPI3 as audio master.
+
+      The pin parameter tuple (ws, ck, sd) should be `(PA15, PB3, PB5)`, optionally followed
+      by `PC7` to output the master clock.
+    */
+    #[cfg(not(feature = "connectivity"))]
+    pub fn i2s3(spi: pac::SPI3, pins: PINS, config: Config, clocks: Clocks) -> Self
+    where
+        REMAP: Remap<Periph = pac::SPI3>,
+        PINS: Pins<REMAP, DIR>,
+        DIR: Direction,
+    {
+        I2s::configure(spi, pins, config, clocks)
+    }
+
+    /**
+      Constructs an I2S instance using SPI3 as audio master.
+
+      The pin parameter tuple (ws, ck, sd) should be `(PA15, PB3, PB5)` or `(PA4, PC10, PC12)`,
+      optionally followed by `PC7` to output the master clock.
+    */
+    #[cfg(feature = "connectivity")]
+    pub fn i2s3(spi: pac::SPI3, pins: PINS, mapr: &mut MAPR, config: Config, clocks: Clocks) -> Self
+    where
+        REMAP: Remap<Periph = pac::SPI3>,
+        PINS: Pins<REMAP, DIR>,
+        DIR: Direction,
+    {
+        mapr.modify_mapr(|_, w| w.spi3_remap().bit(REMAP::REMAP));
+        I2s::configure(spi, pins, config, clocks)
+    }
+}
+
+impl<SPI, REMAP, PINS, DIR> I2s<SPI, REMAP, PINS, DIR>
+where
+    SPI: Instance,
+    PINS: Pins<REMAP, DIR>,
+    DIR: Direction,
+{
+    fn configure(spi: SPI, pins: PINS, config: Config, clocks: Clocks) -> Self {
+        // enable or reset SPI
+        let rcc = unsafe { &(*RCC::ptr()) };
+        SPI::enable(rcc);
+        SPI::reset(rcc);
+
+        let chlen32 = config.data_format != DataFormat::Data16Channel16;
+
+        // Fs = I2SCLK / (factor * (2 * I2SDIV + ODD))
+        let factor = if PINS::MCK {
+            256
+        } else if chlen32 {
+            64
+        } else {
+            32
+        };
+        let clk = u64::from(clocks.sysclk().raw());
+        let fs = factor * u64::from(config.sample_rate.raw());
+        let div = ((clk + fs / 2) / fs).clamp(4, 511);
+
+        spi.i2spr.write(|w| {
+            unsafe { w.i2sdiv().bits((div / 2) as u8) }
+                .odd()
+                .bit(div & 1 == 1)
+                .mckoe()
+                .bit(PINS::MCK)
+        });
+
+        spi.i2scfgr.write(|w| {
+            let w = w.i2smod().i2smode();
+            let w = if DIR::RX {
+                w.i2scfg().master_rx()
+            } else {
+                w.i2scfg().master_tx()
+            };
+            let w = match config.standard {
+                Standard::Philips => w.i2sstd().philips(),
+                Standard::Msb => w.i2sstd().msb(),
+                Standard::Lsb => w.i2sstd().lsb(),
+            };
+            let w = match config.data_format {
+                DataFormat::Data16Channel16 | DataFormat::Data16Channel32 => {
+                    w.datlen().sixteen_bit()
+                }
+                DataFormat::Data24Channel32 => w.datlen().twenty_four_bit(),
+                DataFormat::Data32Channel32 => w.datlen().thirty_two_bit(),
+            };
+            w.chlen()
+                .bit(chlen32)
+                .ckpol()
+                .bit(config.clock_idle_high)
+                .i2se()
+                .enabled()
+        });
+
+        I2s {
+            spi,
+            pins,
+            sample_rate: Hertz::Hz((clk / (factor * div)) as u32),
+            _remap: PhantomData,
+            _dir: PhantomData,
+        }
+    }
+}
+
+impl<SPI, REMAP, PINS, DIR> I2s<SPI, REMAP, PINS, DIR>
+where
+    SPI: Instance,
+{
+    /// Sample rate actually achieved with the I2S clock dividers
+    pub fn sample_rate(&self) -> Hertz {
+        self.sample_rate
+    }
+
+    /// Returns true if the current half-word belongs to the right channel
+    pub fn is_right_channel(&self) -> bool {
+        self.spi.sr.read().chside().bit_is_set()
+    }
+
+    pub fn release(self) -> (SPI, PINS) {
+        self.spi.i2scfgr.modify(|_, w| w.i2se().disabled());
+        (self.spi, self.pins)
+    }
+
+    fn read_data_reg(&mut self) -> u16 {
+        unsafe { ptr::read_volatile(&self.spi.dr as *const _ as *const u16) }
+    }
+
+    fn write_data_reg(&mut self, data: u16) {
+        unsafe { ptr::write_volatile(ptr::addr_of!(self.spi.dr) as *mut u16, data) }
+    }
+}
+
+impl<SPI, REMAP, PINS> I2s<SPI, REMAP, PINS, MasterTx>
+where
+    SPI: Instance,
+{
+    /// Queues a half-word for transmission
+    pub fn send(&mut self, data: u16) -> nb::Result<(), Infallible> {
+        if self.spi.sr.read().txe().bit_is_set() {
+            self.write_data_reg(data);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Transmits all half-words, blocking until the last one is queued
+    pub fn write(&mut self, words: &[u16]) {
+        for word in words {
+            nb::block!(self.send(*word)).ok();
+        }
+    }
+}
+
+impl<SPI, REMAP, PINS> I2s<SPI, REMAP, PINS, MasterRx>
+where
+    SPI: Instance,
+{
+    /// Returns the last received half-word
+    pub fn receive(&mut self) -> nb::Result<u16, Error> {
+        let sr = self.spi.sr.read();
+        if sr.ovr().bit_is_set() {
+            // OVR is cleared by reading DR then SR
+            let _ = self.read_data_reg();
+            let _ = self.spi.sr.read();
+            Err(nb::Error::Other(Error::Overrun))
+        } else if sr.rxne().bit_is_set() {
+            Ok(self.read_data_reg())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Fills `words` with received half-words
+    pub fn read(&mut self, words: &mut [u16]) -> Result<(), Error> {
+        for word in words {
+            *word = nb::block!(self.receive())?;
+        }
+        Ok(())
+    }
+}
+
+// DMA
+
+pub type I2sTxDma<SPI, REMAP, PINS, CHANNEL> = TxDma<I2s<SPI, REMAP, PINS, MasterTx>, CHANNEL>;
+pub type I2sRxDma<SPI, REMAP, PINS, CHANNEL> = RxDma<I2s<SPI, REMAP, PINS, MasterRx>, CHANNEL>;
+
+macro_rules! i2s_dma {
+    ($SPIi:ty, $RCi:ty, $TCi:ty, $rxdma:ident, $txdma:ident) => {
+        pub type $rxdma<REMAP, PINS> = I2sRxDma<$SPIi, REMAP, PINS, $RCi>;
+        pub type $txdma<REMAP, PINS> = I2sTxDma<$SPIi, REMAP, PINS, $TCi>;
+
+        impl<REMAP, PINS> Transmit for I2sTxDma<$SPIi, REMAP, PINS, $TCi> {
+            type TxChannel = $TCi;
+            type ReceivedWord = u16;
+        }
+
+        impl<REMAP, PINS> Receive for I2sRxDma<$SPIi, REMAP, PINS, $RCi> {
+            type RxChannel = $RCi;
+            type TransmittedWord = u16;
+        }
+
+        impl<REMAP, PINS> I2s<$SPIi, REMAP, PINS, MasterTx> {
+            pub fn with_tx_dma(self, mut channel: $TCi) -> I2sTxDma<$SPIi, REMAP, PINS, $TCi> {
+                // high channel priority level by default, audio can't wait
+                channel.set_priority(Priority::High);
+                self.spi.cr2.modify(|_, w| w.txdmaen().set_bit());
+                I2sTxDma {
+                    payload: self,
+                    channel,
+                }
+            }
+        }
+
+        impl<REMAP, PINS> I2s<$SPIi, REMAP, PINS, MasterRx> {
+            pub fn with_rx_dma(self, mut channel: $RCi) -> I2sRxDma<$SPIi, REMAP, PINS, $RCi> {
+                // high channel priority level by default, audio can't wait
+                channel.set_priority(Priority::High);
+                self.spi.cr2.modify(|_, w| w.rxdmaen().set_bit());
+                I2sRxDma {
+                    payload: self,
+                    channel,
+                }
+            }
+        }
+
+        impl<REMAP, PINS> I2sTxDma<$SPIi, REMAP, PINS, $TCi> {
+            pub fn release(self) -> (I2s<$SPIi, REMAP, PINS, MasterTx>, $TCi) {
+                let I2sTxDma { payload, channel } = self;
+                payload.spi.cr2.modify(|_, w| w.txdmaen().clear_bit());
+                (payload, channel)
+            }
+        }
+
+        impl<REMAP, PINS> I2sRxDma<$SPIi, REMAP, PINS, $RCi> {
+            pub fn release(self) -> (I2s<$SPIi, REMAP, PINS, MasterRx>, $RCi) {
+                let I2sRxDma { payload, channel } = self;
+                payload.spi.cr2.modify(|_, w| w.rxdmaen().clear_bit());
+                (payload, channel)
+            }
+        }
+
+        impl<REMAP, PINS> TransferPayload for I2sTxDma<$SPIi, REMAP, PINS, $TCi> {
+            fn start(&mut self) {
+                self.channel.start();
+            }
+            fn stop(&mut self) {
+                self.channel.stop();
+            }
+        }
+
+        impl<REMAP, PINS> TransferPayload for I2sRxDma<$SPIi, REMAP, PINS, $RCi> {
+            fn start(&mut self) {
+                self.channel.start();
+            }
+            fn stop(&mut self) {
+                self.channel.stop();
+            }
+        }
+
+        impl<B, REMAP, PINS> crate::dma::CircReadDma<B, u16> for I2sRxDma<$SPIi, REMAP, PINS, $RCi>
+        where
+            &'static mut [B; 2]: WriteBuffer<Word = u16>,
+            B: 'static,
+        {
+            fn circ_read(mut self, mut buffer: &'static mut [B; 2]) -> CircBuffer<B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.write_buffer() };
+                self.channel.set_peripheral_address(
+                    unsafe { &(*<$SPIi>::ptr()).dr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w
+                        // memory to memory mode disabled
+                        .mem2mem()
+                        .clear_bit()
+                        // 16-bit memory size
+                        .msize()
+                        .bits16()
+                        // 16-bit peripheral size
+                        .psize()
+                        .bits16()
+                        // circular mode enabled
+                        .circ()
+                        .set_bit()
+                        // write to memory
+                        .dir()
+                        .clear_bit()
+                });
+                self.start();
+
+                CircBuffer::new(buffer, self)
+            }
+        }
+
+        impl<B, REMAP, PINS> crate::dma::ReadDma<B, u16> for I2sRxDma<$SPIi, REMAP, PINS, $RCi>
+        where
+            B: WriteBuffer<Word = u16>,
+        {
+            fn read(mut self, mut buffer: B) -> Transfer<W, B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.write_buffer() };
+                self.channel.set_peripheral_address(
+                    unsafe { &(*<$SPIi>::ptr()).dr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w
+                        // memory to memory mode disabled
+                        .mem2mem()
+                        .clear_bit()
+                        // 16-bit memory size
+                        .msize()
+                        .bits16()
+                        // 16-bit peripheral size
+                        .psize()
+                        .bits16()
+                        // circular mode disabled
+                        .circ()
+                        .clear_bit()
+                        // write to memory
+                        .dir()
+                        .clear_bit()
+                });
+                self.start();
+
+                Transfer::w(buffer, self)
+            }
+        }
+
+        impl<B, REMAP, PINS> crate::dma::WriteDma<B, u16> for I2sTxDma<$SPIi, REMAP, PINS, $TCi>
+        where
+            B: ReadBuffer<Word = u16>,
+        {
+            fn write(mut self, buffer: B) -> Transfer<R, B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.read_buffer() };
+                self.channel.set_peripheral_address(
+                    unsafe { &(*<$SPIi>::ptr()).dr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w
+                        // memory to memory mode disabled
+                        .mem2mem()
+                        .clear_bit()
+                        // 16-bit memory size
+                        .msize()
+                        .bits16()
+                        // 16-bit peripheral size
+                        .psize()
+                        .bits16()
+                        // circular mode disabled
+                        .circ()
+                        .clear_bit()
+                        // read from memory
+                        .dir()
+                        .set_bit()
+                });
+                self.start();
+
+                Transfer::r(buffer, self)
+            }
+        }
+    };
+}
+
+i2s_dma!(pac::SPI2, dma1::C4, dma1::C5, I2s2RxDma, I2s2TxDma);
+i2s_dma!(pac::SPI3, dma2::C1, dma2::C2, I2s3RxDma, I2s3TxDma);
@@ -160,6 +160,11 @@ pub mod flash;
 pub mod gpio;
 #[cfg(feature = "device-selected")]
 pub mod i2c;
+#[cfg(all(
+    feature = "device-selected",
+    any(all(feature = "stm32f103", feature = "high"), feature = "connectivity")
+))]
+pub mod i2s;
 #[cfg(feature = "device-selected")]
 pub mod prelude;
 #[cfg(feature = "device-selected")]
@@ -3,6 +3,29 @@
 
   NOTE: In some cases you need to specify remap you need, especially for TIM2
   (see [Alternate function remapping](super::timer)):
+
+  ## Extended position
+
+  [`ExtendedQei`] counts the overflows and underflows of the counter in the timer interrupt
+  into a 64-bit position, optionally zeroed by the index pulse of the encoder on an EXTI line:
+
+  ```rust
+  let mut encoder = qei.extended().with_index(pa2, &mut afio, &mut dp.EXTI, Edge::Rising);
+
+  #[interrupt]
+  fn TIM2() {
+      // encoder is shared with the interrupt handlers
+      encoder.handle_interrupt();
+  }
+
+  #[interrupt]
+  fn EXTI2() {
+      encoder.handle_index_interrupt();
+  }
+
+  // Every 10 ms, e.g. in the interrupt of another timer
+  let counts_per_second = encoder.velocity(10_000.micros());
+  ```
 */
 use core::u16;
 
@@ -16,7 +39,10 @@ use crate::pac::TIM1;
 use crate::pac::TIM4;
 use crate::pac::{TIM2, TIM3};
 
-use crate::afio::MAPR;
+use crate::afio::{self, MAPR};
+use crate::gpio::{Edge, ExtiPin};
+use crate::pac::EXTI;
+use crate::time::MicroSeconds;
 
 use crate::timer::pwm_input::Pins;
 use crate::timer::{pins::sealed::Remap, Timer};
@@ -146,6 +172,37 @@ impl Timer<TIM4> {
     }
 }
 
+/// Quadrature encoder with a 64-bit position, optionally zeroed by the `INDEX` pulse
+pub struct ExtendedQei<TIM, REMAP, PINS, INDEX = ()> {
+    qei: Qei<TIM, REMAP, PINS>,
+    index: INDEX,
+    base: i64,
+    last: i64,
+    range: u32,
+}
+
+impl<TIM, REMAP, PINS> ExtendedQei<TIM, REMAP, PINS> {
+    /// Zeroes the position on the `edge`s of the index pulse on `pin`
+    pub fn with_index<INDEX: ExtiPin>(
+        self,
+        mut pin: INDEX,
+        afio: &mut afio::Parts,
+        exti: &mut EXTI,
+        edge: Edge,
+    ) -> ExtendedQei<TIM, REMAP, PINS, INDEX> {
+        pin.make_interrupt_source(afio);
+        pin.trigger_on_edge(exti, edge);
+        pin.enable_interrupt(exti);
+        ExtendedQei {
+            qei: self.qei,
+            index: pin,
+            base: self.base,
+            last: self.last,
+            range: self.range,
+        }
+    }
+}
+
 macro_rules! hal {
     ($($TIMX:ident: ($timX:ident, $timXen:ident, $timXrst:ident),)+) => {
         $(
@@ -178,6 +235,119 @@ macro_rules! hal {
                 pub fn release(self) -> ($TIMX, PINS) {
                     (self.tim, self.pins)
                 }
+
+                /// Tracks the position beyond the counter range, with the update interrupt of
+                /// the timer
+                pub fn extended(self) -> ExtendedQei<$TIMX, REMAP, PINS> {
+                    // Only the overflows and underflows set the update flag
+                    self.tim.cr1.modify(|_, w| w.urs().set_bit());
+                    self.tim.sr.write(|w| unsafe { w.bits(0xffff) }.uif().clear_bit());
+                    self.tim.dier.modify(|_, w| w.uie().set_bit());
+                    let range = self.tim.arr.read().arr().bits() as u32 + 1;
+                    ExtendedQei {
+                        qei: self,
+                        index: (),
+                        base: 0,
+                        last: 0,
+                        range,
+                    }
+                }
+            }
+
+            impl<REMAP, PINS, INDEX> ExtendedQei<$TIMX, REMAP, PINS, INDEX> {
+                /// Counts the overflow or underflow of the counter, to be called from the
+                /// timer interrupt handler
+                ///
+                /// The interrupt must not be delayed by more than half the counter range.
+                pub fn handle_interrupt(&mut self) {
+                    let tim = &self.qei.tim;
+                    if tim.sr.read().uif().bit_is_set() {
+                        tim.sr.write(|w| unsafe { w.bits(0xffff) }.uif().clear_bit());
+                        self.base += self.wrap(tim.cnt.read().cnt().bits());
+                    }
+                }
+
+                /// Offset of the wrap of the counter to `count`, up if it is in the lower half
+                /// of the range
+                fn wrap(&self, count: u16) -> i64 {
+                    if (count as u32) < self.range / 2 {
+                        self.range as i64
+                    } else {
+                        -(self.range as i64)
+                    }
+                }
+
+                /// Current position
+                pub fn position(&self) -> i64 {
+                    let tim = &self.qei.tim;
+                    let count = tim.cnt.read().cnt().bits();
+                    // The wrap has not been counted by the interrupt yet
+                    if tim.sr.read().uif().bit_is_set() {
+                        let count = tim.cnt.read().cnt().bits();
+                        self.base + self.wrap(count) + count as i64
+                    } else {
+                        self.base + count as i64
+                    }
+                }
+
+                /// Sets the current position
+                pub fn set_position(&mut self, position: i64) {
+                    let tim = &self.qei.tim;
+                    let count = position.rem_euclid(self.range as i64);
+                    tim.cnt.write(|w| w.cnt().bits(count as u16));
+                    tim.sr.write(|w| unsafe { w.bits(0xffff) }.uif().clear_bit());
+                    self.base = position - count;
+                    self.last = position;
+                }
+
+                /// Speed in counts per second since the previous call, `dt` ago
+                ///
+                /// To be called periodically, e.g. from the interrupt of another timer.
+                pub fn velocity(&mut self, dt: MicroSeconds) -> i32 {
+                    let position = self.position();
+                    let delta = position - self.last;
+                    self.last = position;
+                    (delta * 1_000_000 / dt.ticks().max(1) as i64) as i32
+                }
+
+                /// Direction of the last count
+                pub fn direction(&self) -> Direction {
+                    hal::Qei::direction(&self.qei)
+                }
+            }
+
+            impl<REMAP, PINS> ExtendedQei<$TIMX, REMAP, PINS> {
+                /// Disables the update interrupt and returns the encoder interface
+                pub fn release(self) -> Qei<$TIMX, REMAP, PINS> {
+                    self.qei.tim.dier.modify(|_, w| w.uie().clear_bit());
+                    self.qei
+                }
+            }
+
+            impl<REMAP, PINS, INDEX: ExtiPin> ExtendedQei<$TIMX, REMAP, PINS, INDEX> {
+                /// Zeroes the position on the index pulse, to be called from the EXTI interrupt
+                /// handler of the index pin
+                ///
+                /// Returns true if the index pulse has been received.
+                pub fn handle_index_interrupt(&mut self) -> bool {
+                    if !self.index.check_interrupt() {
+                        return false;
+                    }
+                    self.index.clear_interrupt_pending_bit();
+                    let position = self.position();
+                    let last = self.last;
+                    self.set_position(0);
+                    // The velocity is kept across the index
+                    self.last = last - position;
+                    true
+                }
+
+                /// Disables the interrupts and returns the encoder interface and the index pin
+                pub fn release(mut self, exti: &mut EXTI) -> (Qei<$TIMX, REMAP, PINS>, INDEX) {
+                    self.index.disable_interrupt(exti);
+                    self.qei.tim.dier.modify(|_, w| w.uie().clear_bit());
+                    (self.qei, self.index)
+                }
             }
 
             impl<REMAP, PINS> hal::Qei for Qei<$TIMX, REMAP, PINS> {
@@ -60,55 +60,196 @@
 //! // Receive a data (8 bits) from the USART and store it in "received"
 //! let received = block!(rx.read()).unwrap();
 //!  ```
+//!
+//! ## Hardware flow control
+//!
+//! RTS and CTS are enabled by passing the pins as `(tx, rx, rts, cts)`, with `rts` configured
+//! as `Alternate<...>` and `cts` as `Input<...>`. `NoRts` or `NoCts` can be used to only
+//! enable one of them.
+//!
+//! ## UART4 and UART5
+//!
+//! On high-density STM32F103 devices, UART4 (`(PC10, PC11)`) and UART5 (`(PC12, PD2)`) are
+//! also available. They have no flow control, no clock output and no smartcard mode, and only
+//! UART4 supports DMA.
 
 use core::convert::Infallible;
 use core::marker::PhantomData;
-use core::ops::Deref;
+use core::ptr;
 use core::sync::atomic::{self, Ordering};
 use embedded_dma::{ReadBuffer, WriteBuffer};
 
 use crate::afio::MAPR;
+#[cfg(all(feature = "stm32f103", feature = "high"))]
+use crate::dma::dma2;
 use crate::dma::{dma1, CircBuffer, RxDma, Transfer, TxDma, R, W};
-use crate::gpio::{self, Alternate, Input};
+use crate::gpio::{self, Alternate, Input, OpenDrain};
 use crate::pac::{RCC, USART1, USART2, USART3};
+#[cfg(all(feature = "stm32f103", feature = "high"))]
+use crate::pac::{UART4, UART5};
 use crate::rcc::{BusClock, Clocks, Enable, Reset};
-use crate::time::{Bps, U32Ext};
+use crate::time::{Bps, Hertz, U32Ext};
+
+pub mod buffered;
+pub use buffered::{BufferedSerial, OverflowPolicy, ReceiveError};
+pub mod rs485;
+pub use rs485::Rs485;
+pub mod modbus;
+pub use modbus::RtuReceiver;
+pub mod writer;
+pub use writer::SerialWriter;
+pub mod timeout;
+pub use timeout::RxWithTimeout;
+#[cfg(feature = "async")]
+pub mod asynch;
 
 // USART REMAPPING, see: https://www.st.com/content/ccc/resource/technical/document/reference_manual/59/b9/ba/7f/11/af/43/d5/CD00171190.pdf/files/CD00171190.pdf/jcr:content/translations/en.CD00171190.pdf
 // Section 9.3.8
 pub trait Pins<USART> {
+    /// Hardware RTS flow control is used
+    const RTS: bool = false;
+    /// Hardware CTS flow control is used
+    const CTS: bool = false;
+
+    fn remap(mapr: &mut MAPR);
+}
+
+/// TX pin of a single-wire half-duplex interface
+pub trait TxPin<USART> {
+    fn remap(mapr: &mut MAPR);
+}
+
+/// `(tx, ck)` pins of a smartcard interface
+pub trait SmartcardPins<USART> {
     fn remap(mapr: &mut MAPR);
 }
 
+/// `(tx, rx, ck)` pins of a synchronous interface
+pub trait SynchronousPins<USART> {
+    fn remap(mapr: &mut MAPR);
+}
+
+/// A filler type for when hardware RTS flow control is unused
+pub struct NoRts;
+/// A filler type for when hardware CTS flow control is unused
+pub struct NoCts;
+
+mod sealed {
+    /// RTS pin of `USART` with the pin remapping `REMAP`
+    pub trait Rts<USART, const REMAP: u8> {
+        const ENABLED: bool;
+    }
+    /// CTS pin of `USART` with the pin remapping `REMAP`
+    pub trait Cts<USART, const REMAP: u8> {
+        const ENABLED: bool;
+    }
+    /// CK pin of `USART` with the pin remapping `REMAP`
+    pub trait Ck<USART, const REMAP: u8> {}
+}
+use sealed::{Ck, Cts, Rts};
+
+impl<USART, const REMAP: u8> Rts<USART, REMAP> for NoRts {
+    const ENABLED: bool = false;
+}
+impl<USART, const REMAP: u8> Cts<USART, REMAP> for NoCts {
+    const ENABLED: bool = false;
+}
+
 macro_rules! remap {
-    ($($USART:ty, $TX:ident, $RX:ident => { $remapex:expr };)+) => {
+    ($($USART:ty, $TX:ident, $RX:ident, $RTS:ident, $CTS:ident, $CK:ident, $id:literal => { $remapex:expr };)+) => {
         $(
             impl<INMODE, OUTMODE> Pins<$USART> for (gpio::$TX<Alternate<OUTMODE>>, gpio::$RX<Input<INMODE>>) {
                 fn remap(mapr: &mut MAPR) {
                     mapr.modify_mapr($remapex);
                 }
             }
+
+            impl<INMODE, OUTMODE, RTS, CTS> Pins<$USART> for (gpio::$TX<Alternate<OUTMODE>>, gpio::$RX<Input<INMODE>>, RTS, CTS)
+            where
+                RTS: Rts<$USART, $id>,
+                CTS: Cts<$USART, $id>,
+            {
+                const RTS: bool = RTS::ENABLED;
+                const CTS: bool = CTS::ENABLED;
+
+                fn remap(mapr: &mut MAPR) {
+                    mapr.modify_mapr($remapex);
+                }
+            }
+
+            impl TxPin<$USART> for gpio::$TX<Alternate<OpenDrain>> {
+                fn remap(mapr: &mut MAPR) {
+                    mapr.modify_mapr($remapex);
+                }
+            }
+
+            impl<CK> SmartcardPins<$USART> for (gpio::$TX<Alternate<OpenDrain>>, CK)
+            where
+                CK: Ck<$USART, $id>,
+            {
+                fn remap(mapr: &mut MAPR) {
+                    mapr.modify_mapr($remapex);
+                }
+            }
+
+            impl<INMODE, OUTMODE, CK> SynchronousPins<$USART> for (gpio::$TX<Alternate<OUTMODE>>, gpio::$RX<Input<INMODE>>, CK)
+            where
+                CK: Ck<$USART, $id>,
+            {
+                fn remap(mapr: &mut MAPR) {
+                    mapr.modify_mapr($remapex);
+                }
+            }
+
+            impl<MODE> Ck<$USART, $id> for gpio::$CK<Alternate<MODE>> {}
+
+            impl<MODE> Rts<$USART, $id> for gpio::$RTS<Alternate<MODE>> {
+                const ENABLED: bool = true;
+            }
+            impl<MODE> Cts<$USART, $id> for gpio::$CTS<Input<MODE>> {
+                const ENABLED: bool = true;
+            }
         )+
     }
 }
 
 remap!(
-    USART1, PA9, PA10 => { |_, w| w.usart1_remap().bit(false) };
-    USART1, PB6, PB7  => { |_, w| w.usart1_remap().bit(true) };
+    USART1, PA9, PA10, PA12, PA11, PA8, 0 => { |_, w| w.usart1_remap().bit(false) };
+    USART1, PB6, PB7, PA12, PA11, PA8, 1  => { |_, w| w.usart1_remap().bit(true) };
+
+    USART2, PA2, PA3, PA1, PA0, PA4, 0 => { |_, w| w.usart2_remap().bit(false) };
+    USART2, PD5, PD6, PD4, PD3, PD7, 1 => { |_, w| w.usart2_remap().bit(true) };
+
+    USART3, PB10, PB11, PB14, PB13, PB12, 0 => { |_, w| unsafe { w.usart3_remap().bits(0b00)} };
+    USART3, PC10, PC11, PB14, PB13, PC12, 1 => { |_, w| unsafe { w.usart3_remap().bits(0b01)} };
+    USART3, PD8, PD9, PD12, PD11, PD10, 3 => { |_, w| unsafe { w.usart3_remap().bits(0b11)} };
+);
 
-    USART2, PA2, PA3 => { |_, w| w.usart2_remap().bit(false) };
-    USART2, PD5, PD6 => { |_, w| w.usart2_remap().bit(true) };
+// UARTs without pin remapping, flow control and clock output
+#[cfg(all(feature = "stm32f103", feature = "high"))]
+macro_rules! uart_pins {
+    ($($UART:ty, $TX:ident, $RX:ident;)+) => {
+        $(
+            impl<INMODE, OUTMODE> Pins<$UART> for (gpio::$TX<Alternate<OUTMODE>>, gpio::$RX<Input<INMODE>>) {
+                fn remap(_mapr: &mut MAPR) {}
+            }
+
+            impl TxPin<$UART> for gpio::$TX<Alternate<OpenDrain>> {
+                fn remap(_mapr: &mut MAPR) {}
+            }
+        )+
+    }
+}
 
-    USART3, PB10, PB11 => { |_, w| unsafe { w.usart3_remap().bits(0b00)} };
-    USART3, PC10, PC11 => { |_, w| unsafe { w.usart3_remap().bits(0b01)} };
-    USART3, PD8, PD9 => { |_, w| unsafe { w.usart3_remap().bits(0b11)} };
+#[cfg(all(feature = "stm32f103", feature = "high"))]
+uart_pins!(
+    UART4, PC10, PC11;
+    UART5, PC12, PD2;
 );
 
 use crate::pac::usart1 as uart_base;
 
-pub trait Instance:
-    crate::Sealed + Deref<Target = uart_base::RegisterBlock> + Enable + Reset + BusClock
-{
+pub trait Instance: crate::Sealed + Enable + Reset + BusClock {
     #[doc(hidden)]
     fn ptr() -> *const uart_base::RegisterBlock;
 }
@@ -130,9 +271,14 @@ inst! {
     USART2
     USART3
 }
+#[cfg(all(feature = "stm32f103", feature = "high"))]
+inst! {
+    UART4
+    UART5
+}
 
 /// Serial error
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum Error {
     /// Framing error
@@ -143,6 +289,12 @@ pub enum Error {
     Overrun,
     /// Parity check error
     Parity,
+    /// Break received, a framing error on an all-zero word
+    Break,
+    /// No data received before the timeout, see `RxWithTimeout`
+    Timeout,
+    /// The timeout is out of the range of the timer, see `RxWithTimeout`
+    Timer(crate::timer::Error),
 }
 
 pub enum WordLength {
@@ -242,6 +394,54 @@ impl From<Bps> for Config {
     }
 }
 
+/// Smartcard (ISO 7816-3) settings
+#[derive(Clone, Copy, Debug)]
+pub struct SmartcardConfig {
+    pub baudrate: Bps,
+    /// Card clock output on CK, divided down from the USART clock
+    pub clock: Hertz,
+    /// Guard time in baud clock cycles
+    pub guard_time: u8,
+    /// Sends a NACK on parity errors
+    pub nack: bool,
+}
+
+impl SmartcardConfig {
+    /// Settings for a card clocked at `clock`, at the default rate of `clock / 372`
+    pub fn new(clock: Hertz) -> Self {
+        Self {
+            baudrate: Bps(clock.raw() / 372),
+            clock,
+            guard_time: 2,
+            nack: true,
+        }
+    }
+
+    pub fn baudrate(mut self, baudrate: Bps) -> Self {
+        self.baudrate = baudrate;
+        self
+    }
+
+    pub fn guard_time(mut self, guard_time: u8) -> Self {
+        self.guard_time = guard_time;
+        self
+    }
+
+    pub fn nack(mut self, nack: bool) -> Self {
+        self.nack = nack;
+        self
+    }
+}
+
+/// IrDA SIR power mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IrdaMode {
+    /// Pulses of 3/16 of a bit period
+    Normal,
+    /// Pulses of 3 periods of the ~1.8432 MHz low-power clock
+    LowPower,
+}
+
 /// Serial abstraction
 pub struct Serial<USART, PINS> {
     pub tx: Tx<USART>,
@@ -257,6 +457,8 @@ pub struct Tx<USART> {
 /// Serial receiver
 pub struct Rx<USART> {
     _usart: PhantomData<USART>,
+    // Error received after the first byte of an `embedded_io` read
+    pending: Option<Error>,
 }
 
 /// Stores data for release
@@ -266,6 +468,10 @@ pub struct ReleaseToken<USART, PINS> {
 }
 
 impl<USART: Instance, PINS> Serial<USART, PINS> {
+    fn usart(&self) -> &uart_base::RegisterBlock {
+        unsafe { &*USART::ptr() }
+    }
+
     /// Configures the serial interface and creates the interface
     /// struct.
     ///
@@ -291,19 +497,179 @@ impl<USART: Instance, PINS> Serial<USART, PINS> {
     where
         PINS: Pins<USART>,
     {
+        PINS::remap(mapr);
+
+        Self::init(usart, pins, config.into(), clocks, |usart| {
+            // RTSE/CTSE: hardware flow control when the pins are given
+            usart
+                .cr3
+                .modify(|_, w| w.rtse().bit(PINS::RTS).ctse().bit(PINS::CTS));
+        })
+    }
+
+    /// Configures the serial interface in single-wire half-duplex mode
+    ///
+    /// Only the TX pin is used, configured as `Alternate<OpenDrain>` with an external pull-up.
+    /// Everything transmitted is also received, use `half_duplex_transmit` to ignore it.
+    pub fn new_half_duplex(
+        usart: USART,
+        pins: PINS,
+        mapr: &mut MAPR,
+        config: impl Into<Config>,
+        clocks: &Clocks,
+    ) -> Self
+    where
+        PINS: TxPin<USART>,
+    {
+        PINS::remap(mapr);
+
+        Self::init(usart, pins, config.into(), clocks, |usart| {
+            usart.cr3.modify(|_, w| w.hdsel().set_bit());
+        })
+    }
+
+    /// Configures the serial interface as an IrDA SIR encoder/decoder
+    ///
+    /// The TX pin drives the transceiver input, the RX pin receives its output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the baud rate is above the 115.2 kbps supported by SIR, or if the low-power
+    /// clock can't be brought between 1.42 and 2.12 MHz.
+    pub fn new_irda(
+        usart: USART,
+        pins: PINS,
+        mapr: &mut MAPR,
+        config: impl Into<Config>,
+        mode: IrdaMode,
+        clocks: &Clocks,
+    ) -> Self
+    where
+        PINS: Pins<USART>,
+    {
+        let config = config.into();
+        assert!(
+            config.baudrate.0 <= 115_200,
+            "baud rate too high for IrDA SIR"
+        );
+        assert!(
+            matches!(config.stopbits, StopBits::STOP1),
+            "IrDA SIR requires 1 stop bit"
+        );
+
+        // Normal mode requires a prescaler of 1, the low-power mode divides the clock down to
+        // about 1.8432 MHz
+        let psc = match mode {
+            IrdaMode::Normal => 1,
+            IrdaMode::LowPower => irda_low_power_psc(USART::clock(clocks).raw()),
+        };
+
+        PINS::remap(mapr);
+
+        Self::init(usart, pins, config, clocks, |usart| {
+            usart.gtpr.modify(|_, w| unsafe { w.psc().bits(psc) });
+            usart.cr3.modify(|_, w| {
+                w.iren()
+                    .set_bit()
+                    .irlp()
+                    .bit(matches!(mode, IrdaMode::LowPower))
+            });
+        })
+    }
+
+    /// Configures the serial interface in smartcard mode
+    ///
+    /// The TX pin configured as `Alternate<OpenDrain>` is the card I/O line, the CK pin
+    /// provides the card clock. The frames have 8 data bits, even parity and 1.5 stop bits.
+    pub fn new_smartcard(
+        usart: USART,
+        pins: PINS,
+        mapr: &mut MAPR,
+        config: SmartcardConfig,
+        clocks: &Clocks,
+    ) -> Self
+    where
+        PINS: SmartcardPins<USART>,
+    {
+        let psc = smartcard_psc(USART::clock(clocks).raw(), config.clock.raw());
+
+        PINS::remap(mapr);
+
+        let frame = Config::default()
+            .baudrate(config.baudrate)
+            .wordlength_9bits()
+            .parity_even()
+            .stopbits(StopBits::STOP1P5);
+        Self::init(usart, pins, frame, clocks, |usart| {
+            usart
+                .gtpr
+                .write(|w| unsafe { w.gt().bits(config.guard_time).psc().bits(psc) });
+            usart.cr2.modify(|_, w| w.clken().set_bit());
+            usart
+                .cr3
+                .modify(|_, w| w.scen().set_bit().nack().bit(config.nack));
+        })
+    }
+
+    /// Configures the serial interface in synchronous master mode, outputting the bit clock
+    /// on CK
+    ///
+    /// The pins `(tx, rx, ck)` should be configured as `(Alternate<...>, Input<...>,
+    /// Alternate<...>)`. The data is shifted LSB first, `mode` giving the clock polarity and
+    /// phase. The clock pulse of the last data bit is only output with `last_bit_clock`.
+    pub fn new_synchronous(
+        usart: USART,
+        pins: PINS,
+        mapr: &mut MAPR,
+        config: impl Into<Config>,
+        mode: crate::hal::spi::Mode,
+        last_bit_clock: bool,
+        clocks: &Clocks,
+    ) -> Self
+    where
+        PINS: SynchronousPins<USART>,
+    {
+        use crate::hal::spi::{Phase, Polarity};
+
+        PINS::remap(mapr);
+
+        Self::init(usart, pins, config.into(), clocks, |usart| {
+            usart.cr2.modify(|_, w| {
+                w.clken()
+                    .set_bit()
+                    .cpol()
+                    .bit(mode.polarity == Polarity::IdleHigh)
+                    .cpha()
+                    .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                    .lbcl()
+                    .bit(last_bit_clock)
+            });
+        })
+    }
+
+    /// Enables, resets and configures the USART, `mode` selecting the special mode of operation
+    /// before the USART is enabled
+    fn init(
+        usart: USART,
+        pins: PINS,
+        config: Config,
+        clocks: &Clocks,
+        mode: impl FnOnce(&uart_base::RegisterBlock),
+    ) -> Self {
         // Enable and reset USART
         let rcc = unsafe { &(*RCC::ptr()) };
         USART::enable(rcc);
         USART::reset(rcc);
 
-        PINS::remap(mapr);
+        apply_config::<USART>(config, clocks);
 
-        apply_config::<USART>(config.into(), clocks);
+        let regs = unsafe { &*USART::ptr() };
+        mode(regs);
 
         // UE: enable USART
         // TE: enable transceiver
         // RE: enable receiver
-        usart.cr1.modify(|_r, w| {
+        regs.cr1.modify(|_r, w| {
             w.ue().set_bit();
             w.te().set_bit();
             w.re().set_bit();
@@ -316,15 +682,29 @@ impl<USART: Instance, PINS> Serial<USART, PINS> {
             },
             rx: Rx {
                 _usart: PhantomData,
+                pending: None,
             },
             token: ReleaseToken { usart, pins },
         }
     }
 
+    /// Switches a half-duplex interface to transmission, disabling the receiver so the
+    /// transmitted data is not received back
+    pub fn half_duplex_transmit(&mut self) {
+        self.usart().cr1.modify(|_, w| w.re().clear_bit());
+    }
+
+    /// Switches a half-duplex interface to reception once the transmission is complete
+    pub fn half_duplex_receive(&mut self) {
+        let _ = self.tx.bflush();
+        self.usart().cr1.modify(|_, w| w.re().set_bit());
+    }
+
     /// Reconfigure the USART instance.
     ///
     /// If a transmission is currently in progress, this returns
-    /// [`nb::Error::WouldBlock`].
+    /// [`nb::Error::WouldBlock`]. The baud rate, word length, parity and stop bits are updated
+    /// while the interrupts, DMA requests and special modes stay enabled.
     pub fn reconfigure(
         &mut self,
         config: impl Into<Config>,
@@ -456,6 +836,18 @@ impl<USART: Instance> Tx<USART> {
         Ok(())
     }
 
+    /// Transmits a break after the current transmission
+    ///
+    /// The break is received as `Error::Break`.
+    pub fn send_break(&mut self) {
+        unsafe { (*USART::ptr()).cr1.modify(|_, w| w.sbk().set_bit()) };
+    }
+
+    /// Returns true while the break is being transmitted
+    pub fn is_sending_break(&self) -> bool {
+        unsafe { (*USART::ptr()).cr1.read().sbk().bit_is_set() }
+    }
+
     pub fn flush(&mut self) -> nb::Result<(), Infallible> {
         let usart = unsafe { &*USART::ptr() };
 
@@ -547,6 +939,31 @@ impl<USART: Instance> core::fmt::Write for Tx<USART> {
 }
 
 impl<USART: Instance> Rx<USART> {
+    /// Returns the error kept by the previous `embedded_io` read
+    pub(crate) fn take_pending(&mut self) -> Result<(), Error> {
+        self.pending.take().map_or(Ok(()), Err)
+    }
+
+    /// Stores the bytes already received into `buf` without blocking and returns their count
+    ///
+    /// An error is kept for the next `embedded_io` read, so the bytes stored before it are
+    /// returned first.
+    pub(crate) fn read_received(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        for word in buf {
+            match self.read() {
+                Ok(w) => *word = w,
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(e)) => {
+                    self.pending = Some(e);
+                    break;
+                }
+            }
+            count += 1;
+        }
+        count
+    }
+
     /// Reads 9-bit words from the UART/USART
     ///
     /// If the UART/USART was configured with `WordLength::Bits9`, the returned value will contain
@@ -573,8 +990,12 @@ impl<USART: Instance> Rx<USART> {
             // Some error occurred. In order to clear that error flag, you have to
             // do a read from the sr register followed by a read from the dr register.
             let _ = usart.sr.read();
-            let _ = usart.dr.read();
-            Err(nb::Error::Other(err))
+            let word = usart.dr.read().dr().bits();
+            Err(nb::Error::Other(if err == Error::Framing && word == 0 {
+                Error::Break
+            } else {
+                err
+            }))
         } else {
             // Check if a byte is available
             if sr.rxne().bit_is_set() {
@@ -610,6 +1031,19 @@ impl<USART: Instance> Rx<USART> {
         unsafe { (*USART::ptr()).cr1.modify(|_, w| w.idleie().clear_bit()) };
     }
 
+    /// Start listening for error interrupt event
+    ///
+    /// The interrupt is raised on framing, noise and overrun errors, including received
+    /// breaks, while receiving with DMA. Otherwise these errors come with the receive interrupt.
+    pub fn listen_error(&mut self) {
+        unsafe { (*USART::ptr()).cr3.modify(|_, w| w.eie().set_bit()) };
+    }
+
+    /// Stop listening for error interrupt event
+    pub fn unlisten_error(&mut self) {
+        unsafe { (*USART::ptr()).cr3.modify(|_, w| w.eie().clear_bit()) };
+    }
+
     /// Returns true if the line idle status is set
     pub fn is_idle(&self) -> bool {
         unsafe { (*USART::ptr()).sr.read().idle().bit_is_set() }
@@ -653,6 +1087,10 @@ pub enum Event {
     Rxne,
     /// Idle line state detected
     Idle,
+    /// LIN break detected
+    LinBreak,
+    /// Framing, noise or overrun error detected while receiving with DMA
+    Error,
 }
 
 impl<USART: Instance, PINS> Serial<USART, PINS> {
@@ -664,6 +1102,8 @@ impl<USART: Instance, PINS> Serial<USART, PINS> {
             Event::Rxne => self.rx.listen(),
             Event::Txe => self.tx.listen(),
             Event::Idle => self.rx.listen_idle(),
+            Event::LinBreak => self.usart().cr2.modify(|_, w| w.lbdie().set_bit()),
+            Event::Error => self.rx.listen_error(),
         }
     }
 
@@ -675,6 +1115,8 @@ impl<USART: Instance, PINS> Serial<USART, PINS> {
             Event::Rxne => self.rx.unlisten(),
             Event::Txe => self.tx.unlisten(),
             Event::Idle => self.rx.unlisten_idle(),
+            Event::LinBreak => self.usart().cr2.modify(|_, w| w.lbdie().clear_bit()),
+            Event::Error => self.rx.unlisten_error(),
         }
     }
 
@@ -699,6 +1141,140 @@ impl<USART: Instance, PINS> Serial<USART, PINS> {
     }
 }
 
+/// Event waking the receiver up from mute mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wakeup {
+    /// Idle line detected
+    IdleLine,
+    /// Address mark, a word with its MSB set, matching the node address
+    AddressMark,
+}
+
+impl<USART: Instance, PINS> Serial<USART, PINS> {
+    /// Selects how the receiver is woken up from mute mode
+    pub fn set_wakeup(&mut self, wakeup: Wakeup) {
+        self.usart()
+            .cr1
+            .modify(|_, w| w.wake().bit(wakeup == Wakeup::AddressMark));
+    }
+
+    /// Sets the 4-bit address of this node in a multiprocessor network
+    pub fn set_node_address(&mut self, address: u8) {
+        self.usart().cr2.modify(|_, w| w.add().bits(address & 0xf));
+    }
+
+    /// Mutes the receiver until the wakeup event selected with `set_wakeup`
+    pub fn enter_mute(&mut self) {
+        self.usart().cr1.modify(|_, w| w.rwu().set_bit());
+    }
+
+    /// Unmutes the receiver
+    pub fn exit_mute(&mut self) {
+        self.usart().cr1.modify(|_, w| w.rwu().clear_bit());
+    }
+
+    /// Returns true if the receiver is muted
+    pub fn is_muted(&self) -> bool {
+        self.usart().cr1.read().rwu().bit_is_set()
+    }
+
+    /// Transmits an address mark selecting the node `address`
+    ///
+    /// The MSB of the word is set, which is the 9th bit with `WordLength::Bits9`.
+    pub fn write_address(&mut self, address: u8) -> nb::Result<(), Infallible> {
+        let mark = if self.usart().cr1.read().m().bit_is_set() {
+            0x100
+        } else {
+            0x80
+        };
+        self.tx.write_u16(mark | u16::from(address & 0xf))
+    }
+}
+
+/// Length of the LIN break detected by the receiver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinBreakLength {
+    /// 10-bit break detection
+    Bits10,
+    /// 11-bit break detection
+    Bits11,
+}
+
+/// IrDA low-power prescaler dividing `pclk` down to about 1.8432 MHz
+fn irda_low_power_psc(pclk: u32) -> u8 {
+    let psc = ((pclk + 921_600) / 1_843_200).clamp(1, 255);
+    let freq = pclk / psc;
+    assert!(
+        (1_420_000..=2_120_000).contains(&freq),
+        "impossible IrDA low-power frequency"
+    );
+    psc as u8
+}
+
+/// Smartcard prescaler giving a card clock of at most `clock`, CK = PCLK / (2 * PSC)
+fn smartcard_psc(pclk: u32, clock: u32) -> u8 {
+    ((pclk + 2 * clock - 1) / (2 * clock)).clamp(1, 31) as u8
+}
+
+/// Returns the LIN protected identifier of the 6-bit frame `id`, with its two parity bits
+pub fn lin_protected_id(id: u8) -> u8 {
+    let id = id & 0x3f;
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+    id | (p0 << 6) | (p1 << 7)
+}
+
+impl<USART: Instance, PINS> Serial<USART, PINS> {
+    /// Configures the serial interface in LIN mode, with 8 data bits, no parity and 1 stop bit
+    pub fn new_lin(
+        usart: USART,
+        pins: PINS,
+        mapr: &mut MAPR,
+        baudrate: Bps,
+        break_length: LinBreakLength,
+        clocks: &Clocks,
+    ) -> Self
+    where
+        PINS: Pins<USART>,
+    {
+        PINS::remap(mapr);
+
+        Self::init(usart, pins, Config::from(baudrate), clocks, |usart| {
+            usart.cr2.modify(|_, w| {
+                w.linen()
+                    .set_bit()
+                    .lbdl()
+                    .bit(break_length == LinBreakLength::Bits11)
+            });
+        })
+    }
+
+    /// Transmits a break after the current transmission
+    pub fn send_break(&mut self) {
+        self.tx.send_break();
+    }
+
+    /// Returns true if a LIN break has been detected
+    pub fn is_lin_break(&self) -> bool {
+        self.usart().sr.read().lbd().bit_is_set()
+    }
+
+    /// Clears the LIN break detection flag
+    pub fn clear_lin_break(&mut self) {
+        self.usart().sr.modify(|_, w| w.lbd().clear_bit());
+    }
+
+    /// Transmits a LIN frame header: the break, the sync field and the protected identifier of
+    /// the 6-bit frame `id`
+    pub fn send_lin_header(&mut self, id: u8) {
+        nb::block!(self.tx.flush()).ok();
+        self.send_break();
+        nb::block!(self.tx.write(0x55)).ok();
+        nb::block!(self.tx.write(lin_protected_id(id))).ok();
+    }
+}
+
 impl<USART: Instance, PINS> embedded_hal::serial::Write<u8> for Serial<USART, PINS> {
     type Error = Infallible;
 
@@ -778,6 +1354,15 @@ pub type Tx3 = Tx<USART3>;
 
 use crate::dma::{Receive, TransferPayload, Transmit};
 
+/// Receiver continuously writing into a ring buffer through circular DMA
+///
+/// Created by `circ_read_idle`, the USART IDLE interrupt marking the end of each frame.
+pub struct RxRingBuffer<PAYLOAD, const N: usize> {
+    buffer: &'static mut [u8; N],
+    payload: PAYLOAD,
+    read_pos: usize,
+}
+
 macro_rules! serialdma {
     ($(
         $USARTX:ident: (
@@ -869,6 +1454,120 @@ macro_rules! serialdma {
                         channel,
                     )
                 }
+
+                /// Sends `buffer` through DMA, blocking until the last byte has been
+                /// transmitted on the line
+                ///
+                /// Returns `dma::Error::TransferError` if the transfer is aborted by a bus
+                /// error, the rest of `buffer` is not sent.
+                pub fn write_all(&mut self, buffer: &[u8]) -> Result<(), crate::dma::Error> {
+                    for chunk in buffer.chunks(u16::MAX as usize) {
+                        self.channel.set_peripheral_address(unsafe{ &(*$USARTX::ptr()).dr as *const _ as u32 }, false);
+                        self.channel.set_memory_address(chunk.as_ptr() as u32, true);
+                        self.channel.set_transfer_length(chunk.len());
+
+                        atomic::compiler_fence(Ordering::Release);
+
+                        self.channel.ch().cr.modify(|_, w| { w
+                            .mem2mem() .clear_bit()
+                            .msize()   .bits8()
+                            .psize()   .bits8()
+                            .circ()    .clear_bit()
+                            .dir()     .set_bit()
+                        });
+                        self.start();
+
+                        while self.channel.in_progress() {
+                            // The channel is disabled by the error, TCIF is never set
+                            if self.channel.is_event_triggered(crate::dma::Event::TransferError) {
+                                atomic::compiler_fence(Ordering::Acquire);
+                                self.stop();
+                                return Err(crate::dma::Error::TransferError);
+                            }
+                        }
+                        atomic::compiler_fence(Ordering::Acquire);
+                        self.stop();
+                    }
+                    self.payload.bflush().ok();
+                    Ok(())
+                }
+
+                /// Returns true once the last byte of the transfer has been transmitted on the
+                /// line, the transfer being done as soon as it's written to the USART
+                pub fn is_tx_complete(&self) -> bool {
+                    self.payload.is_tx_complete()
+                }
+            }
+
+            impl $rxdma {
+                /// Receives continuously into the ring `buffer`, listening to the IDLE
+                /// interrupt so the frames of unknown length can be read once the line is idle
+                pub fn circ_read_idle<const N: usize>(mut self, buffer: &'static mut [u8; N]) -> RxRingBuffer<Self, N> {
+                    self.channel.set_peripheral_address(unsafe{ &(*$USARTX::ptr()).dr as *const _ as u32 }, false);
+                    self.channel.set_memory_address(buffer.as_ptr() as u32, true);
+                    self.channel.set_transfer_length(N);
+
+                    atomic::compiler_fence(Ordering::Release);
+
+                    self.channel.ch().cr.modify(|_, w| { w
+                        .mem2mem() .clear_bit()
+                        .msize()   .bits8()
+                        .psize()   .bits8()
+                        .circ()    .set_bit()
+                        .dir()     .clear_bit()
+                    });
+
+                    self.payload.listen_idle();
+                    self.start();
+
+                    RxRingBuffer { buffer, payload: self, read_pos: 0 }
+                }
+            }
+
+            impl<const N: usize> RxRingBuffer<$rxdma, N> {
+                fn write_pos(&self) -> usize {
+                    (N - self.payload.channel.get_ndtr() as usize) % N
+                }
+
+                /// Number of bytes received since the last `read`
+                ///
+                /// The count wraps around if more than `N - 1` bytes are not read in time.
+                pub fn available(&self) -> usize {
+                    (self.write_pos() + N - self.read_pos) % N
+                }
+
+                /// Copies the bytes received since the last call into `buf`, returning their
+                /// number
+                pub fn read(&mut self, buf: &mut [u8]) -> usize {
+                    let write_pos = self.write_pos();
+                    atomic::compiler_fence(Ordering::Acquire);
+
+                    let mut count = 0;
+                    while self.read_pos != write_pos && count < buf.len() {
+                        // NOTE(read_volatile) the DMA writes to the other bytes of the buffer
+                        buf[count] = unsafe { ptr::read_volatile(&self.buffer[self.read_pos]) };
+                        self.read_pos = (self.read_pos + 1) % N;
+                        count += 1;
+                    }
+                    count
+                }
+
+                /// Returns true if the line idle status is set
+                pub fn is_idle(&self) -> bool {
+                    self.payload.payload.is_idle()
+                }
+
+                /// Clear idle line interrupt flag
+                pub fn clear_idle_interrupt(&self) {
+                    self.payload.payload.clear_idle_interrupt();
+                }
+
+                /// Stops the transfer and returns the buffer and the receiver
+                pub fn stop(mut self) -> (&'static mut [u8; N], $rxdma) {
+                    self.payload.payload.unlisten_idle();
+                    self.payload.stop();
+                    (self.buffer, self.payload)
+                }
             }
 
             impl<B> crate::dma::CircReadDma<B, u8> for $rxdma
@@ -888,7 +1587,6 @@ macro_rules! serialdma {
 
                     self.channel.ch().cr.modify(|_, w| { w
                         .mem2mem() .clear_bit()
-                        .pl()      .medium()
                         .msize()   .bits8()
                         .psize()   .bits8()
                         .circ()    .set_bit()
@@ -916,7 +1614,6 @@ macro_rules! serialdma {
                     atomic::compiler_fence(Ordering::Release);
                     self.channel.ch().cr.modify(|_, w| { w
                         .mem2mem() .clear_bit()
-                        .pl()      .medium()
                         .msize()   .bits8()
                         .psize()   .bits8()
                         .circ()    .clear_bit()
@@ -946,7 +1643,6 @@ macro_rules! serialdma {
 
                     self.channel.ch().cr.modify(|_, w| { w
                         .mem2mem() .clear_bit()
-                        .pl()      .medium()
                         .msize()   .bits8()
                         .psize()   .bits8()
                         .circ()    .clear_bit()
@@ -957,6 +1653,20 @@ macro_rules! serialdma {
                     Transfer::r(buffer, self)
                 }
             }
+
+            impl<const N: usize> writer::WriterDma<N> for $txdma {
+                fn start(self, buffer: writer::WriterBuffer<N>) -> Transfer<R, writer::WriterBuffer<N>, Self> {
+                    crate::dma::WriteDma::write(self, buffer)
+                }
+
+                fn is_done(transfer: &Transfer<R, writer::WriterBuffer<N>, Self>) -> bool {
+                    transfer.is_done()
+                }
+
+                fn wait(transfer: Transfer<R, writer::WriterBuffer<N>, Self>) -> (writer::WriterBuffer<N>, Self) {
+                    transfer.wait()
+                }
+            }
         )+
     }
 }
@@ -981,3 +1691,129 @@ serialdma! {
         dma1::C2,
     ),
 }
+#[cfg(all(feature = "stm32f103", feature = "high"))]
+serialdma! {
+    UART4: (
+        RxDma4,
+        TxDma4,
+        dma2::C3,
+        dma2::C5,
+    ),
+}
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Error::Overrun => embedded_io::ErrorKind::Other,
+            Error::Timeout => embedded_io::ErrorKind::TimedOut,
+            Error::Timer(_) => embedded_io::ErrorKind::InvalidInput,
+            _ => embedded_io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl<USART> embedded_io::ErrorType for Tx<USART> {
+    type Error = Infallible;
+}
+
+impl<USART: Instance> embedded_io::Write for Tx<USART> {
+    /// Waits for room for the first byte, then queues as many bytes as possible without blocking
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        nb::block!(self.write(buf[0]))?;
+        let mut count = 1;
+        for &word in &buf[1..] {
+            match self.write(word) {
+                Ok(()) => count += 1,
+                Err(_) => break,
+            }
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Infallible> {
+        self.bflush()
+    }
+}
+
+impl<USART: Instance> embedded_io::WriteReady for Tx<USART> {
+    fn write_ready(&mut self) -> Result<bool, Infallible> {
+        Ok(self.is_tx_empty())
+    }
+}
+
+impl<USART> embedded_io::ErrorType for Rx<USART> {
+    type Error = Error;
+}
+
+impl<USART: Instance> embedded_io::Read for Rx<USART> {
+    /// Waits for the first byte, then returns the bytes received without blocking
+    ///
+    /// An error received after the first byte is returned by the next read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.take_pending()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = nb::block!(self.read())?;
+        Ok(1 + self.read_received(&mut buf[1..]))
+    }
+}
+
+impl<USART: Instance> embedded_io::ReadReady for Rx<USART> {
+    fn read_ready(&mut self) -> Result<bool, Error> {
+        Ok(self.is_rx_not_empty())
+    }
+}
+
+impl<USART, PINS> embedded_io::ErrorType for Serial<USART, PINS> {
+    type Error = Error;
+}
+
+impl<USART: Instance, PINS> embedded_io::Write for Serial<USART, PINS> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        embedded_io::Write::write(&mut self.tx, buf).map_err(|e| match e {})
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.tx.bflush().map_err(|e| match e {})
+    }
+}
+
+impl<USART: Instance, PINS> embedded_io::WriteReady for Serial<USART, PINS> {
+    fn write_ready(&mut self) -> Result<bool, Error> {
+        Ok(self.tx.is_tx_empty())
+    }
+}
+
+impl<USART: Instance, PINS> embedded_io::Read for Serial<USART, PINS> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        embedded_io::Read::read(&mut self.rx, buf)
+    }
+}
+
+impl<USART: Instance, PINS> embedded_io::ReadReady for Serial<USART, PINS> {
+    fn read_ready(&mut self) -> Result<bool, Error> {
+        Ok(self.rx.is_rx_not_empty())
+    }
+}
+
+#[test]
+fn lin_protected_ids() {
+    assert_eq!(lin_protected_id(0x3C), 0x3C);
+    assert_eq!(lin_protected_id(0x01), 0xC1);
+    assert_eq!(lin_protected_id(0xC1), 0xC1);
+}
+
+#[test]
+fn irda_smartcard_prescalers() {
+    assert_eq!(irda_low_power_psc(8_000_000), 4);
+    assert_eq!(irda_low_power_psc(36_000_000), 20);
+
+    assert_eq!(smartcard_psc(36_000_000, 4_000_000), 5);
+    assert_eq!(smartcard_psc(72_000_000, 5_000_000), 8);
+    assert_eq!(smartcard_psc(8_000_000, 8_000_000), 1);
+    assert_eq!(smartcard_psc(72_000_000, 100_000), 31);
+}
@@ -0,0 +1,261 @@
+//! Async serial driven by the USART interrupt
+//!
+//! [`on_interrupt`] has to be called from the interrupt handler of the USART instance:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn USART1() {
+//!     serial::asynch::on_interrupt::<pac::USART1>();
+//! }
+//! ```
+
+// `poll_fn` is stable since 1.64, the `async` feature requires Rust 1.75
+#![allow(clippy::incompatible_msrv)]
+
+use super::*;
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+use cortex_m::interrupt::{self, Mutex};
+
+type WakerCell = Mutex<RefCell<Option<Waker>>>;
+
+/// USART instance with receive and transmit task wakers
+pub trait WakerInstance: Instance {
+    #[doc(hidden)]
+    fn rx_waker() -> &'static WakerCell;
+    #[doc(hidden)]
+    fn tx_waker() -> &'static WakerCell;
+}
+
+macro_rules! waker {
+    ($USARTX:ty, $RX_WAKER:ident, $TX_WAKER:ident) => {
+        static $RX_WAKER: WakerCell = Mutex::new(RefCell::new(None));
+        static $TX_WAKER: WakerCell = Mutex::new(RefCell::new(None));
+
+        impl WakerInstance for $USARTX {
+            fn rx_waker() -> &'static WakerCell {
+                &$RX_WAKER
+            }
+
+            fn tx_waker() -> &'static WakerCell {
+                &$TX_WAKER
+            }
+        }
+    };
+}
+
+waker!(USART1, USART1_RX_WAKER, USART1_TX_WAKER);
+waker!(USART2, USART2_RX_WAKER, USART2_TX_WAKER);
+waker!(USART3, USART3_RX_WAKER, USART3_TX_WAKER);
+#[cfg(all(feature = "stm32f103", feature = "high"))]
+waker!(UART4, UART4_RX_WAKER, UART4_TX_WAKER);
+#[cfg(all(feature = "stm32f103", feature = "high"))]
+waker!(UART5, UART5_RX_WAKER, UART5_TX_WAKER);
+
+fn wake(waker: &'static WakerCell) {
+    if let Some(waker) = interrupt::free(|cs| waker.borrow(cs).take()) {
+        waker.wake();
+    }
+}
+
+/// Wakes the tasks waiting for `USART`, to be called from the USART interrupt handler
+pub fn on_interrupt<USART: WakerInstance>() {
+    let usart = unsafe { &*USART::ptr() };
+    let sr = usart.sr.read();
+    let cr1 = usart.cr1.read();
+
+    let rx_event = sr.rxne().bit_is_set()
+        || sr.ore().bit_is_set()
+        || sr.pe().bit_is_set()
+        || sr.fe().bit_is_set()
+        || sr.ne().bit_is_set();
+    if (cr1.rxneie().bit_is_set() && rx_event)
+        || (cr1.idleie().bit_is_set() && sr.idle().bit_is_set())
+    {
+        usart
+            .cr1
+            .modify(|_, w| w.rxneie().clear_bit().idleie().clear_bit());
+        wake(USART::rx_waker());
+    }
+
+    if (cr1.txeie().bit_is_set() && sr.txe().bit_is_set())
+        || (cr1.tcie().bit_is_set() && sr.tc().bit_is_set())
+    {
+        usart
+            .cr1
+            .modify(|_, w| w.txeie().clear_bit().tcie().clear_bit());
+        wake(USART::tx_waker());
+    }
+}
+
+/// Waits for `f` to complete, enabling the interrupt with `listen` while it would block
+async fn wait<T, E>(
+    waker: &'static WakerCell,
+    mut f: impl FnMut() -> nb::Result<T, E>,
+    listen: impl Fn(),
+) -> Result<T, E> {
+    poll_fn(|cx| match f() {
+        Ok(v) => Poll::Ready(Ok(v)),
+        Err(nb::Error::Other(e)) => Poll::Ready(Err(e)),
+        Err(nb::Error::WouldBlock) => {
+            interrupt::free(|cs| waker.borrow(cs).replace(Some(cx.waker().clone())));
+            listen();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Serial transmitter implementing the `embedded-io-async` `Write` trait
+pub struct AsyncTx<USART> {
+    tx: Tx<USART>,
+}
+
+/// Serial receiver implementing the `embedded-io-async` `Read` trait
+pub struct AsyncRx<USART> {
+    rx: Rx<USART>,
+}
+
+impl<USART: WakerInstance> Tx<USART> {
+    /// Converts into an async transmitter
+    pub fn into_async(self) -> AsyncTx<USART> {
+        AsyncTx { tx: self }
+    }
+}
+
+impl<USART: WakerInstance> Rx<USART> {
+    /// Converts into an async receiver
+    pub fn into_async(self) -> AsyncRx<USART> {
+        AsyncRx { rx: self }
+    }
+}
+
+impl<USART: WakerInstance, PINS> Serial<USART, PINS> {
+    /// Splits into an async transmitter and receiver
+    pub fn split_async(self) -> (AsyncTx<USART>, AsyncRx<USART>) {
+        (self.tx.into_async(), self.rx.into_async())
+    }
+}
+
+impl<USART: WakerInstance> AsyncTx<USART> {
+    /// Returns the blocking transmitter
+    pub fn release(self) -> Tx<USART> {
+        self.tx
+    }
+
+    /// Transmits `word` once the transmit register is empty
+    pub async fn write_u8(&mut self, word: u8) {
+        let tx = &mut self.tx;
+        let _ = wait(
+            USART::tx_waker(),
+            || tx.write(word),
+            || unsafe { (*USART::ptr()).cr1.modify(|_, w| w.txeie().set_bit()) },
+        )
+        .await;
+    }
+}
+
+impl<USART: WakerInstance> AsyncRx<USART> {
+    /// Returns the blocking receiver
+    pub fn release(self) -> Rx<USART> {
+        self.rx
+    }
+
+    /// Waits for the next received byte
+    pub async fn read_u8(&mut self) -> Result<u8, Error> {
+        let rx = &mut self.rx;
+        wait(
+            USART::rx_waker(),
+            || rx.read(),
+            || unsafe { (*USART::ptr()).cr1.modify(|_, w| w.rxneie().set_bit()) },
+        )
+        .await
+    }
+
+    /// Receives into `buf` until it is full or the line becomes idle after at least one byte,
+    /// returning the number of bytes received
+    pub async fn read_until_idle(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut count = 0;
+        while count < buf.len() {
+            let rx = &mut self.rx;
+            let received = wait(
+                USART::rx_waker(),
+                || {
+                    if rx.is_rx_not_empty() {
+                        rx.read().map(Some)
+                    } else if rx.is_idle() {
+                        rx.clear_idle_interrupt();
+                        Ok(None)
+                    } else {
+                        rx.read().map(Some)
+                    }
+                },
+                || unsafe {
+                    (*USART::ptr())
+                        .cr1
+                        .modify(|_, w| w.rxneie().set_bit().idleie().set_bit())
+                },
+            )
+            .await?;
+            match received {
+                Some(word) => {
+                    buf[count] = word;
+                    count += 1;
+                }
+                None if count > 0 => break,
+                None => {}
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl<USART> embedded_io::ErrorType for AsyncTx<USART> {
+    type Error = Infallible;
+}
+
+impl<USART: WakerInstance> embedded_io_async::Write for AsyncTx<USART> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.write_u8(buf[0]).await;
+        let mut count = 1;
+        for &word in &buf[1..] {
+            if self.tx.write(word).is_err() {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn flush(&mut self) -> Result<(), Infallible> {
+        let tx = &mut self.tx;
+        wait(
+            USART::tx_waker(),
+            || tx.flush(),
+            || unsafe { (*USART::ptr()).cr1.modify(|_, w| w.tcie().set_bit()) },
+        )
+        .await
+    }
+}
+
+impl<USART> embedded_io::ErrorType for AsyncRx<USART> {
+    type Error = Error;
+}
+
+impl<USART: WakerInstance> embedded_io_async::Read for AsyncRx<USART> {
+    /// Waits for the first byte, then returns the bytes received without blocking
+    ///
+    /// An error received after the first byte is returned by the next read.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.rx.take_pending()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = self.read_u8().await?;
+        Ok(1 + self.rx.read_received(&mut buf[1..]))
+    }
+}
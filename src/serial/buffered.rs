@@ -0,0 +1,294 @@
+//! Interrupt-driven serial with receive and transmit queues
+
+use super::*;
+use crate::Sealed;
+use heapless::spsc::Queue;
+
+/// Word moved through the queues of a [`BufferedSerial`], `u8` or `u16` for 9-bit words
+pub trait Word: Copy + Sealed {
+    #[doc(hidden)]
+    fn from_bits(bits: u16) -> Self;
+    #[doc(hidden)]
+    fn write<USART: Instance>(self, tx: &mut Tx<USART>) -> nb::Result<(), Infallible>;
+}
+
+impl Sealed for u8 {}
+impl Word for u8 {
+    fn from_bits(bits: u16) -> Self {
+        bits as u8
+    }
+    fn write<USART: Instance>(self, tx: &mut Tx<USART>) -> nb::Result<(), Infallible> {
+        tx.write(self)
+    }
+}
+
+impl Sealed for u16 {}
+impl Word for u16 {
+    fn from_bits(bits: u16) -> Self {
+        bits
+    }
+    fn write<USART: Instance>(self, tx: &mut Tx<USART>) -> nb::Result<(), Infallible> {
+        tx.write_u16(self)
+    }
+}
+
+/// Reception error queued by a [`BufferedSerial`] at the position it occurred
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReceiveError<W> {
+    /// Error flagged by the USART
+    pub error: Error,
+    /// Word received along with the error
+    ///
+    /// For `Error::Overrun` this is the last word received before the lost ones, it is `None`
+    /// when the words were lost because the receive queue was full.
+    pub word: Option<W>,
+}
+
+/// What a [`BufferedSerial`] does with a received word when its receive queue is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drops the received word
+    DropNewest,
+    /// Drops the oldest word of the queue to store the received word
+    OverwriteOldest,
+    /// Drops the received words until there is room in the queue, where an `Error::Overrun`
+    /// is then reported
+    Error,
+}
+
+/// Serial interface moving the data between its queues and the USART from the USART
+/// interrupt
+///
+/// The receive queue holds up to `RX - 1` words and the transmit queue up to `TX - 1` words.
+/// The words are bytes, or 9-bit words stored as `u16` for a serial configured with
+/// `WordLength::Bits9` and no parity.
+pub struct BufferedSerial<USART, PINS, const RX: usize, const TX: usize, W = u8> {
+    serial: Serial<USART, PINS>,
+    rx: Queue<Result<W, ReceiveError<W>>, RX>,
+    tx: Queue<W, TX>,
+    overflow: bool,
+    policy: OverflowPolicy,
+    lost: u32,
+}
+
+impl<USART: Instance, PINS> Serial<USART, PINS> {
+    /// Converts into an interrupt-driven serial interface, `BufferedSerial::handle_interrupt`
+    /// has to be called from the USART interrupt handler
+    pub fn into_buffered<const RX: usize, const TX: usize>(
+        self,
+    ) -> BufferedSerial<USART, PINS, RX, TX> {
+        self.into_buffered_words()
+    }
+
+    /// Converts into an interrupt-driven serial interface queuing 9-bit words as `u16`
+    pub fn into_buffered_u16<const RX: usize, const TX: usize>(
+        self,
+    ) -> BufferedSerial<USART, PINS, RX, TX, u16> {
+        self.into_buffered_words()
+    }
+
+    fn into_buffered_words<const RX: usize, const TX: usize, W>(
+        mut self,
+    ) -> BufferedSerial<USART, PINS, RX, TX, W> {
+        self.listen(Event::Rxne);
+        BufferedSerial {
+            serial: self,
+            rx: Queue::new(),
+            tx: Queue::new(),
+            overflow: false,
+            policy: OverflowPolicy::Error,
+            lost: 0,
+        }
+    }
+}
+
+impl<USART: Instance, PINS, const RX: usize, const TX: usize, W: Word>
+    BufferedSerial<USART, PINS, RX, TX, W>
+{
+    /// Takes the next received word from the receive queue
+    ///
+    /// A reception error, including `Error::Overrun` when the receive queue was full with
+    /// `OverflowPolicy::Error`, is returned once, at the position in the received data where
+    /// it occurred. The word received with the error is dropped, see `read_with_error`.
+    pub fn read(&mut self) -> nb::Result<W, Error> {
+        self.read_with_error()
+            .map_err(|e| e.map(|e: ReceiveError<W>| e.error))
+    }
+
+    /// Takes the next received word from the receive queue, along with the reception error
+    /// flagged for this word
+    pub fn read_with_error(&mut self) -> nb::Result<W, ReceiveError<W>> {
+        match self.rx.dequeue() {
+            Some(entry) => entry.map_err(nb::Error::Other),
+            None if self.overflow => {
+                self.overflow = false;
+                Err(nb::Error::Other(ReceiveError {
+                    error: Error::Overrun,
+                    word: None,
+                }))
+            }
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Queues `word` for transmission
+    ///
+    /// Returns `WouldBlock` if the transmit queue is full.
+    pub fn write(&mut self, word: W) -> nb::Result<(), Infallible> {
+        self.tx.enqueue(word).map_err(|_| nb::Error::WouldBlock)?;
+        self.serial.listen(Event::Txe);
+        Ok(())
+    }
+
+    /// Waits for all the queued words to be transmitted
+    pub fn flush(&mut self) -> nb::Result<(), Infallible> {
+        if self.tx.is_empty() {
+            self.serial.tx.flush()
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Reconfigures the USART once all the queued words have been transmitted
+    ///
+    /// Returns `WouldBlock` while the transmission is in progress, the queued received words
+    /// are kept.
+    pub fn reconfigure(
+        &mut self,
+        config: impl Into<Config>,
+        clocks: &Clocks,
+    ) -> nb::Result<(), Infallible> {
+        if !self.tx.is_empty() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.serial.reconfigure(config, clocks)
+    }
+
+    /// Selects what to do when the receive queue is full, `OverflowPolicy::Error` by default
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.policy = policy;
+    }
+
+    /// Number of received words lost because the receive queue was full, wrapping around
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+
+    /// Resets the count of lost words
+    pub fn clear_lost(&mut self) {
+        self.lost = 0;
+    }
+
+    /// Number of received words waiting in the receive queue
+    pub fn available(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Moves the data between the queues and the USART
+    pub fn handle_interrupt(&mut self) {
+        let usart = unsafe { &*USART::ptr() };
+        let sr = usart.sr.read();
+
+        if sr.rxne().bit_is_set()
+            || sr.ore().bit_is_set()
+            || sr.pe().bit_is_set()
+            || sr.fe().bit_is_set()
+            || sr.ne().bit_is_set()
+        {
+            // Reading DR after SR clears the error flags
+            let bits = usart.dr.read().dr().bits();
+            let word = if sr.rxne().bit_is_set() {
+                Some(W::from_bits(bits))
+            } else {
+                None
+            };
+            let error = if sr.pe().bit_is_set() {
+                Some(Error::Parity)
+            } else if sr.fe().bit_is_set() {
+                Some(if bits == 0 {
+                    Error::Break
+                } else {
+                    Error::Framing
+                })
+            } else if sr.ne().bit_is_set() {
+                Some(Error::Noise)
+            } else if sr.ore().bit_is_set() {
+                Some(Error::Overrun)
+            } else {
+                None
+            };
+            match (word, error) {
+                (word, Some(error)) => self.push(Err(ReceiveError { error, word })),
+                (Some(word), None) => self.push(Ok(word)),
+                (None, None) => {}
+            }
+        }
+
+        if sr.txe().bit_is_set() && usart.cr1.read().txeie().bit_is_set() {
+            match self.tx.dequeue() {
+                Some(word) => {
+                    let _ = word.write(&mut self.serial.tx);
+                }
+                None => self.serial.unlisten(Event::Txe),
+            }
+        }
+    }
+
+    fn push(&mut self, entry: Result<W, ReceiveError<W>>) {
+        if self.overflow {
+            let overrun = ReceiveError {
+                error: Error::Overrun,
+                word: None,
+            };
+            if self.rx.enqueue(Err(overrun)).is_ok() {
+                self.overflow = false;
+            }
+        }
+        if self.overflow {
+            self.lost = self.lost.wrapping_add(1);
+            return;
+        }
+        if let Err(entry) = self.rx.enqueue(entry) {
+            self.lost = self.lost.wrapping_add(1);
+            match self.policy {
+                OverflowPolicy::DropNewest => {}
+                OverflowPolicy::OverwriteOldest => {
+                    let _ = self.rx.dequeue();
+                    let _ = self.rx.enqueue(entry);
+                }
+                OverflowPolicy::Error => self.overflow = true,
+            }
+        }
+    }
+
+    /// Stops the interrupts and returns the serial interface, the queued words are dropped
+    pub fn release(mut self) -> Serial<USART, PINS> {
+        self.serial.unlisten(Event::Txe);
+        self.serial.unlisten(Event::Rxne);
+        self.serial
+    }
+}
+
+impl<USART: Instance, PINS, const RX: usize, const TX: usize, W: Word> embedded_hal::serial::Read<W>
+    for BufferedSerial<USART, PINS, RX, TX, W>
+{
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<W, Error> {
+        self.read()
+    }
+}
+
+impl<USART: Instance, PINS, const RX: usize, const TX: usize, W: Word>
+    embedded_hal::serial::Write<W> for BufferedSerial<USART, PINS, RX, TX, W>
+{
+    type Error = Infallible;
+
+    fn write(&mut self, word: W) -> nb::Result<(), Self::Error> {
+        self.write(word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.flush()
+    }
+}
@@ -0,0 +1,150 @@
+//! Modbus RTU framing
+//!
+//! RTU frames are delimited by a silent interval of at least 3.5 characters. [`RtuReceiver`]
+//! restarts a timer with this interval on each received byte, the frame is complete when the
+//! timer expires.
+//!
+//! ```ignore
+//! let timer = dp.TIM2.counter_us(&clocks);
+//! let mut rtu: RtuReceiver<_, _, 256> = rx.into_rtu(timer, 19_200.bps()).unwrap();
+//!
+//! #[interrupt]
+//! fn USART1() {
+//!     // rtu is shared with the interrupt handlers
+//!     rtu.handle_usart_interrupt();
+//! }
+//!
+//! #[interrupt]
+//! fn TIM2() {
+//!     rtu.handle_timer_interrupt();
+//! }
+//!
+//! if let Some(frame) = rtu.frame() {
+//!     // frame holds the address, the PDU and the CRC
+//!     rtu.clear_frame();
+//! }
+//! ```
+
+use super::*;
+use crate::time::MicroSeconds;
+use crate::timer::{self, CounterUs};
+use fugit::ExtU32;
+use heapless::Vec;
+
+/// Silent interval of 3.5 characters delimiting RTU frames at `baudrate`
+///
+/// The characters are 11 bits long, the interval is fixed to 1750 µs above 19200 bps as
+/// recommended by the Modbus serial line specification.
+pub fn rtu_silent_interval(baudrate: Bps) -> MicroSeconds {
+    if baudrate.0 > 19_200 {
+        1750.micros()
+    } else {
+        (38_500_000 / baudrate.0 + 1).micros()
+    }
+}
+
+/// Receiver delivering complete Modbus RTU frames of up to `N` bytes
+///
+/// A frame with a reception error, or longer than `N` bytes, is dropped. While a complete frame
+/// has not been cleared, the next frame is dropped.
+pub struct RtuReceiver<USART, TIM, const N: usize> {
+    rx: Rx<USART>,
+    timer: CounterUs<TIM>,
+    silence: MicroSeconds,
+    buffer: Vec<u8, N>,
+    complete: bool,
+    discard: bool,
+}
+
+impl<USART: Instance> Rx<USART> {
+    /// Converts into a Modbus RTU receiver detecting the silent interval with `timer`,
+    /// `baudrate` being the baud rate of the USART
+    ///
+    /// Returns `timer::Error::WrongAutoReload` if the silent interval is out of the range of
+    /// the timer, 65536 µs for a 16-bit timer, below about 600 bps.
+    pub fn into_rtu<TIM: timer::Instance, const N: usize>(
+        mut self,
+        mut timer: CounterUs<TIM>,
+        baudrate: Bps,
+    ) -> Result<RtuReceiver<USART, TIM, N>, timer::Error> {
+        let silence = rtu_silent_interval(baudrate);
+        timer.start(silence)?;
+        timer.cancel()?;
+        timer.listen(timer::Event::Update);
+        self.listen();
+        Ok(RtuReceiver {
+            rx: self,
+            timer,
+            silence,
+            buffer: Vec::new(),
+            complete: false,
+            discard: false,
+        })
+    }
+}
+
+impl<USART: Instance, TIM: timer::Instance, const N: usize> RtuReceiver<USART, TIM, N> {
+    /// Stores the received byte and restarts the silent interval, to be called from the USART
+    /// interrupt handler
+    pub fn handle_usart_interrupt(&mut self) {
+        let result = match self.rx.read() {
+            Err(nb::Error::WouldBlock) => return,
+            Ok(byte) => Ok(byte),
+            Err(nb::Error::Other(e)) => Err(e),
+        };
+        if self.complete {
+            self.discard = true;
+        }
+        match result {
+            Ok(byte) if !self.discard => self.discard = self.buffer.push(byte).is_err(),
+            _ => self.discard = true,
+        }
+        // The interval was checked to be in the range of the timer
+        self.timer.start(self.silence).ok();
+    }
+
+    /// Completes the frame at the end of the silent interval, to be called from the timer
+    /// interrupt handler
+    pub fn handle_timer_interrupt(&mut self) {
+        self.timer.clear_interrupt(timer::Event::Update);
+        self.timer.cancel().ok();
+        if self.discard {
+            self.discard = false;
+            if !self.complete {
+                self.buffer.clear();
+            }
+        } else if !self.buffer.is_empty() {
+            self.complete = true;
+        }
+    }
+
+    /// Returns the complete frame received, if any
+    pub fn frame(&self) -> Option<&[u8]> {
+        if self.complete {
+            Some(&self.buffer)
+        } else {
+            None
+        }
+    }
+
+    /// Clears the complete frame to receive the next one
+    pub fn clear_frame(&mut self) {
+        self.buffer.clear();
+        self.complete = false;
+    }
+
+    /// Stops the interrupts and returns the receiver and the timer
+    pub fn release(mut self) -> (Rx<USART>, CounterUs<TIM>) {
+        self.rx.unlisten();
+        self.timer.unlisten(timer::Event::Update);
+        self.timer.cancel().ok();
+        (self.rx, self.timer)
+    }
+}
+
+#[test]
+fn rtu_silent_intervals() {
+    assert_eq!(rtu_silent_interval(9600.bps()).ticks(), 4011);
+    assert_eq!(rtu_silent_interval(19_200.bps()).ticks(), 2006);
+    assert_eq!(rtu_silent_interval(115_200.bps()).ticks(), 1750);
+}
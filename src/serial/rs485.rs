@@ -0,0 +1,133 @@
+//! RS-485 transceiver control
+//!
+//! The USART has no driver enable output, so [`Rs485`] drives the DE (and /RE) pin of the
+//! transceiver from a GPIO: the pin is set before the first word is written and reset once
+//! the transmission is complete, after the last stop bit.
+//!
+//! ```ignore
+//! let de = gpioa.pa8.into_push_pull_output(&mut gpioa.crh);
+//! let mut rs485 = serial.into_rs485(de);
+//! rs485.bwrite_all(b"request").unwrap();
+//! let reply = block!(rs485.read()).unwrap();
+//! ```
+//!
+//! With the non-blocking `write`, the transmission complete interrupt is enabled and
+//! `Rs485::handle_interrupt` has to be called from the USART interrupt handler to release the
+//! bus.
+
+use super::*;
+use crate::hal::digital::v2::OutputPin;
+
+/// Serial interface driving the DE pin of an RS-485 transceiver
+pub struct Rs485<USART, PINS, DE> {
+    serial: Serial<USART, PINS>,
+    de: DE,
+    driving: bool,
+}
+
+impl<USART: Instance, PINS> Serial<USART, PINS> {
+    /// Converts into an RS-485 interface driving the transceiver with `de`, which is reset
+    pub fn into_rs485<DE>(self, mut de: DE) -> Rs485<USART, PINS, DE>
+    where
+        DE: OutputPin<Error = Infallible>,
+    {
+        de.set_low().ok();
+        Rs485 {
+            serial: self,
+            de,
+            driving: false,
+        }
+    }
+}
+
+impl<USART: Instance, PINS, DE> Rs485<USART, PINS, DE>
+where
+    DE: OutputPin<Error = Infallible>,
+{
+    /// Enables the transceiver driver and writes `word`
+    ///
+    /// The transmission complete interrupt is enabled, `handle_interrupt` disables the driver
+    /// once the last word has left.
+    pub fn write(&mut self, word: u8) -> nb::Result<(), Infallible> {
+        self.drive_bus();
+        self.serial.tx.write(word)?;
+        self.serial.usart().cr1.modify(|_, w| w.tcie().set_bit());
+        Ok(())
+    }
+
+    /// Waits for the transmission to complete and disables the transceiver driver
+    pub fn flush(&mut self) -> nb::Result<(), Infallible> {
+        self.serial.tx.flush()?;
+        self.release_bus();
+        Ok(())
+    }
+
+    /// Writes `buffer` and waits for the transmission to complete
+    pub fn bwrite_all(&mut self, buffer: &[u8]) -> Result<(), Infallible> {
+        self.drive_bus();
+        self.serial.tx.bwrite_all(buffer)?;
+        nb::block!(self.flush())
+    }
+
+    /// Reads a received word
+    pub fn read(&mut self) -> nb::Result<u8, Error> {
+        self.serial.rx.read()
+    }
+
+    /// Returns true while the transceiver driver is enabled
+    pub fn is_transmitting(&self) -> bool {
+        self.driving
+    }
+
+    /// Disables the transceiver driver once the transmission is complete, to be called from
+    /// the USART interrupt handler
+    pub fn handle_interrupt(&mut self) {
+        let usart = self.serial.usart();
+        if usart.cr1.read().tcie().bit_is_set() && usart.sr.read().tc().bit_is_set() {
+            self.release_bus();
+        }
+    }
+
+    fn drive_bus(&mut self) {
+        self.de.set_high().ok();
+        self.driving = true;
+    }
+
+    fn release_bus(&mut self) {
+        self.serial.usart().cr1.modify(|_, w| w.tcie().clear_bit());
+        self.de.set_low().ok();
+        self.driving = false;
+    }
+
+    /// Returns the serial interface and the DE pin
+    pub fn release(mut self) -> (Serial<USART, PINS>, DE) {
+        self.release_bus();
+        (self.serial, self.de)
+    }
+}
+
+impl<USART: Instance, PINS, DE> embedded_hal::serial::Read<u8> for Rs485<USART, PINS, DE>
+where
+    DE: OutputPin<Error = Infallible>,
+{
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        self.read()
+    }
+}
+
+impl<USART: Instance, PINS, DE> embedded_hal::serial::Write<u8> for Rs485<USART, PINS, DE>
+where
+    DE: OutputPin<Error = Infallible>,
+{
+    type Error = Infallible;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.write(word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.flush()
+    }
+}
@@ -0,0 +1,123 @@
+//! Receiver timeouts measured with a timer
+//!
+//! The USART of the STM32F1 has no receiver timeout, [`RxWithTimeout`] pairs the receiver
+//! with a timer counting the timeout.
+//!
+//! ```ignore
+//! let timer = dp.TIM2.counter_ms(&clocks);
+//! let mut rx = serial.rx.with_timeout(timer);
+//! let mut buffer = [0; 16];
+//! match rx.read_until_idle_timeout(&mut buffer, 100.millis()) {
+//!     Ok(len) => process(&buffer[..len]),
+//!     Err(serial::Error::Timeout) => {}
+//!     Err(e) => handle_error(e),
+//! }
+//! ```
+
+use super::*;
+use crate::timer::{self, Counter};
+use fugit::TimerDurationU32;
+
+/// Receiver with blocking reads timing out after a duration counted by a timer
+pub struct RxWithTimeout<USART, TIM, const FREQ: u32> {
+    rx: Rx<USART>,
+    timer: Counter<TIM, FREQ>,
+}
+
+impl<USART: Instance> Rx<USART> {
+    /// Pairs the receiver with `timer` to read with timeouts
+    pub fn with_timeout<TIM: timer::Instance, const FREQ: u32>(
+        self,
+        timer: Counter<TIM, FREQ>,
+    ) -> RxWithTimeout<USART, TIM, FREQ> {
+        RxWithTimeout { rx: self, timer }
+    }
+}
+
+impl<USART: Instance, TIM: timer::Instance, const FREQ: u32> RxWithTimeout<USART, TIM, FREQ> {
+    /// Reads a word, returns `Error::Timeout` if none is received within `timeout`
+    ///
+    /// Returns `Error::Timer` if `timeout` is out of the range of the timer.
+    pub fn read_timeout(&mut self, timeout: TimerDurationU32<FREQ>) -> Result<u8, Error> {
+        let mut word = [0];
+        self.read_exact_timeout(&mut word, timeout)?;
+        Ok(word[0])
+    }
+
+    /// Fills `buffer`, returns `Error::Timeout` if it is not filled within `timeout`
+    ///
+    /// The words received before the timeout are kept in `buffer`. Returns `Error::Timer` if
+    /// `timeout` is out of the range of the timer.
+    pub fn read_exact_timeout(
+        &mut self,
+        buffer: &mut [u8],
+        timeout: TimerDurationU32<FREQ>,
+    ) -> Result<(), Error> {
+        self.start(timeout)?;
+        let result = buffer
+            .iter_mut()
+            .try_for_each(|word| self.read(false).map(|w| *word = w.unwrap_or(0)));
+        self.timer.cancel().ok();
+        result
+    }
+
+    /// Reads into `buffer` until the line goes idle after a word or `buffer` is full, returns
+    /// the number of words read
+    ///
+    /// Returns `Error::Timeout` if no word is received within `timeout`, the timeout does not
+    /// apply once a word is received. Returns `Error::Timer` if `timeout` is out of the range
+    /// of the timer.
+    pub fn read_until_idle_timeout(
+        &mut self,
+        buffer: &mut [u8],
+        timeout: TimerDurationU32<FREQ>,
+    ) -> Result<usize, Error> {
+        self.start(timeout)?;
+        let mut count = 0;
+        let result = loop {
+            if count == buffer.len() {
+                break Ok(count);
+            }
+            match self.read(count > 0) {
+                Ok(Some(word)) => {
+                    if count == 0 {
+                        self.timer.cancel().ok();
+                    }
+                    buffer[count] = word;
+                    count += 1;
+                }
+                Ok(None) => break Ok(count),
+                Err(e) => break Err(e),
+            }
+        };
+        self.timer.cancel().ok();
+        result
+    }
+
+    fn start(&mut self, timeout: TimerDurationU32<FREQ>) -> Result<(), Error> {
+        self.timer.start(timeout).map_err(Error::Timer)
+    }
+
+    // Waits for a word, or for the idle line if `idle` is true, until the timeout
+    fn read(&mut self, idle: bool) -> Result<Option<u8>, Error> {
+        loop {
+            match self.rx.read() {
+                Ok(word) => return Ok(Some(word)),
+                Err(nb::Error::Other(e)) => return Err(e),
+                Err(nb::Error::WouldBlock) => {}
+            }
+            // Reading a word clears the idle flag
+            if idle && self.rx.is_idle() {
+                return Ok(None);
+            }
+            if self.timer.wait().is_ok() {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// Returns the receiver and the timer
+    pub fn release(self) -> (Rx<USART>, Counter<TIM, FREQ>) {
+        (self.rx, self.timer)
+    }
+}
@@ -0,0 +1,141 @@
+//! Buffered formatted output transmitted with DMA
+//!
+//! [`SerialWriter`] formats into one buffer while the other one is transmitted with DMA, the
+//! formatting only blocks when both buffers are full.
+//!
+//! ```ignore
+//! static mut BUFFERS: [[u8; 64]; 2] = [[0; 64]; 2];
+//!
+//! let tx = serial.tx.with_dma(channels.4);
+//! let mut writer = SerialWriter::new(tx, unsafe { &mut BUFFERS });
+//! writeln!(writer, "value: {}", value).unwrap();
+//! // Transmits the formatted output, e.g. from the main loop or the DMA interrupt
+//! writer.poll();
+//! ```
+
+use super::*;
+use crate::dma::TransferPayload;
+
+/// Buffer of a [`SerialWriter`], transmitted up to the formatted length
+pub struct WriterBuffer<const N: usize> {
+    buffer: &'static mut [u8; N],
+    len: usize,
+}
+
+unsafe impl<const N: usize> ReadBuffer for WriterBuffer<N> {
+    type Word = u8;
+
+    unsafe fn read_buffer(&self) -> (*const u8, usize) {
+        (self.buffer.as_ptr(), self.len)
+    }
+}
+
+/// DMA transmitter of a [`SerialWriter`]
+pub trait WriterDma<const N: usize>: TransferPayload + Sized {
+    #[doc(hidden)]
+    fn start(self, buffer: WriterBuffer<N>) -> Transfer<R, WriterBuffer<N>, Self>;
+    #[doc(hidden)]
+    fn is_done(transfer: &Transfer<R, WriterBuffer<N>, Self>) -> bool;
+    #[doc(hidden)]
+    fn wait(transfer: Transfer<R, WriterBuffer<N>, Self>) -> (WriterBuffer<N>, Self);
+}
+
+enum State<TX: WriterDma<N>, const N: usize> {
+    Idle(TX, WriterBuffer<N>),
+    Busy(Transfer<R, WriterBuffer<N>, TX>),
+}
+
+/// Writer implementing `core::fmt::Write`, and `ufmt::uWrite` with the `ufmt-write` feature,
+/// transmitting with DMA
+pub struct SerialWriter<TX: WriterDma<N>, const N: usize> {
+    state: Option<State<TX, N>>,
+    buffer: WriterBuffer<N>,
+}
+
+impl<TX: WriterDma<N>, const N: usize> SerialWriter<TX, N> {
+    /// Creates a writer transmitting with `tx`, using the two `buffers` alternately
+    pub fn new(tx: TX, buffers: &'static mut [[u8; N]; 2]) -> Self {
+        let (first, second) = buffers.split_at_mut(1);
+        Self {
+            state: Some(State::Idle(
+                tx,
+                WriterBuffer {
+                    buffer: &mut second[0],
+                    len: 0,
+                },
+            )),
+            buffer: WriterBuffer {
+                buffer: &mut first[0],
+                len: 0,
+            },
+        }
+    }
+
+    /// Starts the transmission of the formatted output if the previous one is complete
+    ///
+    /// Returns true while output remains to be transmitted.
+    pub fn poll(&mut self) -> bool {
+        let state = match self.state.take() {
+            Some(State::Busy(transfer)) if TX::is_done(&transfer) => {
+                let (buffer, tx) = TX::wait(transfer);
+                State::Idle(tx, buffer)
+            }
+            Some(state) => state,
+            None => unreachable!(),
+        };
+        let state = match state {
+            State::Idle(tx, mut buffer) if self.buffer.len > 0 => {
+                buffer.len = 0;
+                core::mem::swap(&mut buffer, &mut self.buffer);
+                State::Busy(tx.start(buffer))
+            }
+            state => state,
+        };
+        let busy = matches!(state, State::Busy(_));
+        self.state = Some(state);
+        busy
+    }
+
+    /// Queues `bytes` for transmission, blocking while both buffers are full
+    pub fn write_bytes(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let start = self.buffer.len;
+            let count = bytes.len().min(N - start);
+            self.buffer.buffer[start..start + count].copy_from_slice(&bytes[..count]);
+            self.buffer.len += count;
+            bytes = &bytes[count..];
+            self.poll();
+        }
+    }
+
+    /// Waits for all the formatted output to be transmitted
+    pub fn flush(&mut self) {
+        while self.poll() {}
+    }
+
+    /// Transmits the remaining output and returns the DMA transmitter
+    pub fn release(mut self) -> TX {
+        self.flush();
+        match self.state.take() {
+            Some(State::Idle(tx, _)) => tx,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<TX: WriterDma<N>, const N: usize> core::fmt::Write for SerialWriter<TX, N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ufmt-write")]
+impl<TX: WriterDma<N>, const N: usize> ufmt_write::uWrite for SerialWriter<TX, N> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
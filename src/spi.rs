@@ -8,10 +8,29 @@
 
   You can also use `NoSck`, `NoMiso` or `NoMosi` if you don't want to use the pins
 
+  The instances are created with 8-bit frames, `frame_size_16bit` switches to 16-bit frames
+  (`Spi<_, _, _, u16>`) used through the `FullDuplex<u16>`, `Transfer<u16>` and `Write<u16>` traits.
+
   - `SPI1` can use `(PA5, PA6, PA7)` or `(PB3, PB4, PB5)`.
   - `SPI2` can use `(PB13, PB14, PB15)`
   - `SPI3` can use `(PB3, PB4, PB5)` or only in connectivity line devices `(PC10, PC11, PC12)`
 
+  In slave mode, constructed with the `Spi::spiX_slave` functions, the pins should be configured
+  as `(Input<...>, Alternate<...>, Input<...>)`. The slave is selected by software by default,
+  use `set_slave_select` to use the NSS pin instead. Received data is read with the blocking
+  or non-blocking embedded-hal traits, or from the `Rxne` interrupt. For continuous capture
+  at high clock rates, use `with_rx_dma` and `circ_read` to stream into a double buffer.
+
+  ## Error recovery
+
+  Errors are reported without touching the status flags, so the peripheral has to be brought
+  back to a known state before continuing:
+
+  - `Error::Overrun`: the word received last is lost, call `clear_overrun` and restart the
+    transfer.
+  - `Error::ModeFault`: another master pulled NSS low, which disabled the interface and
+    switched it to slave mode. Call `recover_mode_fault` once the bus is free again.
+  - `Error::Crc`: the flag is cleared when the error is reported, the transfer can be retried.
 
   ## Initialisation example
 
@@ -41,9 +60,11 @@ use crate::pac::{self, RCC};
 
 use crate::afio::MAPR;
 use crate::dma::dma1;
-#[cfg(feature = "connectivity")]
+#[cfg(any(feature = "high", feature = "connectivity"))]
 use crate::dma::dma2;
-use crate::dma::{Receive, RxDma, RxTxDma, Transfer, TransferPayload, Transmit, TxDma, R, W};
+use crate::dma::{
+    CircBuffer, Receive, RxDma, RxTxDma, Transfer, TransferPayload, Transmit, TxDma, R, W,
+};
 use crate::gpio::{self, Alternate, Input};
 use crate::rcc::{BusClock, Clocks, Enable, Reset};
 use crate::time::Hertz;
@@ -51,6 +72,15 @@ use crate::time::Hertz;
 use core::sync::atomic::{self, Ordering};
 use embedded_dma::{ReadBuffer, WriteBuffer};
 
+pub mod buffered;
+pub use buffered::BufferedSpi;
+pub mod device;
+pub use device::{DeviceError, NoDelay, SpiDevice};
+pub mod shared;
+pub use shared::{SharedSpi, SharedSpiDevice};
+#[cfg(feature = "async")]
+pub mod asynch;
+
 /// Interrupt event
 pub enum Event {
     /// New data has been received
@@ -73,6 +103,16 @@ pub enum Error {
     Crc,
 }
 
+impl embedded_hal_one::spi::Error for Error {
+    fn kind(&self) -> embedded_hal_one::spi::ErrorKind {
+        match self {
+            Self::Overrun => embedded_hal_one::spi::ErrorKind::Overrun,
+            Self::ModeFault => embedded_hal_one::spi::ErrorKind::ModeFault,
+            Self::Crc => embedded_hal_one::spi::ErrorKind::Other,
+        }
+    }
+}
+
 use core::marker::PhantomData;
 
 /// Spi in Master mode (type state)
@@ -80,6 +120,16 @@ pub struct Master;
 /// Spi in Slave mode (type state)
 pub struct Slave;
 
+/// Slave select management in slave mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlaveSelect {
+    /// The slave is selected while the NSS pin, configured as an input, is low
+    Hardware,
+    /// The slave is selected by software with [`Spi::set_selected`], the NSS pin is free
+    /// for other uses (default)
+    Software,
+}
+
 mod sealed {
     pub trait Remap {
         type Periph;
@@ -91,9 +141,10 @@ mod sealed {
     pub trait Ssck<REMAP> {}
     pub trait So<REMAP> {}
     pub trait Si<REMAP> {}
+    pub trait Nss<REMAP> {}
 }
 pub use sealed::Remap;
-use sealed::{Miso, Mosi, Sck, Si, So, Ssck};
+use sealed::{Miso, Mosi, Nss, Sck, Si, So, Ssck};
 
 pub trait Pins<REMAP, OPERATION = Master> {}
 
@@ -121,6 +172,9 @@ pub struct Spi<SPI, REMAP, PINS, FRAMESIZE, OPERATION = Master> {
     _operation: PhantomData<OPERATION>,
 }
 
+/// Spi in slave mode
+pub type SpiSlave<SPI, REMAP, PINS, FRAMESIZE = u8> = Spi<SPI, REMAP, PINS, FRAMESIZE, Slave>;
+
 /// The bit format to send the data in
 #[derive(Debug, Clone, Copy)]
 pub enum SpiBitFormat {
@@ -130,6 +184,45 @@ pub enum SpiBitFormat {
     MsbFirst,
 }
 
+/// Bus settings applied by [`Spi::reconfigure`]
+#[derive(Clone, Copy)]
+pub struct Config {
+    pub mode: Mode,
+    pub frequency: Hertz,
+    pub bit_format: SpiBitFormat,
+}
+
+impl Config {
+    /// Settings for `mode` and `frequency`, MSB first
+    pub fn new(mode: Mode, frequency: Hertz) -> Self {
+        Config {
+            mode,
+            frequency,
+            bit_format: SpiBitFormat::MsbFirst,
+        }
+    }
+
+    pub fn bit_format(mut self, bit_format: SpiBitFormat) -> Self {
+        self.bit_format = bit_format;
+        self
+    }
+}
+
+/// Baud rate prescaler for a bus clock of `pclk`
+fn baud_rate_divider(pclk: Hertz, freq: Hertz) -> u8 {
+    match pclk / freq {
+        0 => unreachable!(),
+        1..=2 => 0b000,
+        3..=5 => 0b001,
+        6..=11 => 0b010,
+        12..=23 => 0b011,
+        24..=47 => 0b100,
+        48..=95 => 0b101,
+        96..=191 => 0b110,
+        _ => 0b111,
+    }
+}
+
 /// A filler type for when the SCK pin is unnecessary
 pub struct NoSck;
 /// A filler type for when the Miso pin is unnecessary
@@ -144,7 +237,7 @@ impl<REMAP> So<REMAP> for NoMiso {}
 impl<REMAP> Si<REMAP> for NoMosi {}
 
 macro_rules! remap {
-    ($name:ident, $SPIX:ty, $state:literal, $SCK:ident, $MISO:ident, $MOSI:ident) => {
+    ($name:ident, $SPIX:ty, $state:literal, $SCK:ident, $MISO:ident, $MOSI:ident, $NSS:ident) => {
         pub struct $name;
         impl Remap for $name {
             type Periph = $SPIX;
@@ -154,6 +247,7 @@ macro_rules! remap {
         impl<MODE> Sck<$name> for gpio::$SCK<Alternate<MODE>> {}
         impl<MODE> Miso<$name> for gpio::$MISO<Input<MODE>> {}
         impl<MODE> Mosi<$name> for gpio::$MOSI<Alternate<MODE>> {}
+        impl<MODE> Nss<$name> for gpio::$NSS<Alternate<MODE>> {}
         // Slave mode pins
         impl<MODE> Ssck<$name> for gpio::$SCK<Input<MODE>> {}
         impl<MODE> So<$name> for gpio::$MISO<Alternate<MODE>> {}
@@ -161,13 +255,13 @@ macro_rules! remap {
     };
 }
 
-remap!(Spi1NoRemap, pac::SPI1, false, PA5, PA6, PA7);
-remap!(Spi1Remap, pac::SPI1, true, PB3, PB4, PB5);
-remap!(Spi2NoRemap, pac::SPI2, false, PB13, PB14, PB15);
+remap!(Spi1NoRemap, pac::SPI1, false, PA5, PA6, PA7, PA4);
+remap!(Spi1Remap, pac::SPI1, true, PB3, PB4, PB5, PA15);
+remap!(Spi2NoRemap, pac::SPI2, false, PB13, PB14, PB15, PB12);
 #[cfg(any(feature = "high", feature = "connectivity"))]
-remap!(Spi3NoRemap, pac::SPI3, false, PB3, PB4, PB5);
+remap!(Spi3NoRemap, pac::SPI3, false, PB3, PB4, PB5, PA15);
 #[cfg(feature = "connectivity")]
-remap!(Spi3Remap, pac::SPI3, true, PC10, PC11, PC12);
+remap!(Spi3Remap, pac::SPI3, true, PC10, PC11, PC12, PA4);
 
 pub trait Instance:
     crate::Sealed + Deref<Target = crate::pac::spi1::RegisterBlock> + Enable + Reset + BusClock
@@ -312,7 +406,7 @@ impl<REMAP, PINS> Spi<pac::SPI3, REMAP, PINS, u8, Slave> {
     pub fn spi3_slave(spi: pac::SPI3, pins: PINS, mode: Mode) -> Self
     where
         REMAP: Remap<Periph = pac::SPI3>,
-        PINS: Pins<REMAP>,
+        PINS: Pins<REMAP, Slave>,
     {
         Spi::<pac::SPI3, _, _, u8, Slave>::configure(spi, pins, mode)
     }
@@ -328,7 +422,7 @@ impl<REMAP, PINS> Spi<pac::SPI3, REMAP, PINS, u8, Slave> {
     pub fn spi3_slave(spi: pac::SPI3, pins: PINS, mapr: &mut MAPR, mode: Mode) -> Self
     where
         REMAP: Remap<Periph = pac::SPI3>,
-        PINS: Pins<REMAP>,
+        PINS: Pins<REMAP, Slave>,
     {
         mapr.modify_mapr(|_, w| w.spi3_remap().bit(REMAP::REMAP));
         Spi::<pac::SPI3, _, _, u8, Slave>::configure(spi, pins, mode)
@@ -425,8 +519,8 @@ where
     }
 
     /// Starts listening to the SPI by enabling the _Received data
-    /// ready to be read (RXNE)_ interrupt and _Transmit data
-    /// register empty (TXE)_ interrupt
+    /// ready to be read (RXNE)_, _Transmit data register empty (TXE)_
+    /// or _Error (ERR)_ interrupt
     pub fn listen(&mut self, event: Event) {
         match event {
             Event::Rxne => self.spi.cr2.modify(|_, w| w.rxneie().set_bit()),
@@ -436,8 +530,8 @@ where
     }
 
     /// Stops listening to the SPI by disabling the _Received data
-    /// ready to be read (RXNE)_ interrupt and _Transmit data
-    /// register empty (TXE)_ interrupt
+    /// ready to be read (RXNE)_, _Transmit data register empty (TXE)_
+    /// or _Error (ERR)_ interrupt
     pub fn unlisten(&mut self, event: Event) {
         match event {
             Event::Rxne => self.spi.cr2.modify(|_, w| w.rxneie().clear_bit()),
@@ -464,6 +558,178 @@ where
     pub fn is_busy(&self) -> bool {
         self.spi.sr.read().bsy().bit_is_set()
     }
+
+    /// Returns true if a mode fault occurred, see [`Spi::recover_mode_fault`]
+    pub fn is_mode_fault(&self) -> bool {
+        self.spi.sr.read().modf().bit_is_set()
+    }
+
+    /// Returns true if the received CRC did not match the computed one
+    pub fn is_crc_error(&self) -> bool {
+        self.spi.sr.read().crcerr().bit_is_set()
+    }
+
+    /// Clears the CRC error flag
+    pub fn clear_crc_error(&mut self) {
+        self.spi.sr.write(|w| w.crcerr().clear_bit());
+    }
+
+    /// Clears the overrun flag, dropping the word held in the data register
+    pub fn clear_overrun(&mut self) {
+        // OVR is cleared by reading DR then SR
+        let _ = self.read_data_reg();
+        let _ = self.spi.sr.read();
+    }
+
+    /// Runs `f` with the peripheral disabled, then restores its previous enable state
+    ///
+    /// The peripheral is not enabled if it was disabled, so that a hardware NSS output is not
+    /// asserted outside a transaction.
+    fn with_spi_disabled(&mut self, f: impl FnOnce(&SPI)) {
+        let spe = self.spi.cr1.read().spe().bit_is_set();
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        f(&self.spi);
+        self.spi.cr1.modify(|_, w| w.spe().bit(spe));
+    }
+
+    /// Enables hardware CRC calculation with `polynomial`, resetting the CRC values
+    pub fn enable_crc(&mut self, polynomial: u16) {
+        self.with_spi_disabled(|spi| {
+            spi.crcpr.write(|w| w.crcpoly().bits(polynomial));
+            spi.cr1.modify(|_, w| w.crcen().set_bit());
+        });
+    }
+
+    /// Disables hardware CRC calculation
+    pub fn disable_crc(&mut self) {
+        self.with_spi_disabled(|spi| {
+            spi.cr1
+                .modify(|_, w| w.crcen().clear_bit().crcnext().clear_bit())
+        });
+    }
+
+    /// Resets the transmit and receive CRC values
+    pub fn reset_crc(&mut self) {
+        // The CRC values are cleared by writing CRCEN
+        self.with_spi_disabled(|spi| {
+            spi.cr1
+                .modify(|_, w| w.crcen().clear_bit().crcnext().clear_bit());
+            spi.cr1.modify(|_, w| w.crcen().set_bit());
+        });
+    }
+
+    /// Returns the CRC of the transmitted data
+    pub fn tx_crc(&self) -> u16 {
+        self.spi.txcrcr.read().tx_crc().bits()
+    }
+
+    /// Returns the CRC of the received data
+    pub fn rx_crc(&self) -> u16 {
+        self.spi.rxcrcr.read().rx_crc().bits()
+    }
+
+    /// Exchanges `words` like the blocking `Transfer` trait, followed by the CRC
+    ///
+    /// The CRC enabled with [`Spi::enable_crc`] is transmitted after the data, and the CRC
+    /// received after the data is checked, producing `Error::Crc` on a mismatch. The CRC
+    /// values are reset afterwards.
+    pub fn transfer_with_crc<'w>(
+        &mut self,
+        words: &'w mut [FrameSize],
+    ) -> Result<&'w [FrameSize], Error> {
+        let len = words.len();
+        for (i, word) in words.iter_mut().enumerate() {
+            while !self.is_tx_empty() {}
+            self.write_data_reg(*word);
+            if i + 1 == len {
+                self.spi.cr1.modify(|_, w| w.crcnext().set_bit());
+            }
+            *word = nb::block!(crate::hal::spi::FullDuplex::read(self))?;
+        }
+
+        // The received CRC, checked by the hardware
+        while !self.is_rx_not_empty() {}
+        let _ = self.read_data_reg();
+        while self.is_busy() {}
+        let crc_error = self.spi.sr.read().crcerr().bit_is_set();
+        self.clear_crc_error();
+        self.reset_crc();
+
+        if crc_error {
+            Err(Error::Crc)
+        } else {
+            Ok(words)
+        }
+    }
+
+    /// Transmits `words` without handling the received data
+    ///
+    /// The received words are dropped and the resulting overrun is cleared once the last word
+    /// has been sent, which makes this the fastest way to push data to write only devices.
+    pub fn write_only(&mut self, words: &[FrameSize]) -> Result<(), Error> {
+        self.spi_write(words)
+    }
+
+    /// Fills `words` with received data, clocking out `FrameSize::default()` dummy words
+    pub fn read_only(&mut self, words: &mut [FrameSize]) -> Result<(), Error>
+    where
+        FrameSize: Default,
+    {
+        self.exchange(words, Some(FrameSize::default()))
+    }
+
+    /// Exchanges `words`, replacing each transmitted word with the one received
+    pub fn transfer_in_place(&mut self, words: &mut [FrameSize]) -> Result<(), Error> {
+        self.exchange(words, None)
+    }
+
+    /// Transmits `dummy`, or `words` if none, storing the received data in `words`
+    ///
+    /// The next word is queued before reading the current one, so the clock is not stopped
+    /// between words.
+    fn exchange(&mut self, words: &mut [FrameSize], dummy: Option<FrameSize>) -> Result<(), Error> {
+        let len = words.len();
+        if len == 0 {
+            return Ok(());
+        }
+        nb::block!(FullDuplex::send(self, dummy.unwrap_or(words[0])))?;
+        for i in 0..len {
+            if i + 1 < len {
+                nb::block!(FullDuplex::send(self, dummy.unwrap_or(words[i + 1])))?;
+            }
+            words[i] = nb::block!(FullDuplex::read(self))?;
+        }
+        Ok(())
+    }
+
+    /// Starts generating the clock for receiving in master mode
+    fn start_receive_only(&mut self) {
+        if self.spi.cr1.read().mstr().bit_is_set() {
+            self.spi.cr1.modify(|_, w| w.rxonly().set_bit());
+        }
+    }
+
+    /// Stops the clock started by `start_receive_only` and drops the pending data
+    fn stop_receive_only(&mut self) {
+        if self.spi.cr1.read().rxonly().bit_is_set() {
+            self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+            self.spi
+                .cr1
+                .modify(|_, w| w.rxonly().clear_bit().spe().set_bit());
+            let _ = self.read_data_reg();
+            let _ = self.spi.sr.read();
+        }
+    }
+
+    /// Drops the data received during a transmit only transfer and clears the resulting
+    /// overrun. In master mode the end of the transmission is awaited first.
+    fn discard_received(&mut self) {
+        if self.spi.cr1.read().mstr().bit_is_set() {
+            while !self.is_tx_empty() {}
+            while self.is_busy() {}
+        }
+        self.clear_overrun();
+    }
 }
 
 impl<SPI, REMAP, PINS> Spi<SPI, REMAP, PINS, u8, Master>
@@ -479,17 +745,7 @@ where
         // disable SS output
         spi.cr2.write(|w| w.ssoe().clear_bit());
 
-        let br = match SPI::clock(&clocks) / freq {
-            0 => unreachable!(),
-            1..=2 => 0b000,
-            3..=5 => 0b001,
-            6..=11 => 0b010,
-            12..=23 => 0b011,
-            24..=47 => 0b100,
-            48..=95 => 0b101,
-            96..=191 => 0b110,
-            _ => 0b111,
-        };
+        let br = baud_rate_divider(SPI::clock(&clocks), freq);
 
         spi.cr1.write(|w| {
             w
@@ -595,15 +851,204 @@ where
     }
 }
 
-impl<SPI, REMAP, PINS, OP> Spi<SPI, REMAP, PINS, u8, OP>
+impl<SPI, REMAP, PINS, FrameSize> Spi<SPI, REMAP, PINS, FrameSize, Master>
 where
     SPI: Instance,
+    FrameSize: Copy,
 {
-    /// Converts from 8bit dataframe to 16bit.
-    pub fn frame_size_16bit(self) -> Spi<SPI, REMAP, PINS, u16> {
+    /// Clears a mode fault and enables the interface in master mode again
+    ///
+    /// Does nothing if no mode fault occurred.
+    pub fn recover_mode_fault(&mut self) {
+        // MODF is cleared by reading SR then writing CR1, the fault having cleared MSTR and SPE
+        if self.spi.sr.read().modf().bit_is_set() {
+            self.spi
+                .cr1
+                .modify(|_, w| w.mstr().set_bit().spe().set_bit());
+        }
+    }
+
+    /// Changes the clock divider, clock mode and bit order without recreating the driver
+    ///
+    /// Waits for the ongoing transfer to finish, the interface is briefly disabled while the
+    /// settings are changed. Other settings, like the frame size or CRC, are kept.
+    pub fn reconfigure(&mut self, config: Config, clocks: &Clocks) {
+        let br = baud_rate_divider(SPI::clock(clocks), config.frequency);
+
+        while !self.is_tx_empty() || self.is_busy() {}
         self.spi.cr1.modify(|_, w| w.spe().clear_bit());
-        self.spi.cr1.modify(|_, w| w.dff().set_bit());
+        self.spi.cr1.modify(|_, w| {
+            w.cpha()
+                .bit(config.mode.phase == Phase::CaptureOnSecondTransition)
+                .cpol()
+                .bit(config.mode.polarity == Polarity::IdleHigh)
+                .br()
+                .bits(br)
+                .lsbfirst()
+                .bit(matches!(config.bit_format, SpiBitFormat::LsbFirst))
+        });
         self.spi.cr1.modify(|_, w| w.spe().set_bit());
+    }
+
+    /// Enables or disables the 3-wire half-duplex mode, where MOSI is used to both transmit
+    /// and receive. MISO is unused in this mode and can be `NoMiso`.
+    pub fn set_half_duplex(&mut self, enable: bool) {
+        self.spi
+            .cr1
+            .modify(|_, w| w.bidimode().bit(enable).bidioe().set_bit());
+    }
+
+    /// Transmits `words` in half-duplex mode
+    pub fn half_duplex_write(&mut self, words: &[FrameSize]) -> Result<(), Error> {
+        self.spi.cr1.modify(|_, w| w.bidioe().set_bit());
+        self.spi_write(words)
+    }
+
+    /// Receives enough words to fill `words` in half-duplex mode
+    ///
+    /// The clock runs as long as the line is in receive mode, so the interface is disabled
+    /// during the last word as described in RM0008 25.3.8, one SPI clock period after its
+    /// start. `clocks` gives the length of this period in core clock cycles.
+    pub fn half_duplex_read(
+        &mut self,
+        words: &mut [FrameSize],
+        clocks: &Clocks,
+    ) -> Result<(), Error> {
+        let len = words.len();
+        if len == 0 {
+            return Ok(());
+        }
+        let core_cycles_per_pclk = clocks.sysclk().raw() / SPI::clock(clocks).raw();
+        let spi_clock_cycles = (2u32 << self.spi.cr1.read().br().bits()) * core_cycles_per_pclk;
+
+        // Switching to receive mode starts the clock
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        self.spi
+            .cr1
+            .modify(|_, w| w.bidioe().clear_bit().spe().set_bit());
+        if len == 1 {
+            cortex_m::asm::delay(spi_clock_cycles);
+            self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        }
+
+        let mut result = Ok(());
+        for (i, word) in words.iter_mut().enumerate() {
+            loop {
+                let sr = self.spi.sr.read();
+                if sr.ovr().bit_is_set() {
+                    result = Err(Error::Overrun);
+                }
+                if sr.rxne().bit_is_set() {
+                    break;
+                }
+            }
+            *word = self.read_data_reg();
+            if i + 2 == len {
+                cortex_m::asm::delay(spi_clock_cycles);
+                self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+            }
+        }
+
+        // Back to transmit mode, which stops the clock
+        self.spi
+            .cr1
+            .modify(|_, w| w.bidioe().set_bit().spe().set_bit());
+        let _ = self.spi.sr.read();
+        result
+    }
+}
+
+impl<SPI, REMAP, PINS, FrameSize> Spi<SPI, REMAP, PINS, FrameSize, Master>
+where
+    SPI: Instance,
+{
+    /**
+      Lets the peripheral drive the NSS pin, configured as `Alternate<...>`
+
+      NSS is asserted while the interface is enabled, so the interface stays disabled outside
+      of [`Spi::transaction`] and all transfers have to be made within a transaction.
+    */
+    pub fn with_hardware_nss<NSS: Nss<REMAP>>(
+        self,
+        nss: NSS,
+    ) -> Spi<SPI, REMAP, (PINS, NSS), FrameSize, Master> {
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        self.spi.cr1.modify(|_, w| w.ssm().clear_bit());
+        self.spi.cr2.modify(|_, w| w.ssoe().set_bit());
+        Spi {
+            spi: self.spi,
+            pins: (self.pins, nss),
+            _remap: PhantomData,
+            _framesize: PhantomData,
+            _operation: PhantomData,
+        }
+    }
+}
+
+impl<SPI, REMAP, PINS, NSS, FrameSize> Spi<SPI, REMAP, (PINS, NSS), FrameSize, Master>
+where
+    SPI: Instance,
+{
+    /// Runs `f` with NSS asserted, NSS is released once the last word is transmitted
+    pub fn transaction<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+        let ret = f(self);
+        while self.spi.sr.read().txe().bit_is_clear() {}
+        while self.spi.sr.read().bsy().bit_is_set() {}
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        ret
+    }
+
+    /// Returns to software slave management and releases the NSS pin
+    pub fn without_hardware_nss(self) -> (Spi<SPI, REMAP, PINS, FrameSize, Master>, NSS) {
+        self.spi.cr2.modify(|_, w| w.ssoe().clear_bit());
+        self.spi
+            .cr1
+            .modify(|_, w| w.ssm().set_bit().ssi().set_bit());
+        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+        let (pins, nss) = self.pins;
+        (
+            Spi {
+                spi: self.spi,
+                pins,
+                _remap: PhantomData,
+                _framesize: PhantomData,
+                _operation: PhantomData,
+            },
+            nss,
+        )
+    }
+}
+
+impl<SPI, REMAP, PINS, FrameSize> Spi<SPI, REMAP, PINS, FrameSize, Slave>
+where
+    SPI: Instance,
+{
+    /// Selects how the slave is selected
+    ///
+    /// In hardware mode the NSS pin (`PA4`/`PA15` for SPI1 and SPI3, `PB12` for SPI2) has to
+    /// be configured as an input.
+    pub fn set_slave_select(&mut self, nss: SlaveSelect) {
+        self.spi
+            .cr1
+            .modify(|_, w| w.ssm().bit(nss == SlaveSelect::Software));
+    }
+
+    /// Selects or deselects the slave when using [`SlaveSelect::Software`]
+    ///
+    /// A deselected slave ignores the clock and doesn't drive MISO.
+    pub fn set_selected(&mut self, selected: bool) {
+        self.spi.cr1.modify(|_, w| w.ssi().bit(!selected));
+    }
+}
+
+impl<SPI, REMAP, PINS, OP> Spi<SPI, REMAP, PINS, u8, OP>
+where
+    SPI: Instance,
+{
+    /// Converts from 8bit dataframe to 16bit.
+    pub fn frame_size_16bit(mut self) -> Spi<SPI, REMAP, PINS, u16, OP> {
+        self.with_spi_disabled(|spi| spi.cr1.modify(|_, w| w.dff().set_bit()));
         Spi {
             spi: self.spi,
             pins: self.pins,
@@ -619,10 +1064,8 @@ where
     SPI: Instance,
 {
     /// Converts from 16bit dataframe to 8bit.
-    pub fn frame_size_8bit(self) -> Spi<SPI, REMAP, PINS, u8> {
-        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
-        self.spi.cr1.modify(|_, w| w.dff().clear_bit());
-        self.spi.cr1.modify(|_, w| w.spe().set_bit());
+    pub fn frame_size_8bit(mut self) -> Spi<SPI, REMAP, PINS, u8, OP> {
+        self.with_spi_disabled(|spi| spi.cr1.modify(|_, w| w.dff().clear_bit()));
         Spi {
             spi: self.spi,
             pins: self.pins,
@@ -649,6 +1092,7 @@ where
         } else if sr.modf().bit_is_set() {
             nb::Error::Other(Error::ModeFault)
         } else if sr.crcerr().bit_is_set() {
+            self.clear_crc_error();
             nb::Error::Other(Error::Crc)
         } else if sr.rxne().bit_is_set() {
             // NOTE(read_volatile) read only 1 byte (the svd2rust API only allows
@@ -710,6 +1154,48 @@ where
     }
 }
 
+impl<SPI, REMAP, PINS, FrameSize, OP> embedded_hal_one::spi::ErrorType
+    for Spi<SPI, REMAP, PINS, FrameSize, OP>
+{
+    type Error = Error;
+}
+
+impl<SPI, REMAP, PINS, FrameSize, OP> embedded_hal_one::spi::SpiBus<FrameSize>
+    for Spi<SPI, REMAP, PINS, FrameSize, OP>
+where
+    SPI: Instance,
+    FrameSize: Copy + Default + 'static,
+{
+    fn read(&mut self, words: &mut [FrameSize]) -> Result<(), Error> {
+        self.read_only(words)
+    }
+
+    fn write(&mut self, words: &[FrameSize]) -> Result<(), Error> {
+        self.spi_write(words)
+    }
+
+    fn transfer(&mut self, read: &mut [FrameSize], write: &[FrameSize]) -> Result<(), Error> {
+        for i in 0..read.len().max(write.len()) {
+            let word = write.get(i).copied().unwrap_or_default();
+            nb::block!(FullDuplex::send(self, word))?;
+            let word = nb::block!(FullDuplex::read(self))?;
+            if let Some(r) = read.get_mut(i) {
+                *r = word;
+            }
+        }
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [FrameSize]) -> Result<(), Error> {
+        Spi::transfer_in_place(self, words)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        while self.is_busy() {}
+        Ok(())
+    }
+}
+
 // DMA
 
 pub type SpiTxDma<SPI, REMAP, PINS, OP, CHANNEL> = TxDma<Spi<SPI, REMAP, PINS, u8, OP>, CHANNEL>;
@@ -811,6 +1297,7 @@ macro_rules! spi_dma {
             }
             fn stop(&mut self) {
                 self.channel.stop();
+                self.payload.discard_received();
             }
         }
 
@@ -820,6 +1307,7 @@ macro_rules! spi_dma {
             }
             fn stop(&mut self) {
                 self.channel.stop();
+                self.payload.stop_receive_only();
             }
         }
 
@@ -834,6 +1322,57 @@ macro_rules! spi_dma {
             }
         }
 
+        impl<B, REMAP, PIN, OP> crate::dma::CircReadDma<B, u8>
+            for SpiRxDma<$SPIi, REMAP, PIN, OP, $RCi>
+        where
+            &'static mut [B; 2]: WriteBuffer<Word = u8>,
+            B: 'static,
+        {
+            /// Receives continuously into the two halves of `buffer`
+            ///
+            /// In master mode the interface is switched to receive only mode, so that the
+            /// clock is generated without transmitting, until the transfer is stopped. In slave
+            /// mode the data is captured as it is clocked in by the master.
+            ///
+            /// Listen to the `HalfTransfer` and `TransferComplete` events of the channel
+            /// before starting to process each half from the DMA interrupt.
+            fn circ_read(mut self, mut buffer: &'static mut [B; 2]) -> CircBuffer<B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.write_buffer() };
+                self.channel.set_peripheral_address(
+                    unsafe { &(*<$SPIi>::ptr()).dr as *const _ as u32 },
+                    false,
+                );
+                self.channel.set_memory_address(ptr as u32, true);
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.ch().cr.modify(|_, w| {
+                    w
+                        // memory to memory mode disabled
+                        .mem2mem()
+                        .clear_bit()
+                        // 8-bit memory size
+                        .msize()
+                        .bits8()
+                        // 8-bit peripheral size
+                        .psize()
+                        .bits8()
+                        // circular mode enabled
+                        .circ()
+                        .set_bit()
+                        // write to memory
+                        .dir()
+                        .clear_bit()
+                });
+                self.start();
+                self.payload.start_receive_only();
+
+                CircBuffer::new(buffer, self)
+            }
+        }
+
         impl<B, REMAP, PIN, OP> crate::dma::ReadDma<B, u8> for SpiRxDma<$SPIi, REMAP, PIN, OP, $RCi>
         where
             B: WriteBuffer<Word = u8>,
@@ -855,9 +1394,6 @@ macro_rules! spi_dma {
                         // memory to memory mode disabled
                         .mem2mem()
                         .clear_bit()
-                        // medium channel priority level
-                        .pl()
-                        .medium()
                         // 8-bit memory size
                         .msize()
                         .bits8()
@@ -899,9 +1435,6 @@ macro_rules! spi_dma {
                         // memory to memory mode disabled
                         .mem2mem()
                         .clear_bit()
-                        // medium channel priority level
-                        .pl()
-                        .medium()
                         // 8-bit memory size
                         .msize()
                         .bits8()
@@ -961,9 +1494,6 @@ macro_rules! spi_dma {
                         // memory to memory mode disabled
                         .mem2mem()
                         .clear_bit()
-                        // medium channel priority level
-                        .pl()
-                        .medium()
                         // 8-bit memory size
                         .msize()
                         .bits8()
@@ -982,9 +1512,6 @@ macro_rules! spi_dma {
                         // memory to memory mode disabled
                         .mem2mem()
                         .clear_bit()
-                        // medium channel priority level
-                        .pl()
-                        .medium()
                         // 8-bit memory size
                         .msize()
                         .bits8()
@@ -1022,7 +1549,7 @@ spi_dma!(
     Spi2TxDma,
     Spi2RxTxDma
 );
-#[cfg(feature = "connectivity")]
+#[cfg(any(feature = "high", feature = "connectivity"))]
 spi_dma!(
     pac::SPI3,
     dma2::C1,
@@ -0,0 +1,182 @@
+//! Async SPI master driven by the SPI interrupt
+//!
+//! [`on_interrupt`] has to be called from the interrupt handler of the SPI instance:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn SPI1() {
+//!     spi::asynch::on_interrupt::<pac::SPI1>();
+//! }
+//! ```
+//!
+//! Dropping a transfer future waits for the word in progress to be received and discards it,
+//! so the interface is ready for the next transfer.
+
+// `poll_fn` is stable since 1.64, the `async` feature requires Rust 1.75
+#![allow(clippy::incompatible_msrv)]
+
+use super::*;
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+use cortex_m::interrupt::{self, Mutex};
+
+/// SPI instance with a task waker
+pub trait WakerInstance: Instance {
+    #[doc(hidden)]
+    fn waker() -> &'static Mutex<RefCell<Option<Waker>>>;
+    #[doc(hidden)]
+    fn regs() -> &'static pac::spi1::RegisterBlock;
+}
+
+macro_rules! waker {
+    ($SPIX:ty, $WAKER:ident) => {
+        static $WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
+
+        impl WakerInstance for $SPIX {
+            fn waker() -> &'static Mutex<RefCell<Option<Waker>>> {
+                &$WAKER
+            }
+
+            fn regs() -> &'static pac::spi1::RegisterBlock {
+                unsafe { &*<$SPIX>::ptr() }
+            }
+        }
+    };
+}
+
+waker!(pac::SPI1, SPI1_WAKER);
+waker!(pac::SPI2, SPI2_WAKER);
+#[cfg(any(feature = "high", feature = "connectivity"))]
+waker!(pac::SPI3, SPI3_WAKER);
+
+/// Wakes the task waiting for `SPI`, to be called from the SPI interrupt handler
+pub fn on_interrupt<SPI: WakerInstance>() {
+    SPI::regs()
+        .cr2
+        .modify(|_, w| w.rxneie().clear_bit().errie().clear_bit());
+    if let Some(waker) = interrupt::free(|cs| SPI::waker().borrow(cs).take()) {
+        waker.wake();
+    }
+}
+
+// Word transfer in progress, cancelled when dropped before its completion
+struct WordTransfer<SPI: WakerInstance> {
+    done: bool,
+    _spi: PhantomData<SPI>,
+}
+
+impl<SPI: WakerInstance> Drop for WordTransfer<SPI> {
+    fn drop(&mut self) {
+        if !self.done {
+            let regs = SPI::regs();
+            regs.cr2
+                .modify(|_, w| w.rxneie().clear_bit().errie().clear_bit());
+            interrupt::free(|cs| SPI::waker().borrow(cs).take());
+            while regs.sr.read().rxne().bit_is_clear() {}
+            regs.dr.read();
+        }
+    }
+}
+
+/// SPI master implementing the `embedded-hal-async` `SpiBus` trait
+pub struct AsyncSpi<SPI, REMAP, PINS> {
+    spi: Spi<SPI, REMAP, PINS, u8, Master>,
+}
+
+impl<SPI, REMAP, PINS> Spi<SPI, REMAP, PINS, u8, Master>
+where
+    SPI: WakerInstance,
+{
+    /// Converts into an async SPI master
+    pub fn into_async(self) -> AsyncSpi<SPI, REMAP, PINS> {
+        AsyncSpi { spi: self }
+    }
+}
+
+impl<SPI, REMAP, PINS> AsyncSpi<SPI, REMAP, PINS>
+where
+    SPI: WakerInstance,
+{
+    /// Returns the blocking SPI master
+    pub fn release(self) -> Spi<SPI, REMAP, PINS, u8, Master> {
+        self.spi
+    }
+
+    /// Transmits `word` and waits for the received word
+    async fn transfer_word(&mut self, word: u8) -> Result<u8, Error> {
+        while !self.spi.is_tx_empty() {}
+        self.spi.write_data_reg(word);
+
+        let mut transfer = WordTransfer::<SPI> {
+            done: false,
+            _spi: PhantomData,
+        };
+        poll_fn(|cx| match FullDuplex::read(&mut self.spi) {
+            Ok(word) => {
+                transfer.done = true;
+                Poll::Ready(Ok(word))
+            }
+            Err(nb::Error::Other(e)) => {
+                transfer.done = true;
+                Poll::Ready(Err(e))
+            }
+            Err(nb::Error::WouldBlock) => {
+                interrupt::free(|cs| SPI::waker().borrow(cs).replace(Some(cx.waker().clone())));
+                self.spi
+                    .spi
+                    .cr2
+                    .modify(|_, w| w.rxneie().set_bit().errie().set_bit());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<SPI, REMAP, PINS> embedded_hal_one::spi::ErrorType for AsyncSpi<SPI, REMAP, PINS> {
+    type Error = Error;
+}
+
+impl<SPI, REMAP, PINS> embedded_hal_async::spi::SpiBus for AsyncSpi<SPI, REMAP, PINS>
+where
+    SPI: WakerInstance,
+{
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        for word in words {
+            *word = self.transfer_word(0).await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        for word in words {
+            self.transfer_word(*word).await?;
+        }
+        Ok(())
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+        for i in 0..read.len().max(write.len()) {
+            let word = self
+                .transfer_word(write.get(i).copied().unwrap_or(0))
+                .await?;
+            if let Some(r) = read.get_mut(i) {
+                *r = word;
+            }
+        }
+        Ok(())
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        for word in words {
+            *word = self.transfer_word(*word).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        while self.spi.is_busy() {}
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Interrupt-driven SPI master with transmit and receive queues
+
+use super::*;
+use heapless::spsc::Queue;
+
+/// SPI master transferring the words of its queues from the SPI interrupt
+///
+/// Every transmitted word produces one received word. Transmission pauses while the
+/// receive queue has no room left for the words in flight.
+///
+/// The queues hold up to `N - 1` words each.
+pub struct BufferedSpi<SPI, REMAP, PINS, const N: usize> {
+    spi: Spi<SPI, REMAP, PINS, u8, Master>,
+    tx: Queue<u8, N>,
+    rx: Queue<u8, N>,
+    in_flight: usize,
+    overrun: bool,
+}
+
+impl<SPI, REMAP, PINS> Spi<SPI, REMAP, PINS, u8, Master>
+where
+    SPI: Instance,
+{
+    /// Converts into an interrupt-driven SPI master, `BufferedSpi::handle_interrupt` has to be
+    /// called from the SPI interrupt handler
+    pub fn buffered<const N: usize>(mut self) -> BufferedSpi<SPI, REMAP, PINS, N> {
+        self.listen(Event::Rxne);
+        BufferedSpi {
+            spi: self,
+            tx: Queue::new(),
+            rx: Queue::new(),
+            in_flight: 0,
+            overrun: false,
+        }
+    }
+}
+
+impl<SPI, REMAP, PINS, const N: usize> BufferedSpi<SPI, REMAP, PINS, N>
+where
+    SPI: Instance,
+{
+    /// Queues `word` for transmission
+    ///
+    /// Returns `WouldBlock` if the transmit queue is full.
+    pub fn write(&mut self, word: u8) -> nb::Result<(), Error> {
+        self.tx.enqueue(word).map_err(|_| nb::Error::WouldBlock)?;
+        self.spi.listen(Event::Txe);
+        Ok(())
+    }
+
+    /// Takes the next received word from the receive queue
+    ///
+    /// Returns `Error::Overrun` once if a word was lost since the last call.
+    pub fn read(&mut self) -> nb::Result<u8, Error> {
+        if self.overrun {
+            self.overrun = false;
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        let word = self.rx.dequeue().ok_or(nb::Error::WouldBlock)?;
+        if !self.tx.is_empty() {
+            self.spi.listen(Event::Txe);
+        }
+        Ok(word)
+    }
+
+    /// Returns `true` once all the queued words are transmitted
+    pub fn is_flushed(&self) -> bool {
+        self.tx.is_empty() && self.in_flight == 0
+    }
+
+    /// Number of received words waiting in the receive queue
+    pub fn available(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Moves the words between the queues and the peripheral
+    pub fn handle_interrupt(&mut self) {
+        let sr = self.spi.spi.sr.read();
+
+        if sr.ovr().bit_is_set() {
+            let _ = self.spi.read_data_reg();
+            let _ = self.spi.spi.sr.read();
+            self.overrun = true;
+            self.in_flight = 0;
+        } else if sr.rxne().bit_is_set() {
+            let word = self.spi.read_data_reg();
+            self.in_flight = self.in_flight.saturating_sub(1);
+            if self.rx.enqueue(word).is_err() {
+                self.overrun = true;
+            }
+        }
+
+        if sr.txe().bit_is_set() && self.spi.spi.cr2.read().txeie().bit_is_set() {
+            if self.rx.len() + self.in_flight >= self.rx.capacity() {
+                // Wait for the receive queue to be read
+                self.spi.unlisten(Event::Txe);
+            } else if let Some(word) = self.tx.dequeue() {
+                self.spi.write_data_reg(word);
+                self.in_flight += 1;
+            } else {
+                self.spi.unlisten(Event::Txe);
+            }
+        }
+    }
+
+    /// Stops the interrupts and returns the SPI, the queued words are dropped
+    pub fn release(mut self) -> Spi<SPI, REMAP, PINS, u8, Master> {
+        self.spi.unlisten(Event::Txe);
+        self.spi.unlisten(Event::Rxne);
+        self.spi
+    }
+}
@@ -0,0 +1,138 @@
+//! `embedded-hal` 1.0 `SpiDevice` on top of an exclusively owned SPI bus
+//!
+//! ```ignore
+//! let spi = Spi::spi1(dp.SPI1, pins, &mut afio.mapr, mode, 1.MHz(), clocks);
+//! let cs = gpioa.pa4.into_push_pull_output_with_state(&mut gpioa.crl, PinState::High);
+//! let mut device = SpiDevice::new(spi, cs, &clocks);
+//! ```
+
+use crate::hal::digital::v2::OutputPin;
+use crate::rcc::Clocks;
+use crate::time::Hertz;
+use embedded_hal_one::delay::DelayNs;
+use embedded_hal_one::spi::{self, ErrorKind, ErrorType, Operation, SpiBus};
+
+/// Error of a [`SpiDevice`] transaction
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DeviceError<SPI, CS> {
+    /// SPI bus error
+    Spi(SPI),
+    /// Error driving the CS pin
+    Cs(CS),
+    /// Shared bus in use by another transaction, e.g. interrupted by this one
+    Busy,
+}
+
+impl<SPI: spi::Error, CS: core::fmt::Debug> spi::Error for DeviceError<SPI, CS> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Spi(e) => e.kind(),
+            Self::Cs(_) => ErrorKind::ChipSelectFault,
+            Self::Busy => ErrorKind::Other,
+        }
+    }
+}
+
+/// Default delay of a device created without a delay provider
+///
+/// Busy waits counting cycles of the core clock.
+pub struct NoDelay {
+    sysclk: Hertz,
+}
+
+impl NoDelay {
+    /// Creates a delay for the core clock of `clocks`
+    pub fn new(clocks: &Clocks) -> Self {
+        Self {
+            sysclk: clocks.sysclk(),
+        }
+    }
+}
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        let cycles = u64::from(ns) * u64::from(self.sysclk.raw()) / 1_000_000_000;
+        cortex_m::asm::delay(cycles as u32 + 1);
+    }
+}
+
+/// SPI device owning its bus and chip select pin
+///
+/// CS is asserted (driven low) for the duration of each transaction, after which the bus is
+/// flushed and CS deasserted again.
+pub struct SpiDevice<BUS, CS, D = NoDelay> {
+    bus: BUS,
+    cs: CS,
+    delay: D,
+}
+
+impl<BUS, CS> SpiDevice<BUS, CS, NoDelay>
+where
+    CS: OutputPin,
+{
+    /// Creates a device busy waiting for `Operation::DelayNs` at the core clock of `clocks`,
+    /// deasserting CS
+    pub fn new(bus: BUS, cs: CS, clocks: &Clocks) -> Result<Self, CS::Error> {
+        Self::with_delay(bus, cs, NoDelay::new(clocks))
+    }
+}
+
+impl<BUS, CS, D> SpiDevice<BUS, CS, D>
+where
+    CS: OutputPin,
+{
+    /// Creates a device using `delay` for `Operation::DelayNs`, deasserting CS
+    pub fn with_delay(bus: BUS, mut cs: CS, delay: D) -> Result<Self, CS::Error> {
+        cs.set_high()?;
+        Ok(Self { bus, cs, delay })
+    }
+
+    /// Returns the bus, the CS pin and the delay provider
+    pub fn release(self) -> (BUS, CS, D) {
+        (self.bus, self.cs, self.delay)
+    }
+
+    /// Gives direct access to the underlying bus
+    pub fn bus(&mut self) -> &mut BUS {
+        &mut self.bus
+    }
+}
+
+impl<BUS, CS, D> ErrorType for SpiDevice<BUS, CS, D>
+where
+    BUS: ErrorType,
+    CS: OutputPin,
+    CS::Error: core::fmt::Debug,
+{
+    type Error = DeviceError<BUS::Error, CS::Error>;
+}
+
+impl<Word, BUS, CS, D> spi::SpiDevice<Word> for SpiDevice<BUS, CS, D>
+where
+    Word: Copy + 'static,
+    BUS: SpiBus<Word>,
+    CS: OutputPin,
+    CS::Error: core::fmt::Debug,
+    D: DelayNs,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, Word>]) -> Result<(), Self::Error> {
+        self.cs.set_low().map_err(DeviceError::Cs)?;
+
+        let ret = operations.iter_mut().try_for_each(|op| match op {
+            Operation::Read(words) => self.bus.read(words),
+            Operation::Write(words) => self.bus.write(words),
+            Operation::Transfer(read, write) => self.bus.transfer(read, write),
+            Operation::TransferInPlace(words) => self.bus.transfer_in_place(words),
+            Operation::DelayNs(ns) => {
+                self.bus.flush()?;
+                self.delay.delay_ns(*ns);
+                Ok(())
+            }
+        });
+        // Wait for the last word before releasing CS, even on error
+        let flush = self.bus.flush();
+
+        self.cs.set_high().map_err(DeviceError::Cs)?;
+        ret.and(flush).map_err(DeviceError::Spi)
+    }
+}
@@ -0,0 +1,144 @@
+//! Sharing one SPI bus between several devices
+//!
+//! Each device has its own chip select pin and, optionally, its own bus settings which are
+//! applied at the start of each of its transactions.
+//!
+//! ```ignore
+//! let bus = SharedSpi::new(spi, Config::new(MODE_0, 1.MHz()), clocks);
+//! let mut flash = bus.device(flash_cs, None)?;
+//! let mut display = bus.device(display_cs, Some(Config::new(MODE_3, 8.MHz())))?;
+//! ```
+
+use super::{Config, DeviceError, Error, Instance, Master, Spi};
+use crate::hal::digital::v2::OutputPin;
+use crate::rcc::Clocks;
+use core::cell::{Cell, UnsafeCell};
+use cortex_m::interrupt::{self, Mutex};
+use embedded_hal_one::spi::{ErrorType, Operation, SpiBus, SpiDevice};
+
+/// Owner of a shared SPI master bus
+///
+/// Each transaction made through a [`SharedSpiDevice`] borrows the bus in a short critical
+/// section, so the bus can also be shared with interrupt handlers when stored in a `static`.
+/// The transfers themselves run with the interrupts enabled, and a transaction started while
+/// another one is in progress fails with `DeviceError::Busy`.
+pub struct SharedSpi<SPI, REMAP, PINS, FrameSize = u8> {
+    bus: UnsafeCell<Spi<SPI, REMAP, PINS, FrameSize, Master>>,
+    busy: Mutex<Cell<bool>>,
+    config: Config,
+    clocks: Clocks,
+}
+
+// NOTE(unsafe) the bus is only accessed by the transaction which has set `busy`
+unsafe impl<SPI, REMAP, PINS, FrameSize> Sync for SharedSpi<SPI, REMAP, PINS, FrameSize> where
+    Spi<SPI, REMAP, PINS, FrameSize, Master>: Send
+{
+}
+
+impl<SPI, REMAP, PINS, FrameSize> SharedSpi<SPI, REMAP, PINS, FrameSize> {
+    /// Shares `spi`, `config` being applied for the devices without their own settings
+    pub const fn new(
+        spi: Spi<SPI, REMAP, PINS, FrameSize, Master>,
+        config: Config,
+        clocks: Clocks,
+    ) -> Self {
+        Self {
+            bus: UnsafeCell::new(spi),
+            busy: Mutex::new(Cell::new(false)),
+            config,
+            clocks,
+        }
+    }
+
+    /// Returns a handle to a device selected by `cs`, deasserting it
+    ///
+    /// `config` overrides the default bus settings for this device.
+    pub fn device<CS: OutputPin>(
+        &self,
+        mut cs: CS,
+        config: Option<Config>,
+    ) -> Result<SharedSpiDevice<'_, SPI, REMAP, PINS, FrameSize, CS>, CS::Error> {
+        cs.set_high()?;
+        Ok(SharedSpiDevice {
+            bus: self,
+            cs,
+            config: config.unwrap_or(self.config),
+        })
+    }
+}
+
+/// Device on a [`SharedSpi`] bus implementing the `embedded-hal` 1.0 `SpiDevice` trait
+pub struct SharedSpiDevice<'a, SPI, REMAP, PINS, FrameSize, CS> {
+    bus: &'a SharedSpi<SPI, REMAP, PINS, FrameSize>,
+    cs: CS,
+    config: Config,
+}
+
+impl<'a, SPI, REMAP, PINS, FrameSize, CS> SharedSpiDevice<'a, SPI, REMAP, PINS, FrameSize, CS> {
+    /// Returns the chip select pin
+    pub fn release(self) -> CS {
+        self.cs
+    }
+}
+
+impl<'a, SPI, REMAP, PINS, FrameSize, CS> ErrorType
+    for SharedSpiDevice<'a, SPI, REMAP, PINS, FrameSize, CS>
+where
+    CS: OutputPin,
+    CS::Error: core::fmt::Debug,
+{
+    type Error = DeviceError<Error, CS::Error>;
+}
+
+impl<'a, SPI, REMAP, PINS, FrameSize, CS> SpiDevice<FrameSize>
+    for SharedSpiDevice<'a, SPI, REMAP, PINS, FrameSize, CS>
+where
+    SPI: Instance,
+    FrameSize: Copy + Default + 'static,
+    CS: OutputPin,
+    CS::Error: core::fmt::Debug,
+{
+    fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, FrameSize>],
+    ) -> Result<(), Self::Error> {
+        let Self {
+            bus,
+            cs: pin,
+            config,
+        } = self;
+        if interrupt::free(|cs| bus.busy.borrow(cs).replace(true)) {
+            return Err(DeviceError::Busy);
+        }
+        // NOTE(unsafe) exclusive access to the bus until `busy` is cleared
+        let spi = unsafe { &mut *bus.bus.get() };
+
+        let ret = (|| {
+            spi.reconfigure(*config, &bus.clocks);
+
+            pin.set_low().map_err(DeviceError::Cs)?;
+
+            let ret = operations.iter_mut().try_for_each(|op| match op {
+                Operation::Read(words) => spi.read(words),
+                Operation::Write(words) => spi.write(words),
+                Operation::Transfer(read, write) => spi.transfer(read, write),
+                Operation::TransferInPlace(words) => spi.transfer_in_place(words),
+                Operation::DelayNs(ns) => {
+                    spi.flush()?;
+                    let cycles =
+                        u64::from(*ns) * u64::from(bus.clocks.sysclk().raw()) / 1_000_000_000;
+                    cortex_m::asm::delay(cycles as u32 + 1);
+                    Ok(())
+                }
+            });
+            // Wait for the last word before releasing CS, even on error
+            let flush = spi.flush();
+
+            pin.set_high().map_err(DeviceError::Cs)?;
+            ret.and(flush).map_err(DeviceError::Spi)
+        })();
+
+        interrupt::free(|cs| bus.busy.borrow(cs).set(false));
+        ret
+    }
+}
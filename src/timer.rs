@@ -49,6 +49,7 @@
 #![allow(non_upper_case_globals)]
 
 use crate::bb;
+use crate::dma::dma1;
 use crate::pac::{self, DBGMCU as DBG, RCC};
 
 use crate::rcc::{self, Clocks};
@@ -64,6 +65,8 @@ pub mod monotonic;
 pub use monotonic::*;
 pub(crate) mod pins;
 pub mod pwm_input;
+#[cfg(feature = "rtic2")]
+pub mod rtic2;
 pub use pins::*;
 pub mod delay;
 pub use delay::*;
@@ -71,6 +74,18 @@ pub mod counter;
 pub use counter::*;
 pub mod pwm;
 pub use pwm::*;
+pub mod capture;
+pub use capture::InputCapture;
+pub mod one_pulse;
+pub use one_pulse::OnePulse;
+pub mod sync;
+pub use sync::{InternalTrigger, SlaveMode};
+pub mod chained;
+pub use chained::ChainedTimer;
+pub mod external;
+pub use external::PulseCounter;
+#[cfg(feature = "ws2812")]
+pub mod ws2812;
 
 mod hal_02;
 
@@ -278,6 +293,7 @@ mod sealed {
         fn get_interrupt_flag(&self) -> Event;
         fn read_count(&self) -> Self::Width;
         fn start_one_pulse(&mut self);
+        fn set_one_pulse_mode(&mut self, b: bool);
         fn cr1_reset(&mut self);
         fn stop_in_debug(&mut self, dbg: &mut DBG, state: bool);
     }
@@ -289,25 +305,96 @@ mod sealed {
         fn preload_output_channel_in_mode(&mut self, channel: Channel, mode: Ocm);
         fn start_pwm(&mut self);
         fn enable_channel(channel: u8, b: bool);
+        fn cc_address(channel: u8) -> u32;
+        fn enable_update_dma(b: bool);
+        fn is_center_aligned() -> bool {
+            false
+        }
+        fn enable_main_output(&mut self) {}
+    }
+
+    pub trait CenterAligned: WithPwm {
+        fn set_cms(&mut self, cms: u8);
+    }
+
+    pub trait WithDmaBurst: WithPwm {
+        fn set_dma_burst(dba: u8, dbl: u8);
+        fn dmar_address() -> u32;
+    }
+
+    pub trait SlaveTimer: General {
+        fn set_slave_mode(&mut self, ts: u8, sms: u8);
+        fn set_external_trigger(&mut self, ece: bool, inverted: bool, etps: u8, etf: u8);
+    }
+
+    pub trait WithCapture: WithPwm {
+        fn configure_capture(&mut self, channel: u8, ccmr: u8);
+        fn set_capture_polarity(channel: u8, falling: bool);
+        fn is_overcaptured(channel: u8) -> bool;
+        fn clear_overcapture(channel: u8);
+    }
+
+    pub trait Advanced: WithPwm {
+        fn set_dtg(&mut self, dtg: u8);
+        fn set_main_output(&mut self, moe: bool, aoe: bool);
+        fn set_off_states(&mut self, run: bool, idle: bool);
+        fn set_break(&mut self, enable: bool, active_high: bool, aoe: bool);
+        fn listen_break(&mut self, b: bool);
+        fn is_break_triggered(&self) -> bool;
+        fn clear_break(&mut self);
+        fn enable_complementary(channel: u8, b: bool);
+        fn set_polarity(channel: u8, complementary: bool, active_low: bool);
     }
 
     pub trait MasterTimer: General {
         type Mms;
         fn master_mode(&mut self, mode: Self::Mms);
+        fn master_mode_update(&mut self);
     }
 }
-pub(crate) use sealed::{General, MasterTimer, WithPwm};
+pub(crate) use sealed::{
+    Advanced, CenterAligned, General, MasterTimer, SlaveTimer, WithCapture, WithDmaBurst, WithPwm,
+};
 
 pub trait Instance:
     crate::Sealed + rcc::Enable + rcc::Reset + rcc::BusTimerClock + General
 {
 }
 
+/// Timer with an update DMA request
+///
+/// `Channel` is the DMA channel serving the request. The requests of TIM5 to TIM8 are served
+/// by DMA2, of TIM6 and TIM7 by DMA1 on the low and medium-density value line.
+pub trait UpdateDma: Instance {
+    type Channel: crate::dma::DmaChannel;
+}
+
+macro_rules! update_dma {
+    ($($TIM:ty: $CX:ty,)+) => {
+        $(
+            impl UpdateDma for $TIM {
+                type Channel = $CX;
+            }
+        )+
+    };
+}
+
+// The stop field of the timer, or its bit when missing from the PAC
+macro_rules! dbg_stop {
+    ($dbg:ident, $field:ident, $state:ident) => {
+        $dbg.cr.modify(|_, w| w.$field().bit($state))
+    };
+    ($dbg:ident, $bit:literal, $state:ident) => {
+        $dbg.cr
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $bit) | ($state as u32) << $bit) })
+    };
+}
+
 macro_rules! hal {
     ($($TIM:ty: [
         $Timer:ident,
         $bits:ty,
-        $dbg_timX_stop:ident,
+        $dbg_timX_stop:tt,
         $(c: ($cnum:ident $(, $aoe:ident)?),)?
         $(m: $timbase:ident,)?
     ],)+) => {
@@ -402,26 +489,159 @@ macro_rules! hal {
                     self.cr1.write(|w| unsafe { w.bits(1 << 3) }.cen().set_bit());
                 }
                 #[inline(always)]
+                fn set_one_pulse_mode(&mut self, b: bool) {
+                    self.cr1.modify(|_, w| w.opm().bit(b));
+                }
+                #[inline(always)]
                 fn cr1_reset(&mut self) {
                     self.cr1.reset();
                 }
                 #[inline(always)]
                 fn stop_in_debug(&mut self, dbg: &mut DBG, state: bool) {
-                    dbg.cr.modify(|_, w| w.$dbg_timX_stop().bit(state));
+                    dbg_stop!(dbg, $dbg_timX_stop, state);
                 }
             }
-            $(with_pwm!($TIM: $cnum $(, $aoe)?);)?
+            $(
+                with_pwm!($TIM: $cnum $(, $aoe)?);
+                with_capture!($TIM: $cnum);
+                $(with_advanced!($TIM: $aoe);)?
+            )?
 
             $(impl MasterTimer for $TIM {
                 type Mms = pac::$timbase::cr2::MMS_A;
                 fn master_mode(&mut self, mode: Self::Mms) {
                     self.cr2.modify(|_,w| w.mms().variant(mode));
                 }
+                fn master_mode_update(&mut self) {
+                    self.cr2.modify(|_,w| w.mms().update());
+                }
             })?
         )+
     }
 }
 
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+macro_rules! with_advanced {
+    ($TIM:ty: $aoe:ident) => {
+        impl Advanced for $TIM {
+            #[inline(always)]
+            fn set_dtg(&mut self, dtg: u8) {
+                #[allow(unused_unsafe)]
+                self.bdtr.modify(|_, w| unsafe { w.dtg().bits(dtg) });
+            }
+
+            #[inline(always)]
+            fn set_main_output(&mut self, moe: bool, aoe: bool) {
+                self.bdtr.modify(|_, w| w.aoe().bit(aoe).moe().bit(moe));
+            }
+
+            #[inline(always)]
+            fn set_off_states(&mut self, run: bool, idle: bool) {
+                self.bdtr.modify(|_, w| w.ossr().bit(run).ossi().bit(idle));
+            }
+
+            #[inline(always)]
+            fn set_break(&mut self, enable: bool, active_high: bool, aoe: bool) {
+                self.bdtr
+                    .modify(|_, w| w.bke().bit(enable).bkp().bit(active_high).aoe().bit(aoe));
+            }
+
+            #[inline(always)]
+            fn listen_break(&mut self, b: bool) {
+                self.dier.modify(|_, w| w.bie().bit(b));
+            }
+
+            #[inline(always)]
+            fn is_break_triggered(&self) -> bool {
+                self.sr.read().bif().bit_is_set()
+            }
+
+            #[inline(always)]
+            fn clear_break(&mut self) {
+                self.sr
+                    .write(|w| unsafe { w.bits(0xffff) }.bif().clear_bit());
+            }
+
+            #[inline(always)]
+            fn enable_complementary(c: u8, b: bool) {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                if c < 3 {
+                    unsafe {
+                        bb::write(&tim.ccer, c * 4 + 2, b);
+                    }
+                }
+            }
+
+            #[inline(always)]
+            fn set_polarity(c: u8, complementary: bool, active_low: bool) {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                if c < Self::CH_NUMBER {
+                    let bit = c * 4 + if complementary { 3 } else { 1 };
+                    unsafe {
+                        bb::write(&tim.ccer, bit, active_low);
+                    }
+                }
+            }
+        }
+    };
+}
+
+macro_rules! with_capture {
+    ($TIM:ty: CH4) => {
+        with_capture!(@impl $TIM, tim, channel, ccmr, {
+            if channel < 2 {
+                tim.ccmr1_input().modify(|r, w| unsafe {
+                    w.bits(r.bits() & !(0xff << (channel * 8)) | ccmr << (channel * 8))
+                });
+            } else if channel < 4 {
+                let channel = channel - 2;
+                tim.ccmr2_input().modify(|r, w| unsafe {
+                    w.bits(r.bits() & !(0xff << (channel * 8)) | ccmr << (channel * 8))
+                });
+            }
+        });
+    };
+    ($TIM:ty: $cnum:ident) => {
+        with_capture!(@impl $TIM, tim, channel, ccmr, {
+            if channel < Self::CH_NUMBER {
+                tim.ccmr1_input().modify(|r, w| unsafe {
+                    w.bits(r.bits() & !(0xff << (channel * 8)) | ccmr << (channel * 8))
+                });
+            }
+        });
+    };
+    (@impl $TIM:ty, $tim:ident, $channel:ident, $ccmr:ident, $configure:block) => {
+        impl WithCapture for $TIM {
+            #[inline(always)]
+            fn configure_capture(&mut self, $channel: u8, $ccmr: u8) {
+                let $ccmr = $ccmr as u32;
+                let $tim = self;
+                $configure
+            }
+
+            #[inline(always)]
+            fn set_capture_polarity(c: u8, falling: bool) {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                if c < Self::CH_NUMBER {
+                    unsafe { bb::write(&tim.ccer, c*4 + 1, falling); }
+                }
+            }
+
+            #[inline(always)]
+            fn is_overcaptured(c: u8) -> bool {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                tim.sr.read().bits() & (1 << (9 + c)) != 0
+            }
+
+            #[inline(always)]
+            fn clear_overcapture(c: u8) {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                tim.sr.write(|w| unsafe { w.bits(0xffff & !(1 << (9 + c))) });
+            }
+        }
+    };
+}
+
 macro_rules! with_pwm {
     ($TIM:ty: CH1) => {
         impl WithPwm for $TIM {
@@ -469,6 +689,18 @@ macro_rules! with_pwm {
                     unsafe { bb::write(&tim.ccer, c*4, b); }
                 }
             }
+
+            #[inline(always)]
+            fn cc_address(channel: u8) -> u32 {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                &tim.ccr[channel as usize] as *const _ as u32
+            }
+
+            #[inline(always)]
+            fn enable_update_dma(b: bool) {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                unsafe { bb::write(&tim.dier, 8, b); }
+            }
         }
     };
     ($TIM:ty: CH2) => {
@@ -521,6 +753,18 @@ macro_rules! with_pwm {
                     unsafe { bb::write(&tim.ccer, c*4, b); }
                 }
             }
+
+            #[inline(always)]
+            fn cc_address(channel: u8) -> u32 {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                &tim.ccr[channel as usize] as *const _ as u32
+            }
+
+            #[inline(always)]
+            fn enable_update_dma(b: bool) {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                unsafe { bb::write(&tim.dier, 8, b); }
+            }
         }
     };
     ($TIM:ty: CH4 $(, $aoe:ident)?) => {
@@ -574,6 +818,71 @@ macro_rules! with_pwm {
                     unsafe { bb::write(&tim.ccer, c*4, b); }
                 }
             }
+
+            #[inline(always)]
+            fn cc_address(channel: u8) -> u32 {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                &tim.ccr[channel as usize] as *const _ as u32
+            }
+
+            #[inline(always)]
+            fn enable_update_dma(b: bool) {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                unsafe { bb::write(&tim.dier, 8, b); }
+            }
+
+            #[inline(always)]
+            fn is_center_aligned() -> bool {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                tim.cr1.read().cms().bits() != 0
+            }
+
+            #[inline(always)]
+            fn enable_main_output(&mut self) {
+                $(let $aoe = self.bdtr.modify(|_, w| w.moe().set_bit());)?
+            }
+        }
+
+        impl CenterAligned for $TIM {
+            #[inline(always)]
+            fn set_cms(&mut self, cms: u8) {
+                self.cr1.modify(|_, w| w.cms().bits(cms));
+            }
+        }
+
+        impl WithDmaBurst for $TIM {
+            #[inline(always)]
+            fn set_dma_burst(dba: u8, dbl: u8) {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                tim.dcr.write(|w| unsafe { w.bits((dbl as u32) << 8 | dba as u32) });
+            }
+
+            #[inline(always)]
+            fn dmar_address() -> u32 {
+                let tim = unsafe { &*<$TIM>::ptr() };
+                &tim.dmar as *const _ as u32
+            }
+        }
+
+        impl SlaveTimer for $TIM {
+            #[inline(always)]
+            fn set_slave_mode(&mut self, ts: u8, sms: u8) {
+                self.smcr.modify(|r, w| unsafe {
+                    w.bits(r.bits() & !0b111_0111 | (ts as u32) << 4 | sms as u32)
+                });
+            }
+            #[inline(always)]
+            fn set_external_trigger(&mut self, ece: bool, inverted: bool, etps: u8, etf: u8) {
+                self.smcr.modify(|r, w| unsafe {
+                    w.bits(
+                        r.bits() & !0xff00
+                            | (inverted as u32) << 15
+                            | (ece as u32) << 14
+                            | (etps as u32) << 12
+                            | (etf as u32) << 8,
+                    )
+                });
+            }
         }
     }
 }
@@ -644,6 +953,85 @@ impl<TIM: Instance + MasterTimer> Timer<TIM> {
     }
 }
 
+/// Encodes a dead time of `ticks` of the timer clock into `DTG`, rounding up
+///
+/// The dead time is exact up to 127 ticks, by steps of 2, 8 then 16 ticks up to 1008 ticks.
+fn dead_time_dtg(ticks: u16) -> u8 {
+    match ticks {
+        0..=127 => ticks as u8,
+        128..=254 => 0b1000_0000 | ((ticks + 1) / 2 - 64) as u8,
+        255..=504 => 0b1100_0000 | ((ticks + 7) / 8 - 32) as u8,
+        _ => 0b1110_0000 | ((ticks.min(1008) + 15) / 16 - 32) as u8,
+    }
+}
+
+impl<TIM: Instance + Advanced> Timer<TIM> {
+    /// Inserts `ticks` of the timer clock between an output and its complementary output
+    /// switching, up to 1008 ticks
+    pub fn set_dead_time(&mut self, ticks: u16) {
+        self.tim.set_dtg(dead_time_dtg(ticks))
+    }
+
+    /// Enables the outputs (`MOE`)
+    pub fn enable_outputs(&mut self) {
+        self.tim.set_main_output(true, true)
+    }
+
+    /// Disables the outputs (`MOE`), until `enable_outputs`
+    pub fn disable_outputs(&mut self) {
+        self.tim.set_main_output(false, false)
+    }
+
+    /// Sets the off states of the outputs, driven inactive instead of released when disabled
+    /// while the outputs are enabled (`OSSR`) or disabled (`OSSI`)
+    pub fn set_off_states(&mut self, run: bool, idle: bool) {
+        self.tim.set_off_states(run, idle)
+    }
+
+    /// Enables the break input on `pin`, active at `polarity`, disabling the outputs (`MOE`)
+    /// in their off states
+    ///
+    /// With `automatic_output`, the outputs are enabled again on the first update event once
+    /// the break input is inactive, otherwise by `enable_outputs`.
+    pub fn enable_break<REMAP, PIN, MODE>(
+        &mut self,
+        _pin: PIN,
+        polarity: Polarity,
+        automatic_output: bool,
+    ) where
+        REMAP: Remap<Periph = TIM>,
+        PIN: BkinPin<REMAP> + crate::gpio::PinExt<Mode = crate::gpio::Input<MODE>>,
+    {
+        self.tim
+            .set_break(true, polarity == Polarity::ActiveHigh, automatic_output)
+    }
+
+    /// Disables the break input
+    pub fn disable_break(&mut self) {
+        self.tim.set_break(false, false, true)
+    }
+
+    /// Enables the break interrupt
+    pub fn listen_break(&mut self) {
+        self.tim.listen_break(true)
+    }
+
+    /// Disables the break interrupt
+    pub fn unlisten_break(&mut self) {
+        self.tim.listen_break(false)
+    }
+
+    /// Returns true if the break input has been active
+    pub fn is_break_triggered(&self) -> bool {
+        self.tim.is_break_triggered()
+    }
+
+    /// Clears the break flag, to be done in the interrupt handler
+    pub fn clear_break(&mut self) {
+        self.tim.clear_break()
+    }
+}
+
 /// Timer wrapper for fixed precision timers.
 ///
 /// Uses `fugit::TimerDurationU32` for most of operations
@@ -735,6 +1123,73 @@ impl<TIM: Instance + MasterTimer, const FREQ: u32> FTimer<TIM, FREQ> {
     }
 }
 
+impl<TIM: Instance + Advanced, const FREQ: u32> FTimer<TIM, FREQ> {
+    /// Inserts `ticks` of the timer clock between an output and its complementary output
+    /// switching, up to 1008 ticks
+    pub fn set_dead_time(&mut self, ticks: u16) {
+        self.tim.set_dtg(dead_time_dtg(ticks))
+    }
+
+    /// Enables the outputs (`MOE`)
+    pub fn enable_outputs(&mut self) {
+        self.tim.set_main_output(true, true)
+    }
+
+    /// Disables the outputs (`MOE`), until `enable_outputs`
+    pub fn disable_outputs(&mut self) {
+        self.tim.set_main_output(false, false)
+    }
+
+    /// Sets the off states of the outputs, driven inactive instead of released when disabled
+    /// while the outputs are enabled (`OSSR`) or disabled (`OSSI`)
+    pub fn set_off_states(&mut self, run: bool, idle: bool) {
+        self.tim.set_off_states(run, idle)
+    }
+
+    /// Enables the break input on `pin`, active at `polarity`, disabling the outputs (`MOE`)
+    /// in their off states
+    ///
+    /// With `automatic_output`, the outputs are enabled again on the first update event once
+    /// the break input is inactive, otherwise by `enable_outputs`.
+    pub fn enable_break<REMAP, PIN, MODE>(
+        &mut self,
+        _pin: PIN,
+        polarity: Polarity,
+        automatic_output: bool,
+    ) where
+        REMAP: Remap<Periph = TIM>,
+        PIN: BkinPin<REMAP> + crate::gpio::PinExt<Mode = crate::gpio::Input<MODE>>,
+    {
+        self.tim
+            .set_break(true, polarity == Polarity::ActiveHigh, automatic_output)
+    }
+
+    /// Disables the break input
+    pub fn disable_break(&mut self) {
+        self.tim.set_break(false, false, true)
+    }
+
+    /// Enables the break interrupt
+    pub fn listen_break(&mut self) {
+        self.tim.listen_break(true)
+    }
+
+    /// Disables the break interrupt
+    pub fn unlisten_break(&mut self) {
+        self.tim.listen_break(false)
+    }
+
+    /// Returns true if the break input has been active
+    pub fn is_break_triggered(&self) -> bool {
+        self.tim.is_break_triggered()
+    }
+
+    /// Clears the break flag, to be done in the interrupt handler
+    pub fn clear_break(&mut self) {
+        self.tim.clear_break()
+    }
+}
+
 #[inline(always)]
 const fn compute_arr_presc(freq: u32, clock: u32) -> (u16, u32) {
     let ticks = clock / freq;
@@ -788,25 +1243,62 @@ hal! {
     pac::TIM8: [Timer8, u16, dbg_tim8_stop, c: (CH4, _aoe), m: tim1,],
 }
 
-//TODO: restore these timers once stm32-rs has been updated
-/*
- *   dbg_tim(12-13)_stop fields missing from 103 xl in stm32-rs
- *   dbg_tim(9-10)_stop fields missing from 101 xl in stm32-rs
-#[cfg(any(
-    feature = "xl",
-    all(
-        feature = "stm32f100",
-        feature = "high",
-)))]
+update_dma!(
+    pac::TIM2: dma1::C2,
+    pac::TIM3: dma1::C3,
+);
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+update_dma!(
+    pac::TIM1: dma1::C5,
+);
+
+#[cfg(feature = "medium")]
+update_dma!(
+    pac::TIM4: dma1::C7,
+);
+
+#[cfg(all(feature = "stm32f100", not(feature = "high")))]
+update_dma!(
+    pac::TIM6: dma1::C3,
+    pac::TIM7: dma1::C4,
+);
+
+#[cfg(feature = "stm32f100")]
+update_dma!(
+    pac::TIM15: dma1::C5,
+    pac::TIM16: dma1::C6,
+    pac::TIM17: dma1::C7,
+);
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+update_dma!(
+    pac::TIM5: crate::dma::dma2::C2,
+);
+
+// DMA2 on the high-density value line, AFIO_MAPR2 TIM67_DAC_DMA_REMAP not being set
+#[cfg(any(feature = "high", feature = "connectivity"))]
+update_dma!(
+    pac::TIM6: crate::dma::dma2::C3,
+    pac::TIM7: crate::dma::dma2::C4,
+);
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+update_dma!(
+    pac::TIM8: crate::dma::dma2::C1,
+);
+
+// The stop fields of TIM9-TIM14 are missing from the PAC of the XL-density devices
+#[cfg(any(feature = "xl", all(feature = "stm32f100", feature = "high",)))]
 hal! {
-    TIM12: (tim12, dbg_tim12_stop),
-    TIM13: (tim13, dbg_tim13_stop),
-    TIM14: (tim14, dbg_tim14_stop),
+    pac::TIM12: [Timer12, u16, 25, c: (CH2),],
+    pac::TIM13: [Timer13, u16, 26, c: (CH1),],
+    pac::TIM14: [Timer14, u16, 27, c: (CH1),],
 }
+
 #[cfg(feature = "xl")]
 hal! {
-    TIM9: (tim9, dbg_tim9_stop),
-    TIM10: (tim10, dbg_tim10_stop),
-    TIM11: (tim11, dbg_tim11_stop),
+    pac::TIM9: [Timer9, u16, 28, c: (CH2),],
+    pac::TIM10: [Timer10, u16, 29, c: (CH1),],
+    pac::TIM11: [Timer11, u16, 30, c: (CH1),],
 }
-*/
@@ -0,0 +1,307 @@
+//! Input capture
+//!
+//! The counter of the timer runs freely at the tick frequency and its value is captured into
+//! the channel registers on the edges of the input pins, to timestamp events or measure periods.
+//!
+//! ```ignore
+//! let pa0 = gpioa.pa0.into_floating_input(&mut gpioa.crl);
+//! let mut capture = Timer::new(dp.TIM2, &clocks).input_capture::<Tim2NoRemap, _, _>(
+//!     pa0,
+//!     &mut afio.mapr,
+//!     1.MHz(),
+//!     CaptureConfig::default().edge(Edge::Falling).filter(4),
+//! );
+//!
+//! let first = block!(capture.read(Channel::C1)).unwrap();
+//! let second = block!(capture.read(Channel::C1)).unwrap();
+//! // period in microseconds
+//! let period = second.wrapping_sub(first);
+//! ```
+//!
+//! With `listen`, the capture triggers the timer interrupt, where the value is read.
+
+use core::marker::PhantomData;
+
+use crate::gpio::{self, Input};
+use crate::time::Hertz;
+
+use super::{
+    pins::sealed::Remap, CPin, Ch, Channel, Event, Instance, Timer, WithCapture, C1, C2, C3, C4,
+};
+
+/// Capture pins of a timer
+pub trait Pins<REMAP, P> {
+    const C1: bool = false;
+    const C2: bool = false;
+    const C3: bool = false;
+    const C4: bool = false;
+
+    fn check_used(c: Channel) -> Channel {
+        if (c == Channel::C1 && Self::C1)
+            || (c == Channel::C2 && Self::C2)
+            || (c == Channel::C3 && Self::C3)
+            || (c == Channel::C4 && Self::C4)
+        {
+            c
+        } else {
+            panic!("Unused channel")
+        }
+    }
+}
+
+macro_rules! pins_impl {
+    ( $( ( $($PINX:ident),+ ), ( $($ENCHX:ident),+ ); )+ ) => {
+        $(
+            #[allow(unused_parens)]
+            impl<TIM, REMAP, INMODE, $($PINX,)+> Pins<REMAP, ($(Ch<$ENCHX>),+)> for ($($PINX),+)
+            where
+                TIM: Instance + WithCapture,
+                REMAP: Remap<Periph = TIM>,
+                $($PINX: CPin<REMAP, $ENCHX> + gpio::PinExt<Mode=Input<INMODE>>,)+
+            {
+                $(const $ENCHX: bool = true;)+
+            }
+        )+
+    };
+}
+
+pins_impl!(
+    (P1, P2, P3, P4), (C1, C2, C3, C4);
+    (P2, P3, P4), (C2, C3, C4);
+    (P1, P3, P4), (C1, C3, C4);
+    (P1, P2, P4), (C1, C2, C4);
+    (P1, P2, P3), (C1, C2, C3);
+    (P3, P4), (C3, C4);
+    (P2, P4), (C2, C4);
+    (P2, P3), (C2, C3);
+    (P1, P4), (C1, C4);
+    (P1, P3), (C1, C3);
+    (P1, P2), (C1, C2);
+    (P1), (C1);
+    (P2), (C2);
+    (P3), (C3);
+    (P4), (C4);
+);
+
+/// Edge of the input triggering the capture
+///
+/// The timers of the STM32F1 capture on a single edge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// Number of input edges per capture, or per count of an external clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Prescaler {
+    Div1 = 0b00,
+    Div2 = 0b01,
+    Div4 = 0b10,
+    Div8 = 0b11,
+}
+
+/// Configuration of a capture channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureConfig {
+    pub edge: Edge,
+    /// Digital filter of the input, `ICxF` from 0 (no filter) to 15
+    pub filter: u8,
+    pub prescaler: Prescaler,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            edge: Edge::Rising,
+            filter: 0,
+            prescaler: Prescaler::Div1,
+        }
+    }
+}
+
+impl CaptureConfig {
+    pub fn edge(mut self, edge: Edge) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `filter` is above 15.
+    pub fn filter(mut self, filter: u8) -> Self {
+        assert!(filter < 16, "filter out of range");
+        self.filter = filter;
+        self
+    }
+
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+}
+
+/// Capture errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A capture has been overwritten before being read
+    Overcapture,
+}
+
+/// Timer capturing its counter on the edges of the `PINS`
+pub struct InputCapture<TIM, REMAP, P, PINS> {
+    timer: Timer<TIM>,
+    pins: PINS,
+    tick: Hertz,
+    _remap: PhantomData<(REMAP, P)>,
+}
+
+impl<TIM: Instance + WithCapture> Timer<TIM> {
+    /// Captures the counter, counting at `tick`, on the edges of `pins`, all the channels
+    /// configured with `config`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` is above the timer clock or too low for the prescaler.
+    pub fn input_capture<REMAP, P, PINS>(
+        mut self,
+        pins: PINS,
+        mapr: &mut REMAP::Mapr,
+        tick: Hertz,
+        config: CaptureConfig,
+    ) -> InputCapture<TIM, REMAP, P, PINS>
+    where
+        REMAP: Remap<Periph = TIM>,
+        PINS: Pins<REMAP, P>,
+    {
+        REMAP::remap(mapr);
+
+        let psc = self.clk.raw() / tick.raw() - 1;
+        let psc = u16::try_from(psc).expect("tick too low");
+        self.tim.set_prescaler(psc);
+        self.tim.set_auto_reload(TIM::max_auto_reload()).unwrap();
+        // Trigger update event to load the registers
+        self.tim.trigger_update();
+
+        let tick = Hertz::from_raw(self.clk.raw() / (psc as u32 + 1));
+        let mut capture = InputCapture {
+            timer: self,
+            pins,
+            tick,
+            _remap: PhantomData,
+        };
+        for (used, channel) in [
+            (PINS::C1, Channel::C1),
+            (PINS::C2, Channel::C2),
+            (PINS::C3, Channel::C3),
+            (PINS::C4, Channel::C4),
+        ] {
+            if used {
+                capture.set_config(channel, &config);
+            }
+        }
+        capture.timer.tim.enable_counter();
+        capture
+    }
+}
+
+impl<TIM, REMAP, P, PINS> InputCapture<TIM, REMAP, P, PINS>
+where
+    TIM: Instance + WithCapture,
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P>,
+{
+    /// Configures and enables the capture of `channel`
+    pub fn set_config(&mut self, channel: Channel, config: &CaptureConfig) {
+        let c = PINS::check_used(channel) as u8;
+        // The channel is configurable while disabled
+        TIM::enable_channel(c, false);
+        // CCxS = 01, ICx mapped on TIx
+        let ccmr = 0b01 | (config.prescaler as u8) << 2 | config.filter << 4;
+        self.timer.tim.configure_capture(c, ccmr);
+        TIM::set_capture_polarity(c, config.edge == Edge::Falling);
+        TIM::enable_channel(c, true);
+    }
+
+    /// Enables the capture of `channel`
+    pub fn enable(&mut self, channel: Channel) {
+        TIM::enable_channel(PINS::check_used(channel) as u8, true);
+    }
+
+    /// Disables the capture of `channel`
+    pub fn disable(&mut self, channel: Channel) {
+        TIM::enable_channel(PINS::check_used(channel) as u8, false);
+    }
+
+    /// Returns the value captured on `channel` since the last read
+    ///
+    /// Returns `Error::Overcapture` if captures have been lost meanwhile, the last one is
+    /// returned by `captured`.
+    pub fn read(&mut self, channel: Channel) -> nb::Result<u16, Error> {
+        let c = PINS::check_used(channel) as u8;
+        let flag = Event::from_bits_truncate(1 << (c + 1));
+        if !self.timer.tim.get_interrupt_flag().contains(flag) {
+            return Err(nb::Error::WouldBlock);
+        }
+        if TIM::is_overcaptured(c) {
+            TIM::clear_overcapture(c);
+            self.timer.tim.clear_interrupt_flag(flag);
+            return Err(nb::Error::Other(Error::Overcapture));
+        }
+        // Reading the value clears the capture flag
+        Ok(TIM::read_cc_value(c) as u16)
+    }
+
+    /// Returns the last value captured on `channel`
+    pub fn captured(&self, channel: Channel) -> u16 {
+        TIM::read_cc_value(PINS::check_used(channel) as u8) as u16
+    }
+
+    /// Returns true if captures on `channel` have been lost
+    pub fn is_overcaptured(&self, channel: Channel) -> bool {
+        TIM::is_overcaptured(PINS::check_used(channel) as u8)
+    }
+
+    /// Clears the overcapture flag of `channel`
+    pub fn clear_overcapture(&mut self, channel: Channel) {
+        TIM::clear_overcapture(PINS::check_used(channel) as u8);
+    }
+
+    /// Enables the timer interrupt on the captures of `channel`
+    pub fn listen(&mut self, channel: Channel) {
+        let c = PINS::check_used(channel) as u8;
+        self.timer
+            .tim
+            .listen_interrupt(Event::from_bits_truncate(1 << (c + 1)), true);
+    }
+
+    /// Disables the timer interrupt on the captures of `channel`
+    pub fn unlisten(&mut self, channel: Channel) {
+        let c = PINS::check_used(channel) as u8;
+        self.timer
+            .tim
+            .listen_interrupt(Event::from_bits_truncate(1 << (c + 1)), false);
+    }
+
+    /// Frequency of the counter, `tick` rounded to the prescaler
+    pub fn tick(&self) -> Hertz {
+        self.tick
+    }
+
+    /// Current value of the counter
+    pub fn now(&self) -> u16 {
+        let count: u32 = self.timer.tim.read_count().into();
+        count as u16
+    }
+
+    /// Stops the captures and returns the timer and the pins
+    pub fn release(mut self) -> (Timer<TIM>, PINS) {
+        for c in 0..4 {
+            TIM::enable_channel(c, false);
+        }
+        self.timer.tim.disable_counter();
+        (self.timer, self.pins)
+    }
+}
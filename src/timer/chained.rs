@@ -0,0 +1,145 @@
+//! 32-bit counter of two chained timers
+//!
+//! [`ChainedTimer`] clocks a slave timer with the update events of a master timer, the slave
+//! counting the upper 16 bits of a 32-bit counter at the frequency `FREQ` of the master.
+//!
+//! ```ignore
+//! let mut timer = dp.TIM2.counter_us(&clocks).release().chain(Timer::new(dp.TIM3, &clocks));
+//! timer.start();
+//! let start = timer.now();
+//! // ...
+//! let elapsed = timer.now().ticks().wrapping_sub(start.ticks());
+//!
+//! // 10 s period
+//! timer.start_period(10.secs());
+//! block!(timer.wait()).unwrap();
+//! ```
+//!
+//! A value captured on a channel of the master timer is extended to 32 bits with
+//! `extend_capture`, shortly after the capture.
+
+use fugit::{TimerDurationU32, TimerInstantU32};
+
+use super::{Error, FTimer, Instance, InternalTrigger, MasterTimer, SlaveMode, SlaveTimer, Timer};
+
+/// 32-bit counter counting at `FREQ`, of the `MASTER` timer for the lower 16 bits and of the
+/// `SLAVE` timer for the upper 16 bits
+pub struct ChainedTimer<MASTER, SLAVE, const FREQ: u32> {
+    master: FTimer<MASTER, FREQ>,
+    slave: Timer<SLAVE>,
+    period: Option<(u32, u32)>,
+}
+
+impl<MASTER: Instance + MasterTimer, const FREQ: u32> FTimer<MASTER, FREQ> {
+    /// Chains `slave`, clocked by the update events of the timer, into a 32-bit counter
+    pub fn chain<SLAVE>(mut self, mut slave: Timer<SLAVE>) -> ChainedTimer<MASTER, SLAVE, FREQ>
+    where
+        SLAVE: Instance + SlaveTimer + InternalTrigger<MASTER>,
+    {
+        self.tim.cr1_reset();
+        self.tim.set_auto_reload(MASTER::max_auto_reload()).unwrap();
+        // Trigger update event to load the registers
+        self.tim.trigger_update();
+        self.tim.master_mode_update();
+
+        slave.tim.cr1_reset();
+        slave.tim.set_prescaler(0);
+        slave.tim.set_auto_reload(SLAVE::max_auto_reload()).unwrap();
+        slave.tim.trigger_update();
+        slave.set_slave_mode::<MASTER>(SlaveMode::ExternalClock);
+
+        ChainedTimer {
+            master: self,
+            slave,
+            period: None,
+        }
+    }
+}
+
+impl<MASTER, SLAVE, const FREQ: u32> ChainedTimer<MASTER, SLAVE, FREQ>
+where
+    MASTER: Instance + MasterTimer,
+    SLAVE: Instance + SlaveTimer + InternalTrigger<MASTER>,
+{
+    /// Resets the counter to zero and starts it
+    pub fn start(&mut self) {
+        self.master.tim.disable_counter();
+        self.master.tim.reset_counter();
+        self.slave.tim.reset_counter();
+        self.slave.tim.enable_counter();
+        self.master.tim.enable_counter();
+    }
+
+    /// Stops the counter, `start` resets it
+    pub fn stop(&mut self) {
+        self.master.tim.disable_counter();
+    }
+
+    /// Value of the 32-bit counter
+    pub fn now(&self) -> TimerInstantU32<FREQ> {
+        let high: u32 = self.slave.tim.read_count().into();
+        let low: u32 = self.master.tim.read_count().into();
+        let high2: u32 = self.slave.tim.read_count().into();
+        // The lower half has wrapped between the reads of the upper half
+        let low = if high2 != high {
+            self.master.tim.read_count().into()
+        } else {
+            low
+        };
+        TimerInstantU32::from_ticks(high2 << 16 | low)
+    }
+
+    /// Extends to 32 bits a value captured on a channel of the master timer less than 2^16
+    /// ticks ago
+    pub fn extend_capture(&self, captured: u16) -> TimerInstantU32<FREQ> {
+        let now = self.now().ticks();
+        let mut high = now >> 16;
+        if captured as u32 > now & 0xffff {
+            high = high.wrapping_sub(1);
+        }
+        TimerInstantU32::from_ticks(high << 16 | captured as u32)
+    }
+
+    /// Starts a periodic timeout of `period`, from the current value of the counter
+    ///
+    /// The counter is started if needed, the timeout is polled with `wait`.
+    pub fn start_period(&mut self, period: TimerDurationU32<FREQ>) {
+        if !self.master.tim.is_counter_enabled() {
+            self.start();
+        }
+        self.period = Some((self.now().ticks(), period.ticks()));
+    }
+
+    /// Returns `Ok` once per elapsed period, the next period following without drift
+    pub fn wait(&mut self) -> nb::Result<(), Error> {
+        let (start, period) = self.period.ok_or(nb::Error::Other(Error::Disabled))?;
+        if self.now().ticks().wrapping_sub(start) >= period {
+            self.period = Some((start.wrapping_add(period), period));
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Cancels the periodic timeout, the counter keeps counting
+    pub fn cancel(&mut self) -> Result<(), Error> {
+        self.period.take().map(|_| ()).ok_or(Error::Disabled)
+    }
+
+    /// Busy waits for `duration`, up to 2^32 ticks
+    pub fn delay(&mut self, duration: TimerDurationU32<FREQ>) {
+        if !self.master.tim.is_counter_enabled() {
+            self.start();
+        }
+        let start = self.now().ticks();
+        while self.now().ticks().wrapping_sub(start) < duration.ticks() {}
+    }
+
+    /// Stops the counter and returns the timers
+    pub fn release(mut self) -> (FTimer<MASTER, FREQ>, Timer<SLAVE>) {
+        self.master.tim.cr1_reset();
+        self.slave.tim.cr1_reset();
+        self.slave.disable_slave_mode();
+        (self.master, self.slave)
+    }
+}
@@ -0,0 +1,235 @@
+//! External clock
+//!
+//! [`PulseCounter`] counts the edges of a pin instead of the internal clock, on the channel 1
+//! or 2 input in external clock mode 1, or on the external trigger input (ETR), with a
+//! prescaler, in external clock mode 2.
+//!
+//! ```ignore
+//! let pa0 = gpioa.pa0.into_floating_input(&mut gpioa.crl);
+//! let mut pulses = Timer::new(dp.TIM2, &clocks).pulse_counter_ti::<Tim2NoRemap, _, C1, _>(
+//!     pa0,
+//!     &mut afio.mapr,
+//!     ClockEdge::Rising,
+//!     4,
+//! );
+//! // Sampling the count each second gives the frequency in Hz
+//! let count = pulses.count();
+//!
+//! let pd2 = gpiod.pd2.into_floating_input(&mut gpiod.crl);
+//! let mut flow = Timer::new(dp.TIM3, &clocks).pulse_counter_etr::<Tim3NoRemap, _>(
+//!     pd2,
+//!     &mut afio.mapr,
+//!     EtrConfig::default().prescaler(Prescaler::Div8),
+//! );
+//! // Update flag and interrupt every 1000 counts, 8000 pulses
+//! flow.set_limit(1000);
+//! flow.listen();
+//! ```
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use crate::gpio::{self, Input};
+
+use super::capture::Prescaler;
+use super::{
+    pins::sealed::Remap, CPin, EtrPin, Event, Instance, SlaveTimer, Timer, WithCapture, C1, C2,
+};
+
+/// Edges of the channel 1 or 2 input counted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockEdge {
+    Rising,
+    Falling,
+    /// Both edges, on the channel 1 input only
+    Both,
+}
+
+/// Configuration of the external trigger input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EtrConfig {
+    /// Counts the falling edges instead of the rising edges
+    pub inverted: bool,
+    /// Division of the input, the edges at the output of the prescaler are below a quarter of
+    /// the timer clock
+    pub prescaler: Prescaler,
+    /// Digital filter of the input, `ETF` from 0 (no filter) to 15
+    pub filter: u8,
+}
+
+impl Default for EtrConfig {
+    fn default() -> Self {
+        Self {
+            inverted: false,
+            prescaler: Prescaler::Div1,
+            filter: 0,
+        }
+    }
+}
+
+impl EtrConfig {
+    pub fn inverted(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `filter` is above 15.
+    pub fn filter(mut self, filter: u8) -> Self {
+        assert!(filter < 16, "filter out of range");
+        self.filter = filter;
+        self
+    }
+}
+
+/// Timer counting the edges of `PIN`
+pub struct PulseCounter<TIM, REMAP, PIN> {
+    timer: Timer<TIM>,
+    pin: PIN,
+    _remap: PhantomData<REMAP>,
+}
+
+impl<TIM: Instance + WithCapture + SlaveTimer> Timer<TIM> {
+    /// Counts the `edge`s of `pin`, the input of channel `C`, 1 or 2, filtered with `ICxF`
+    /// `filter`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `C` is not the channel 1 or 2, if both edges are counted on the channel 2, or
+    /// if `filter` is above 15.
+    pub fn pulse_counter_ti<REMAP, PIN, const C: u8, INMODE>(
+        mut self,
+        pin: PIN,
+        mapr: &mut REMAP::Mapr,
+        edge: ClockEdge,
+        filter: u8,
+    ) -> PulseCounter<TIM, REMAP, PIN>
+    where
+        REMAP: Remap<Periph = TIM>,
+        PIN: CPin<REMAP, C> + gpio::PinExt<Mode = Input<INMODE>>,
+    {
+        assert!(C == C1 || C == C2, "external clock on channel 1 or 2 only");
+        assert!(
+            C == C1 || edge != ClockEdge::Both,
+            "both edges on channel 1 only"
+        );
+        assert!(filter < 16, "filter out of range");
+        REMAP::remap(mapr);
+
+        // CCxS = 01, ICx mapped on TIx
+        self.tim.configure_capture(C, 0b01 | filter << 4);
+        TIM::set_capture_polarity(C, edge == ClockEdge::Falling);
+        // TS = TI1F_ED, TI1FP1 or TI2FP2
+        let ts = match (C, edge) {
+            (_, ClockEdge::Both) => 0b100,
+            (C1, _) => 0b101,
+            _ => 0b110,
+        };
+        // SMS = external clock mode 1
+        self.tim.set_slave_mode(ts, 0b111);
+        PulseCounter::new(self, pin)
+    }
+
+    /// Counts the edges of `pin`, the external trigger input, configured with `config`
+    pub fn pulse_counter_etr<REMAP, PIN>(
+        mut self,
+        pin: PIN,
+        mapr: &mut REMAP::Mapr,
+        config: EtrConfig,
+    ) -> PulseCounter<TIM, REMAP, PIN>
+    where
+        REMAP: Remap<Periph = TIM>,
+        PIN: EtrPin<REMAP>,
+    {
+        REMAP::remap(mapr);
+
+        // ECE = external clock mode 2
+        self.tim
+            .set_external_trigger(true, config.inverted, config.prescaler as u8, config.filter);
+        PulseCounter::new(self, pin)
+    }
+}
+
+impl<TIM: Instance + WithCapture + SlaveTimer, REMAP, PIN> PulseCounter<TIM, REMAP, PIN> {
+    fn new(mut timer: Timer<TIM>, pin: PIN) -> Self {
+        timer.tim.set_prescaler(0);
+        timer.tim.set_auto_reload(TIM::max_auto_reload()).unwrap();
+        // Trigger update event to load the registers
+        timer.tim.trigger_update();
+        timer.tim.clear_interrupt_flag(Event::Update);
+        timer.tim.enable_counter();
+        Self {
+            timer,
+            pin,
+            _remap: PhantomData,
+        }
+    }
+
+    /// Number of edges counted
+    pub fn count(&self) -> u16 {
+        let count: u32 = self.timer.tim.read_count().into();
+        count as u16
+    }
+
+    /// Resets the count to zero
+    pub fn reset(&mut self) {
+        self.timer.tim.reset_counter();
+    }
+
+    /// Resets the count to zero and sets the update flag on each `limit` counts
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero.
+    pub fn set_limit(&mut self, limit: u16) {
+        assert!(limit > 0, "zero limit");
+        self.timer.tim.set_auto_reload(limit as u32 - 1).unwrap();
+        self.timer.tim.trigger_update();
+        self.timer.tim.clear_interrupt_flag(Event::Update);
+    }
+
+    /// Returns `Ok` once each time the limit has been reached
+    pub fn wait(&mut self) -> nb::Result<(), Infallible> {
+        if self.timer.tim.get_interrupt_flag().contains(Event::Update) {
+            self.timer.tim.clear_interrupt_flag(Event::Update);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Enables the timer interrupt when the limit is reached
+    pub fn listen(&mut self) {
+        self.timer.tim.listen_interrupt(Event::Update, true);
+    }
+
+    /// Disables the timer interrupt when the limit is reached
+    pub fn unlisten(&mut self) {
+        self.timer.tim.listen_interrupt(Event::Update, false);
+    }
+
+    /// Stops counting, the count is kept
+    pub fn stop(&mut self) {
+        self.timer.tim.disable_counter();
+    }
+
+    /// Resumes counting
+    pub fn resume(&mut self) {
+        self.timer.tim.enable_counter();
+    }
+
+    /// Stops counting and returns the timer and the pin
+    pub fn release(mut self) -> (Timer<TIM>, PIN) {
+        self.timer.tim.cr1_reset();
+        self.timer.tim.listen_interrupt(Event::Update, false);
+        self.timer.tim.set_slave_mode(0, 0);
+        self.timer.tim.set_external_trigger(false, false, 0, 0);
+        (self.timer, self.pin)
+    }
+}
@@ -0,0 +1,150 @@
+//! One-pulse mode
+//!
+//! [`OnePulse`] outputs a single pulse of programmable width on a channel, after a delay from
+//! its start by software or by an edge on the channel 1 or 2 input.
+//!
+//! ```ignore
+//! let pa1 = gpioa.pa1.into_alternate_push_pull(&mut gpioa.crl);
+//! let mut pulse = dp.TIM2.counter_us(&clocks).one_pulse::<Tim2NoRemap, _, C2, _>(
+//!     pa1,
+//!     &mut afio.mapr,
+//!     10.micros(),
+//!     50.micros(),
+//! );
+//! // 50 µs pulse 10 µs after each rising edge on PA0
+//! pulse.with_trigger::<_, _, C1>(gpioa.pa0.into_floating_input(&mut gpioa.crl), Edge::Rising);
+//! ```
+//!
+//! An edge received during the pulse is ignored, the pulses are not retriggerable.
+
+use core::marker::PhantomData;
+
+use crate::gpio::{self, Alternate, Input};
+use fugit::TimerDurationU32;
+
+use super::capture::Edge;
+use super::{
+    pins::sealed::Remap, CPin, Channel, FTimer, Instance, Ocm, SlaveTimer, WithCapture, WithPwm,
+    C1, C2, C3,
+};
+
+/// Timer outputting single pulses on channel `C`
+pub struct OnePulse<TIM, REMAP, const C: u8, const FREQ: u32> {
+    timer: FTimer<TIM, FREQ>,
+    _remap: PhantomData<REMAP>,
+}
+
+impl<TIM: Instance + WithPwm + WithCapture, const FREQ: u32> FTimer<TIM, FREQ> {
+    /// Outputs a pulse of `width` on `pin`, `delay` after each start
+    ///
+    /// # Panics
+    ///
+    /// Panics if `delay` and `width` do not fit in the auto-reload register.
+    pub fn one_pulse<REMAP, PIN, const C: u8, OUTMODE>(
+        mut self,
+        _pin: PIN,
+        mapr: &mut REMAP::Mapr,
+        delay: TimerDurationU32<FREQ>,
+        width: TimerDurationU32<FREQ>,
+    ) -> OnePulse<TIM, REMAP, C, FREQ>
+    where
+        REMAP: Remap<Periph = TIM>,
+        PIN: CPin<REMAP, C> + gpio::PinExt<Mode = Alternate<OUTMODE>>,
+    {
+        REMAP::remap(mapr);
+
+        self.tim.cr1_reset();
+        // PWM mode 2 is active from the delay to the end of the period
+        self.tim
+            .preload_output_channel_in_mode(channel(C), Ocm::PwmMode2);
+        self.tim.set_one_pulse_mode(true);
+
+        let mut pulse = OnePulse {
+            timer: self,
+            _remap: PhantomData,
+        };
+        pulse.set_pulse(delay, width);
+        TIM::enable_channel(C, true);
+        pulse.timer.tim.enable_main_output();
+        pulse
+    }
+}
+
+fn channel(c: u8) -> Channel {
+    match c {
+        C1 => Channel::C1,
+        C2 => Channel::C2,
+        C3 => Channel::C3,
+        _ => Channel::C4,
+    }
+}
+
+impl<TIM, REMAP, const C: u8, const FREQ: u32> OnePulse<TIM, REMAP, C, FREQ>
+where
+    TIM: Instance + WithPwm + WithCapture,
+    REMAP: Remap<Periph = TIM>,
+{
+    /// Sets the delay and the width of the next pulses
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is zero or if `delay` and `width` do not fit in the auto-reload
+    /// register.
+    pub fn set_pulse(&mut self, delay: TimerDurationU32<FREQ>, width: TimerDurationU32<FREQ>) {
+        assert!(width.ticks() > 0, "zero pulse width");
+        TIM::set_cc_value(C, delay.ticks());
+        self.timer
+            .tim
+            .set_auto_reload(delay.ticks() + width.ticks() - 1)
+            .expect("pulse too long");
+        // Trigger update event to load the registers
+        self.timer.tim.trigger_update();
+    }
+
+    /// Starts a pulse
+    pub fn trigger(&mut self) {
+        self.timer.tim.enable_counter();
+    }
+
+    /// Returns true until the end of the pulse
+    pub fn is_running(&self) -> bool {
+        self.timer.tim.is_counter_enabled()
+    }
+
+    /// Disables the output and returns the timer, the trigger is to be disabled first
+    pub fn release(mut self) -> FTimer<TIM, FREQ> {
+        TIM::enable_channel(C, false);
+        self.timer.tim.cr1_reset();
+        self.timer
+    }
+}
+
+impl<TIM, REMAP, const C: u8, const FREQ: u32> OnePulse<TIM, REMAP, C, FREQ>
+where
+    TIM: Instance + WithPwm + WithCapture + SlaveTimer,
+    REMAP: Remap<Periph = TIM>,
+{
+    /// Starts a pulse on each `edge` of `pin`, the input of channel `T`, 1 or 2
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is not the channel 1 or 2, or is the output channel.
+    pub fn with_trigger<PIN, INMODE, const T: u8>(&mut self, _pin: PIN, edge: Edge)
+    where
+        PIN: CPin<REMAP, T> + gpio::PinExt<Mode = Input<INMODE>>,
+    {
+        assert!(T == C1 || T == C2, "trigger on channel 1 or 2 only");
+        assert!(T != C, "trigger on the output channel");
+        // CCxS = 01, ICx mapped on TIx
+        self.timer.tim.configure_capture(T, 0b01);
+        TIM::set_capture_polarity(T, edge == Edge::Falling);
+        // TS = TI1FP1 or TI2FP2, SMS = trigger mode
+        let ts = if T == C1 { 0b101 } else { 0b110 };
+        self.timer.tim.set_slave_mode(ts, 0b110);
+    }
+
+    /// Stops starting the pulses on the input edges
+    pub fn disable_trigger(&mut self) {
+        self.timer.tim.set_slave_mode(0, 0);
+    }
+}
@@ -1,6 +1,12 @@
 use crate::pac;
 
 pub trait CPin<REMAP, const C: u8> {}
+/// Complementary output pin of channel `C`
+pub trait NPin<REMAP, const C: u8> {}
+/// Break input pin
+pub trait BkinPin<REMAP> {}
+/// External trigger input pin
+pub trait EtrPin<REMAP> {}
 pub struct Ch<const C: u8>;
 pub const C1: u8 = 0;
 pub const C2: u8 = 1;
@@ -10,9 +16,11 @@ pub const C4: u8 = 3;
 pub(crate) mod sealed {
     pub trait Remap {
         type Periph;
+        /// AFIO register of the remap, `MAPR` or `MAPR2`
+        type Mapr;
         const REMAP: u8;
 
-        fn remap(mapr: &mut crate::afio::MAPR);
+        fn remap(mapr: &mut Self::Mapr);
     }
 }
 
@@ -22,6 +30,7 @@ macro_rules! remap {
             pub struct $name;
             impl sealed::Remap for $name {
                 type Periph = $TIMX;
+                type Mapr = crate::afio::MAPR;
                 const REMAP: u8 = $state;
 
                 fn remap(mapr: &mut crate::afio::MAPR) {
@@ -36,6 +45,47 @@ macro_rules! remap {
     }
 }
 
+// Remaps of the timers with one or two channels, in MAPR2
+#[cfg(any(feature = "xl", all(feature = "stm32f100", feature = "high",)))]
+macro_rules! remap2 {
+    ($($name:ident: ($TIMX:ty, $state:literal, $P1:ident $(, $P2:ident)?, { $remapex:expr }),)+) => {
+        $(
+            pub struct $name;
+            impl sealed::Remap for $name {
+                type Periph = $TIMX;
+                type Mapr = crate::afio::MAPR2;
+                const REMAP: u8 = $state;
+
+                fn remap(mapr: &mut crate::afio::MAPR2) {
+                    mapr.mapr2().modify($remapex);
+                }
+            }
+            impl<MODE> CPin<$name, 0> for crate::gpio::$P1<MODE> {}
+            $(impl<MODE> CPin<$name, 1> for crate::gpio::$P2<MODE> {})?
+        )+
+    }
+}
+
+macro_rules! etr_pins {
+    ($($name:ident: $ETR:ident,)+) => {
+        $(
+            impl<MODE> EtrPin<$name> for crate::gpio::$ETR<MODE> {}
+        )+
+    }
+}
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+macro_rules! advanced_pins {
+    ($($name:ident: ($P1:ident, $P2:ident, $P3:ident), bkin: $BK:ident,)+) => {
+        $(
+            impl<MODE> NPin<$name, 0> for crate::gpio::$P1<MODE> {}
+            impl<MODE> NPin<$name, 1> for crate::gpio::$P2<MODE> {}
+            impl<MODE> NPin<$name, 2> for crate::gpio::$P3<MODE> {}
+            impl<MODE> BkinPin<$name> for crate::gpio::$BK<MODE> {}
+        )+
+    }
+}
+
 #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
 remap!(
     Tim1NoRemap: (pac::TIM1, 0b00, PA8, PA9, PA10, PA11, {|_, w| unsafe { w.tim1_remap().bits(Self::REMAP)}}),
@@ -43,6 +93,33 @@ remap!(
     Tim1FullRemap: (pac::TIM1, 0b11, PE9, PE11, PE13, PE14, {|_, w| unsafe { w.tim1_remap().bits(Self::REMAP)}}),
 );
 
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+etr_pins!(
+    Tim1NoRemap: PA12,
+    Tim1FullRemap: PE7,
+);
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+advanced_pins!(
+    Tim1NoRemap: (PB13, PB14, PB15), bkin: PB12,
+    Tim1FullRemap: (PE8, PE10, PE12), bkin: PE15,
+);
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+remap!(
+    Tim8NoRemap: (pac::TIM8, 0b00, PC6, PC7, PC8, PC9, {|_, w| w}),
+);
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+etr_pins!(
+    Tim8NoRemap: PA0,
+);
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+advanced_pins!(
+    Tim8NoRemap: (PA7, PB0, PB1), bkin: PA6,
+);
+
 remap!(
     Tim2NoRemap: (pac::TIM2, 0b00, PA0, PA1, PA2, PA3, {|_, w| unsafe { w.tim2_remap().bits(Self::REMAP)}}),
     Tim2PartialRemap1: (pac::TIM2, 0b01, PA15, PB3, PA2, PA3, {|_, w| unsafe { w.tim2_remap().bits(Self::REMAP)}}),
@@ -54,8 +131,57 @@ remap!(
     Tim3FullRemap: (pac::TIM3, 0b11, PC6, PC7, PC8, PC9, {|_, w| unsafe { w.tim3_remap().bits(Self::REMAP)}}),
 );
 
+etr_pins!(
+    Tim2NoRemap: PA0,
+    Tim2PartialRemap1: PA15,
+    Tim2PartialRemap2: PA0,
+    Tim2FullRemap: PA15,
+
+    Tim3NoRemap: PD2,
+    Tim3PartialRemap: PD2,
+    Tim3FullRemap: PD2,
+);
+
 #[cfg(feature = "medium")]
 remap!(
     Tim4NoRemap: (pac::TIM4, 0b00, PB6, PB7, PB8, PB9, {|_, w| w.tim4_remap().bit(Self::REMAP == 1)}),
     Tim4Remap: (pac::TIM4, 0b01, PD12, PD13, PD14, PD15, {|_, w| w.tim4_remap().bit(Self::REMAP == 1)}),
 );
+
+#[cfg(feature = "medium")]
+etr_pins!(
+    Tim4NoRemap: PE0,
+    Tim4Remap: PE0,
+);
+
+#[cfg(feature = "xl")]
+remap2!(
+    Tim9NoRemap: (pac::TIM9, 0b0, PA2, PA3, {|_, w| w.tim9_remap().bit(Self::REMAP == 1)}),
+    Tim9Remap: (pac::TIM9, 0b1, PE5, PE6, {|_, w| w.tim9_remap().bit(Self::REMAP == 1)}),
+
+    Tim10NoRemap: (pac::TIM10, 0b0, PB8, {|_, w| w.tim10_remap().bit(Self::REMAP == 1)}),
+    Tim10Remap: (pac::TIM10, 0b1, PF6, {|_, w| w.tim10_remap().bit(Self::REMAP == 1)}),
+
+    Tim11NoRemap: (pac::TIM11, 0b0, PB9, {|_, w| w.tim11_remap().bit(Self::REMAP == 1)}),
+    Tim11Remap: (pac::TIM11, 0b1, PF7, {|_, w| w.tim11_remap().bit(Self::REMAP == 1)}),
+
+    Tim12NoRemap: (pac::TIM12, 0b0, PB14, PB15, {|_, w| w}),
+
+    Tim13NoRemap: (pac::TIM13, 0b0, PA6, {|_, w| w.tim13_remap().bit(Self::REMAP == 1)}),
+    Tim13Remap: (pac::TIM13, 0b1, PF8, {|_, w| w.tim13_remap().bit(Self::REMAP == 1)}),
+
+    Tim14NoRemap: (pac::TIM14, 0b0, PA7, {|_, w| w.tim14_remap().bit(Self::REMAP == 1)}),
+    Tim14Remap: (pac::TIM14, 0b1, PF9, {|_, w| w.tim14_remap().bit(Self::REMAP == 1)}),
+);
+
+#[cfg(all(feature = "stm32f100", feature = "high",))]
+remap2!(
+    Tim12NoRemap: (pac::TIM12, 0b0, PC4, PC5, {|_, w| w.tim12_remap().bit(Self::REMAP == 1)}),
+    Tim12Remap: (pac::TIM12, 0b1, PB12, PB13, {|_, w| w.tim12_remap().bit(Self::REMAP == 1)}),
+
+    Tim13NoRemap: (pac::TIM13, 0b0, PC8, {|_, w| w.tim13_remap().bit(Self::REMAP == 1)}),
+    Tim13Remap: (pac::TIM13, 0b1, PB0, {|_, w| w.tim13_remap().bit(Self::REMAP == 1)}),
+
+    Tim14NoRemap: (pac::TIM14, 0b0, PC9, {|_, w| w.tim14_remap().bit(Self::REMAP == 1)}),
+    Tim14Remap: (pac::TIM14, 0b1, PB1, {|_, w| w.tim14_remap().bit(Self::REMAP == 1)}),
+);
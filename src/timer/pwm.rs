@@ -51,15 +51,78 @@
     // PWM outputs are disabled by default
     c0.enable()
   ```
+
+  ## Alignment and modes
+
+  The timers with 4 channels also count in center-aligned modes, used in motor control, and
+  each channel is set in PWM mode 1 or 2:
+
+  ```rust
+  pwm.set_alignment(Alignment::Center1);
+  pwm.set_mode(Channel::C2, PwmMode::Mode2);
+  ```
+
+  ## Complementary outputs
+
+  The channels 1 to 3 of the advanced timers (`TIM1`, `TIM8`) drive complementary outputs
+  for half bridges, with a dead time between the switching of the two outputs:
+
+  ```rust
+  let mut pwm = dp.TIM1.pwm_hz::<Tim1NoRemap, _, _>(pins, &mut afio.mapr, 20.kHz(), &clocks);
+  // 500 ns at 72 MHz
+  pwm.set_dead_time(36);
+  let (c1, ..) = pwm.split();
+  let mut c1 = c1.with_complementary(gpiob.pb13.into_alternate_push_pull(&mut gpiob.crh));
+  c1.enable();
+  ```
+
+  The break input disables the outputs in hardware, for instance on an overcurrent:
+
+  ```rust
+  let bkin = gpiob.pb12.into_pull_down_input(&mut gpiob.crh);
+  pwm.set_off_states(true, true);
+  pwm.enable_break(bkin, Polarity::ActiveHigh, false);
+  pwm.listen_break();
+  // the outputs stay disabled after a break until `enable_outputs`
+  ```
+
+  ## Waveform tables
+
+  A channel of a timer with an update DMA request takes a new duty cycle from a constant
+  table on each update event, the table stays in flash:
+
+  ```rust
+  static RAMP: [u16; 8] = [0, 100, 200, 300, 400, 500, 600, 700];
+
+  let pwm_dma = c1.with_dma(dma1.2);
+  let transfer = pwm_dma.write_circular(&RAMP);
+  ```
+
+  With a DMA burst, several consecutive channels take their duty cycles on each update event,
+  from a table of interleaved duty cycles:
+
+  ```rust
+  // duty cycles of C1, C2 and C3 for each period
+  static SEQUENCE: [u16; 6] = [100, 200, 300, 400, 500, 600];
+
+  let burst = c1.with_burst_dma(dma1.2, 3);
+  let transfer = burst.write_table(&SEQUENCE);
+  ```
 */
 
-use crate::afio::MAPR;
 use crate::gpio::{self, Alternate};
 
-use super::{compute_arr_presc, Channel, FTimer, Instance, Ocm, Timer, WithPwm};
+use super::{
+    compute_arr_presc, Advanced, CenterAligned, Channel, FTimer, Instance, Ocm, Timer, UpdateDma,
+    WithDmaBurst, WithPwm,
+};
+use crate::dma::{DmaChannel, TableWord, Transfer, TransferChannel, TransferPayload, R};
 use crate::rcc::Clocks;
 use core::marker::PhantomData;
+use core::mem;
 use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{compiler_fence, Ordering};
+use embedded_dma::ReadBuffer;
 use fugit::{HertzU32 as Hertz, TimerDurationU32};
 
 pub trait Pins<REMAP, P> {
@@ -84,7 +147,39 @@ pub trait Pins<REMAP, P> {
     fn split() -> Self::Channels;
 }
 
-pub use super::{pins::sealed::Remap, CPin, Ch, C1, C2, C3, C4};
+pub use super::{pins::sealed::Remap, CPin, Ch, NPin, C1, C2, C3, C4};
+
+/// PWM mode of a channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PwmMode {
+    /// Active while the counter is below the duty cycle
+    Mode1,
+    /// Inactive while the counter is below the duty cycle
+    Mode2,
+}
+
+impl From<PwmMode> for Ocm {
+    fn from(mode: PwmMode) -> Self {
+        match mode {
+            PwmMode::Mode1 => Ocm::PwmMode1,
+            PwmMode::Mode2 => Ocm::PwmMode2,
+        }
+    }
+}
+
+/// Counting mode of the PWM timer
+///
+/// In the center-aligned modes, the counter counts up to the auto-reload value then down, the
+/// pulses are centered in the period and the flags of the channels set when counting down
+/// (`Center1`), up (`Center2`) or both (`Center3`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Alignment {
+    Edge = 0b00,
+    Center1 = 0b01,
+    Center2 = 0b10,
+    Center3 = 0b11,
+}
 
 pub struct PwmChannel<TIM, const C: u8> {
     pub(super) _tim: PhantomData<TIM>,
@@ -135,7 +230,7 @@ where
     fn pwm<REMAP, P, PINS, const FREQ: u32>(
         self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         time: TimerDurationU32<FREQ>,
         clocks: &Clocks,
     ) -> Pwm<Self, REMAP, P, PINS, FREQ>
@@ -146,7 +241,7 @@ where
     fn pwm_hz<REMAP, P, PINS>(
         self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         freq: Hertz,
         clocks: &Clocks,
     ) -> PwmHz<Self, REMAP, P, PINS>
@@ -157,7 +252,7 @@ where
     fn pwm_us<REMAP, P, PINS>(
         self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         time: TimerDurationU32<1_000_000>,
         clocks: &Clocks,
     ) -> Pwm<Self, REMAP, P, PINS, 1_000_000>
@@ -176,7 +271,7 @@ where
     fn pwm<REMAP, P, PINS, const FREQ: u32>(
         self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         time: TimerDurationU32<FREQ>,
         clocks: &Clocks,
     ) -> Pwm<TIM, REMAP, P, PINS, FREQ>
@@ -190,7 +285,7 @@ where
     fn pwm_hz<REMAP, P, PINS>(
         self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         time: Hertz,
         clocks: &Clocks,
     ) -> PwmHz<TIM, REMAP, P, PINS>
@@ -292,7 +387,7 @@ impl<TIM: Instance + WithPwm> Timer<TIM> {
     pub fn pwm_hz<REMAP, P, PINS>(
         mut self,
         _pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         freq: Hertz,
     ) -> PwmHz<TIM, REMAP, P, PINS>
     where
@@ -372,15 +467,49 @@ where
         let arr = TIM::read_auto_reload();
 
         // Length in ms of an internal clock pulse
-        clk / ((psc + 1) * (arr + 1))
+        if TIM::is_center_aligned() {
+            clk / ((psc + 1) * arr * 2)
+        } else {
+            clk / ((psc + 1) * (arr + 1))
+        }
     }
 
     pub fn set_period(&mut self, period: Hertz) {
         let clk = self.clk;
 
-        let (psc, arr) = compute_arr_presc(period.raw(), clk.raw());
-        self.tim.set_prescaler(psc);
-        self.tim.set_auto_reload(arr).unwrap();
+        if TIM::is_center_aligned() {
+            let (psc, arr) = compute_arr_presc(period.raw() * 2, clk.raw());
+            self.tim.set_prescaler(psc);
+            self.tim.set_auto_reload(arr + 1).unwrap();
+        } else {
+            let (psc, arr) = compute_arr_presc(period.raw(), clk.raw());
+            self.tim.set_prescaler(psc);
+            self.tim.set_auto_reload(arr).unwrap();
+        }
+    }
+
+    /// Sets the PWM mode of `channel`
+    pub fn set_mode(&mut self, channel: Channel, mode: PwmMode) {
+        self.tim
+            .preload_output_channel_in_mode(PINS::check_used(channel), mode.into());
+    }
+}
+
+impl<TIM, REMAP, P, PINS> PwmHz<TIM, REMAP, P, PINS>
+where
+    TIM: Instance + WithPwm + CenterAligned,
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P>,
+{
+    /// Sets the counting mode, keeping the period
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        let period = self.get_period();
+        // The counting mode is not to be changed while counting
+        self.tim.disable_counter();
+        self.tim.set_cms(alignment as u8);
+        self.set_period(period);
+        self.tim.trigger_update();
+        self.tim.enable_counter();
     }
 }
 
@@ -438,7 +567,7 @@ impl<TIM: Instance + WithPwm, const FREQ: u32> FTimer<TIM, FREQ> {
     pub fn pwm<REMAP, P, PINS>(
         mut self,
         _pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         time: TimerDurationU32<FREQ>,
     ) -> Pwm<TIM, REMAP, P, PINS, FREQ>
     where
@@ -511,10 +640,353 @@ where
     }
 
     pub fn get_period(&self) -> TimerDurationU32<FREQ> {
-        TimerDurationU32::from_ticks(TIM::read_auto_reload() + 1)
+        if TIM::is_center_aligned() {
+            TimerDurationU32::from_ticks(TIM::read_auto_reload() * 2)
+        } else {
+            TimerDurationU32::from_ticks(TIM::read_auto_reload() + 1)
+        }
     }
 
     pub fn set_period(&mut self, period: TimerDurationU32<FREQ>) {
-        self.tim.set_auto_reload(period.ticks() - 1).unwrap();
+        if TIM::is_center_aligned() {
+            self.tim.set_auto_reload(period.ticks() / 2).unwrap();
+        } else {
+            self.tim.set_auto_reload(period.ticks() - 1).unwrap();
+        }
+    }
+
+    /// Sets the PWM mode of `channel`
+    pub fn set_mode(&mut self, channel: Channel, mode: PwmMode) {
+        self.tim
+            .preload_output_channel_in_mode(PINS::check_used(channel), mode.into());
+    }
+}
+
+impl<TIM, REMAP, P, PINS, const FREQ: u32> Pwm<TIM, REMAP, P, PINS, FREQ>
+where
+    TIM: Instance + WithPwm + CenterAligned,
+    REMAP: Remap<Periph = TIM>,
+    PINS: Pins<REMAP, P>,
+{
+    /// Sets the counting mode, keeping the period
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        let period = self.get_period();
+        // The counting mode is not to be changed while counting
+        self.tim.disable_counter();
+        self.tim.set_cms(alignment as u8);
+        self.set_period(period);
+        self.tim.trigger_update();
+        self.tim.enable_counter();
+    }
+}
+
+/// Active level of an output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// PWM channel of an advanced timer driving its complementary output too
+///
+/// The dead time set on the timer is inserted between the switching of the two outputs.
+pub struct ComplementaryPwmChannel<TIM, const C: u8, NPIN> {
+    channel: PwmChannel<TIM, C>,
+    pin: NPIN,
+}
+
+impl<TIM: Instance + Advanced, const C: u8> PwmChannel<TIM, C> {
+    /// Drives the complementary output of the channel on `pin`
+    pub fn with_complementary<REMAP, NPIN, OUTMODE>(
+        self,
+        pin: NPIN,
+    ) -> ComplementaryPwmChannel<TIM, C, NPIN>
+    where
+        REMAP: Remap<Periph = TIM>,
+        NPIN: NPin<REMAP, C> + gpio::PinExt<Mode = Alternate<OUTMODE>>,
+    {
+        ComplementaryPwmChannel { channel: self, pin }
+    }
+}
+
+impl<TIM: Instance + Advanced, const C: u8, NPIN> ComplementaryPwmChannel<TIM, C, NPIN> {
+    /// Disables both outputs
+    #[inline]
+    pub fn disable(&mut self) {
+        TIM::enable_channel(C, false);
+        TIM::enable_complementary(C, false);
+    }
+
+    /// Enables both outputs
+    #[inline]
+    pub fn enable(&mut self) {
+        TIM::enable_channel(C, true);
+        TIM::enable_complementary(C, true);
+    }
+
+    #[inline]
+    pub fn get_duty(&self) -> u16 {
+        self.channel.get_duty()
+    }
+
+    /// If `0` returned means max_duty is 2^16
+    #[inline]
+    pub fn get_max_duty(&self) -> u16 {
+        self.channel.get_max_duty()
+    }
+
+    #[inline]
+    pub fn set_duty(&mut self, duty: u16) {
+        self.channel.set_duty(duty)
+    }
+
+    /// Sets the active level of the output
+    pub fn set_polarity(&mut self, polarity: Polarity) {
+        TIM::set_polarity(C, false, polarity == Polarity::ActiveLow);
+    }
+
+    /// Sets the active level of the complementary output
+    pub fn set_complementary_polarity(&mut self, polarity: Polarity) {
+        TIM::set_polarity(C, true, polarity == Polarity::ActiveLow);
+    }
+
+    /// Disables the complementary output and returns the channel and its pin
+    pub fn release(self) -> (PwmChannel<TIM, C>, NPIN) {
+        TIM::enable_complementary(C, false);
+        (self.channel, self.pin)
+    }
+}
+
+/// PWM channel taking its duty cycles from a table with DMA
+pub struct PwmDma<TIM: UpdateDma, const C: u8> {
+    pwm: PwmChannel<TIM, C>,
+    channel: TIM::Channel,
+}
+
+impl<TIM: Instance + WithPwm + UpdateDma, const C: u8> PwmChannel<TIM, C> {
+    /// Writes a duty cycle with `channel` on each update event of the timer
+    pub fn with_dma(self, channel: TIM::Channel) -> PwmDma<TIM, C> {
+        TIM::enable_update_dma(true);
+        PwmDma { pwm: self, channel }
+    }
+}
+
+impl<TIM: Instance + WithPwm + UpdateDma, const C: u8> PwmDma<TIM, C> {
+    /// Returns the PWM channel and the DMA channel
+    pub fn release(self) -> (PwmChannel<TIM, C>, TIM::Channel) {
+        TIM::enable_update_dma(false);
+        (self.pwm, self.channel)
+    }
+
+    /// Writes the duty cycles of `table` once, one on each update event
+    ///
+    /// The table is usually a `&'static` slice in flash, or a `&'static mut` buffer.
+    pub fn write_table<B>(mut self, table: B) -> Transfer<R, B, Self>
+    where
+        B: ReadBuffer,
+        B::Word: TableWord,
+    {
+        self.set_table(&table, false);
+        self.start();
+        Transfer::r(table, self)
+    }
+
+    /// Writes the duty cycles of `table` in a loop, one on each update event
+    pub fn write_circular<B>(mut self, table: B) -> Transfer<R, B, Self>
+    where
+        B: ReadBuffer,
+        B::Word: TableWord,
+    {
+        self.set_table(&table, true);
+        self.start();
+        Transfer::r(table, self)
+    }
+
+    fn set_table<B>(&mut self, table: &B, circular: bool)
+    where
+        B: ReadBuffer,
+        B::Word: TableWord,
+    {
+        // NOTE(unsafe) the buffer is moved into the transfer
+        let (ptr, len) = unsafe { table.read_buffer() };
+        self.channel.stop();
+        let ch = self.channel.ch();
+        ch.par.write(|w| unsafe { w.pa().bits(TIM::cc_address(C)) });
+        ch.cr.modify(|_, w| {
+            // 8-bit words are zero extended to the 16-bit compare register
+            match mem::size_of::<B::Word>() {
+                1 => w.msize().bits8(),
+                _ => w.msize().bits16(),
+            };
+            w.psize()
+                .bits16()
+                .pinc()
+                .clear_bit()
+                .mem2mem()
+                .clear_bit()
+                .circ()
+                .bit(circular)
+                // read from memory
+                .dir()
+                .set_bit()
+        });
+        self.channel.set_memory_address(ptr as u32, true);
+        self.channel.set_transfer_length(len);
+        compiler_fence(Ordering::Release);
+    }
+}
+
+impl<TIM: Instance + WithPwm + UpdateDma, const C: u8> TransferPayload for PwmDma<TIM, C> {
+    fn start(&mut self) {
+        self.channel.start();
+    }
+
+    fn stop(&mut self) {
+        self.channel.stop();
+    }
+}
+
+impl<TIM: Instance + WithPwm + UpdateDma, const C: u8> TransferChannel for PwmDma<TIM, C> {
+    type Channel = TIM::Channel;
+
+    fn channel(&self) -> &TIM::Channel {
+        &self.channel
+    }
+
+    fn channel_mut(&mut self) -> &mut TIM::Channel {
+        &mut self.channel
+    }
+}
+
+/// PWM channels from `C` taking their duty cycles from a table with DMA bursts
+pub struct PwmBurst<TIM: UpdateDma, const C: u8> {
+    pwm: PwmChannel<TIM, C>,
+    channel: TIM::Channel,
+    count: u8,
+}
+
+impl<TIM: Instance + WithDmaBurst + UpdateDma, const C: u8> PwmChannel<TIM, C> {
+    /// Writes the duty cycles of `count` channels from this one with `channel` on each update
+    /// event of the timer
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is zero or if the channels are beyond the last channel.
+    pub fn with_burst_dma(self, channel: TIM::Channel, count: u8) -> PwmBurst<TIM, C> {
+        assert!(
+            count > 0 && C + count <= TIM::CH_NUMBER,
+            "invalid channel count"
+        );
+        // DBA = offset of CCRx in words, DBL = number of transfers - 1
+        TIM::set_dma_burst(13 + C, count - 1);
+        TIM::enable_update_dma(true);
+        PwmBurst {
+            pwm: self,
+            channel,
+            count,
+        }
+    }
+}
+
+impl<TIM: Instance + WithDmaBurst + UpdateDma, const C: u8> PwmBurst<TIM, C> {
+    /// Returns the first PWM channel and the DMA channel
+    pub fn release(self) -> (PwmChannel<TIM, C>, TIM::Channel) {
+        TIM::enable_update_dma(false);
+        TIM::set_dma_burst(0, 0);
+        (self.pwm, self.channel)
+    }
+
+    /// Writes the duty cycles of `table` once, the duty cycles of all the channels on each
+    /// update event
+    ///
+    /// The table is usually a `&'static` slice in flash, or a `&'static mut` buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `table` is not a multiple of the number of channels.
+    pub fn write_table<B>(mut self, table: B) -> Transfer<R, B, Self>
+    where
+        B: ReadBuffer,
+        B::Word: TableWord,
+    {
+        self.set_table(&table, false);
+        self.start();
+        Transfer::r(table, self)
+    }
+
+    /// Writes the duty cycles of `table` in a loop, the duty cycles of all the channels on each
+    /// update event
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `table` is not a multiple of the number of channels.
+    pub fn write_circular<B>(mut self, table: B) -> Transfer<R, B, Self>
+    where
+        B: ReadBuffer,
+        B::Word: TableWord,
+    {
+        self.set_table(&table, true);
+        self.start();
+        Transfer::r(table, self)
+    }
+
+    fn set_table<B>(&mut self, table: &B, circular: bool)
+    where
+        B: ReadBuffer,
+        B::Word: TableWord,
+    {
+        // NOTE(unsafe) the buffer is moved into the transfer
+        let (ptr, len) = unsafe { table.read_buffer() };
+        assert!(
+            len % self.count as usize == 0,
+            "table length not a multiple of the channel count"
+        );
+        self.channel.stop();
+        let ch = self.channel.ch();
+        ch.par
+            .write(|w| unsafe { w.pa().bits(TIM::dmar_address()) });
+        ch.cr.modify(|_, w| {
+            // 8-bit words are zero extended to the 16-bit compare registers
+            match mem::size_of::<B::Word>() {
+                1 => w.msize().bits8(),
+                _ => w.msize().bits16(),
+            };
+            w.psize()
+                .bits16()
+                .pinc()
+                .clear_bit()
+                .mem2mem()
+                .clear_bit()
+                .circ()
+                .bit(circular)
+                // read from memory
+                .dir()
+                .set_bit()
+        });
+        self.channel.set_memory_address(ptr as u32, true);
+        self.channel.set_transfer_length(len);
+        compiler_fence(Ordering::Release);
+    }
+}
+
+impl<TIM: Instance + WithDmaBurst + UpdateDma, const C: u8> TransferPayload for PwmBurst<TIM, C> {
+    fn start(&mut self) {
+        self.channel.start();
+    }
+
+    fn stop(&mut self) {
+        self.channel.stop();
+    }
+}
+
+impl<TIM: Instance + WithDmaBurst + UpdateDma, const C: u8> TransferChannel for PwmBurst<TIM, C> {
+    type Channel = TIM::Channel;
+
+    fn channel(&self) -> &TIM::Channel {
+        &self.channel
+    }
+
+    fn channel_mut(&mut self) -> &mut TIM::Channel {
+        &mut self.channel
     }
 }
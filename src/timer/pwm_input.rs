@@ -1,8 +1,33 @@
 //! This module allows Timer peripherals to be configured as pwm input.
 //! In this mode, the timer sample a squared signal to find it's frequency and duty cycle.
+//!
+//! `auto_range` adjusts the prescaler to the period of the signal, for signals of unknown or
+//! low frequency.
+//!
+//! With DMA, the captures of each cycle are stored into a buffer:
+//!
+//! ```ignore
+//! static mut CYCLES: [u16; 64] = [0; 64];
+//!
+//! let pwm_input = Timer::new(dp.TIM3, &clocks).pwm_input(
+//!     pins,
+//!     &mut afio.mapr,
+//!     &mut dbg,
+//!     Configuration::Frequency(1.kHz()),
+//! );
+//! let tick = pwm_input.tick(&clocks);
+//! let transfer = pwm_input.with_dma(dma1.6).read(unsafe { &mut CYCLES });
+//! let (cycles, pwm_input_dma) = transfer.wait();
+//! // CCR1 then CCR2 of each of the 32 cycles
+//! for cycle in cycles.chunks(2) {
+//!     let frequency = tick / (cycle[0] as u32 + 1);
+//!     let duty = (cycle[1], cycle[0]);
+//! }
+//! ```
 
 use core::marker::PhantomData;
 use core::mem;
+use core::sync::atomic::{compiler_fence, Ordering};
 
 use crate::pac::DBGMCU as DBG;
 #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
@@ -11,11 +36,14 @@ use crate::pac::TIM1;
 use crate::pac::TIM4;
 use crate::pac::{TIM2, TIM3};
 
-use crate::afio::MAPR;
+use crate::dma::{dma1, DmaChannel, Transfer, TransferChannel, TransferPayload, W};
 use crate::gpio::{self, Input};
 use crate::rcc::{BusTimerClock, Clocks};
 use crate::time::Hertz;
 use crate::timer::Timer;
+use embedded_dma::WriteBuffer;
+
+use super::{Instance, WithDmaBurst};
 
 pub trait Pins<REMAP> {}
 
@@ -36,6 +64,41 @@ pub struct PwmInput<TIM, REMAP, PINS> {
     _pins: PhantomData<PINS>,
 }
 
+/// Timer with a DMA request on the captures of the channel 1
+///
+/// `Channel` is the DMA channel serving the request.
+pub trait CaptureDma: Instance {
+    type Channel: DmaChannel;
+}
+
+/// PWM input storing the captures of each cycle with DMA
+pub struct PwmInputDma<TIM: CaptureDma, REMAP, PINS> {
+    input: PwmInput<TIM, REMAP, PINS>,
+    channel: TIM::Channel,
+}
+
+impl<TIM: CaptureDma, REMAP, PINS> TransferPayload for PwmInputDma<TIM, REMAP, PINS> {
+    fn start(&mut self) {
+        self.channel.start();
+    }
+
+    fn stop(&mut self) {
+        self.channel.stop();
+    }
+}
+
+impl<TIM: CaptureDma, REMAP, PINS> TransferChannel for PwmInputDma<TIM, REMAP, PINS> {
+    type Channel = TIM::Channel;
+
+    fn channel(&self) -> &TIM::Channel {
+        &self.channel
+    }
+
+    fn channel_mut(&mut self) -> &mut TIM::Channel {
+        &mut self.channel
+    }
+}
+
 /// How the data is read from the timer
 pub enum ReadMode {
     /// Return the latest captured data
@@ -84,7 +147,7 @@ impl Timer<TIM1> {
     pub fn pwm_input<REMAP, PINS>(
         mut self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         dbg: &mut DBG,
         mode: Configuration,
     ) -> PwmInput<TIM1, REMAP, PINS>
@@ -103,7 +166,7 @@ impl Timer<TIM2> {
     pub fn pwm_input<REMAP, PINS>(
         mut self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         dbg: &mut DBG,
         mode: Configuration,
     ) -> PwmInput<TIM2, REMAP, PINS>
@@ -122,7 +185,7 @@ impl Timer<TIM3> {
     pub fn pwm_input<REMAP, PINS>(
         mut self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         dbg: &mut DBG,
         mode: Configuration,
     ) -> PwmInput<TIM3, REMAP, PINS>
@@ -142,7 +205,7 @@ impl Timer<TIM4> {
     pub fn pwm_input<REMAP, PINS>(
         mut self,
         pins: PINS,
-        mapr: &mut MAPR,
+        mapr: &mut REMAP::Mapr,
         dbg: &mut DBG,
         mode: Configuration,
     ) -> PwmInput<TIM4, REMAP, PINS>
@@ -167,8 +230,12 @@ fn compute_arr_presc(freq: u32, clock: u32) -> (u16, u16) {
     (core::cmp::max(1, arr as u16), presc as u16)
 }
 macro_rules! hal {
-    ($($TIMX:ident: ($timX:ident),)+) => {
+    ($($TIMX:ident: ($timX:ident, $CX:ident),)+) => {
         $(
+            impl CaptureDma for $TIMX {
+                type Channel = dma1::$CX;
+            }
+
             fn $timX<REMAP, PINS>(
                 tim: $TIMX,
                 _pins: PINS,
@@ -292,6 +359,107 @@ macro_rules! hal {
                     unsafe { (*$TIMX::ptr()).sr.write(|w| w.uif().clear_bit().cc1if().clear_bit().cc1of().clear_bit())};
                     while unsafe { (*$TIMX::ptr()).sr.read().cc1if().bit_is_clear()} {}
                 }
+
+                /// Frequency of the counter, the period of the signal is `CCR1 + 1` ticks
+                pub fn tick(&self, clocks: &Clocks) -> Hertz {
+                    let presc = unsafe { (*$TIMX::ptr()).psc.read().bits() };
+                    <$TIMX>::timer_clock(clocks) / (presc + 1)
+                }
+
+                /// Adjusts the prescaler to the period of the signal, returns true if it has
+                /// been changed
+                ///
+                /// To be called after each capture or periodically, the prescaler is doubled
+                /// when the period of the signal overflows the counter, and halved when it is
+                /// below a quarter of the counter range. The auto-reload register is set to its
+                /// maximum. After a change, the next capture is measured with the previous
+                /// prescaler and is to be discarded.
+                pub fn auto_range(&mut self) -> bool {
+                    let tim = unsafe { &*$TIMX::ptr() };
+                    tim.arr.write(|w| w.arr().bits(u16::MAX));
+                    // Only the overflows set the update flag, not the resets by the input
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+
+                    let presc = tim.psc.read().psc().bits();
+                    let sr = tim.sr.read();
+                    let new = if sr.uif().bit_is_set() {
+                        tim.sr.write(|w| unsafe { w.bits(0xffff) }.uif().clear_bit());
+                        presc.saturating_mul(2).saturating_add(1)
+                    } else if presc > 0
+                        && sr.cc1if().bit_is_set()
+                        && tim.ccr1().read().bits() < 0x4000
+                    {
+                        presc / 2
+                    } else {
+                        presc
+                    };
+                    if new == presc {
+                        return false;
+                    }
+                    // Loaded on the next reset by the input
+                    tim.psc.write(|w| w.psc().bits(new));
+                    true
+                }
+
+                /// Stores the captures of each cycle with `channel`
+                pub fn with_dma(self, channel: dma1::$CX) -> PwmInputDma<$TIMX, REMAP, PINS> {
+                    // DMA burst of CCR1 and CCR2 on each capture of the channel 1
+                    <$TIMX as WithDmaBurst>::set_dma_burst(13, 1);
+                    unsafe { (*$TIMX::ptr()).dier.modify(|_, w| w.cc1de().set_bit()) };
+                    PwmInputDma { input: self, channel }
+                }
+            }
+
+            impl<REMAP, PINS> PwmInputDma<$TIMX, REMAP, PINS>
+            where
+                REMAP: Remap<Periph = $TIMX>,
+                PINS: Pins<REMAP>,
+            {
+                /// Returns the PWM input and the DMA channel
+                pub fn release(self) -> (PwmInput<$TIMX, REMAP, PINS>, dma1::$CX) {
+                    unsafe { (*$TIMX::ptr()).dier.modify(|_, w| w.cc1de().clear_bit()) };
+                    <$TIMX as WithDmaBurst>::set_dma_burst(0, 0);
+                    (self.input, self.channel)
+                }
+
+                /// Stores the captures of `buffer.len() / 2` cycles, `CCR1` then `CCR2` for
+                /// each cycle, in the format of `read_duty`
+                ///
+                /// # Panics
+                ///
+                /// Panics if the length of `buffer` is odd.
+                pub fn read<B>(mut self, mut buffer: B) -> Transfer<W, B, Self>
+                where
+                    B: WriteBuffer<Word = u16>,
+                {
+                    // NOTE(unsafe) the buffer is moved into the transfer
+                    let (ptr, len) = unsafe { buffer.write_buffer() };
+                    assert!(len % 2 == 0, "odd buffer length");
+                    self.channel.stop();
+                    let ch = self.channel.ch();
+                    ch.par.write(|w| unsafe { w.pa().bits(<$TIMX as WithDmaBurst>::dmar_address()) });
+                    ch.cr.modify(|_, w| {
+                        w.msize()
+                            .bits16()
+                            .psize()
+                            .bits16()
+                            .pinc()
+                            .clear_bit()
+                            .mem2mem()
+                            .clear_bit()
+                            .circ()
+                            .clear_bit()
+                            // read from peripheral
+                            .dir()
+                            .clear_bit()
+                    });
+                    self.channel.set_memory_address(ptr as u32, true);
+                    self.channel.set_transfer_length(len);
+                    compiler_fence(Ordering::Release);
+
+                    self.start();
+                    Transfer::w(buffer, self)
+                }
             }
         )+
     }
@@ -299,15 +467,15 @@ macro_rules! hal {
 
 #[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
 hal! {
-    TIM1: (tim1),
+    TIM1: (tim1, C2),
 }
 
 hal! {
-    TIM2: (tim2),
-    TIM3: (tim3),
+    TIM2: (tim2, C5),
+    TIM3: (tim3, C6),
 }
 
 #[cfg(feature = "medium")]
 hal! {
-    TIM4: (tim4),
+    TIM4: (tim4, C1),
 }
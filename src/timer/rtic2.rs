@@ -0,0 +1,266 @@
+//! RTIC 2 monotonics
+//!
+//! The monotonics of `TIM2`, `TIM3`, `TIM4`, `TIM5` and `SysTick` implement the `Monotonic` trait
+//! of `rtic-time`, for the scheduling of RTIC 2 and the `delay` of async tasks. The 16-bit
+//! timers are extended to 63 bits by counting their half periods, their counter never wraps.
+//!
+//! ```ignore
+//! type Mono = Tim2Mono<1_000_000>;
+//!
+//! #[init]
+//! fn init(cx: init::Context) -> (Shared, Local) {
+//!     // ...
+//!     Mono::start(cx.device.TIM2, &clocks);
+//! }
+//!
+//! #[task(binds = TIM2, priority = 3)]
+//! fn tim2(_: tim2::Context) {
+//!     unsafe { Mono::on_interrupt() };
+//! }
+//!
+//! #[task]
+//! async fn blink(_: blink::Context) {
+//!     loop {
+//!         // ...
+//!         Mono::delay(500.millis()).await;
+//!     }
+//! }
+//! ```
+//!
+//! The interrupt of the timer is to be bound to a task of the highest priority calling
+//! `on_interrupt`, it must not be delayed by more than half a period of the 16-bit counter.
+//! `rtic-time` requires a `critical-section` implementation, e.g. the
+//! `critical-section-single-core` feature of `cortex-m`.
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cortex_m::interrupt::{self, Mutex};
+use cortex_m::peripheral::{syst::SystClkSource, NVIC, SCB, SYST};
+use rtic_time::half_period_counter::calculate_now;
+use rtic_time::monotonic::TimerQueueBasedMonotonic;
+use rtic_time::timer_queue::{TimerQueue, TimerQueueBackend};
+
+use super::FTimer;
+use crate::pac::{self, Interrupt};
+use crate::rcc::Clocks;
+
+macro_rules! mono {
+    ($($TIM:ident: ($Mono:ident, $Backend:ident, $COUNTER:ident, $QUEUE:ident),)+) => {
+        $(
+            static $COUNTER: AtomicU32 = AtomicU32::new(0);
+            static $QUEUE: TimerQueue<$Backend> = TimerQueue::new();
+
+            #[doc = concat!("Timer queue backend of `", stringify!($TIM), "`")]
+            pub struct $Backend;
+
+            impl $Backend {
+                fn tim() -> &'static pac::tim2::RegisterBlock {
+                    unsafe { &*pac::$TIM::ptr() }
+                }
+            }
+
+            impl TimerQueueBackend for $Backend {
+                type Ticks = u64;
+
+                fn now() -> u64 {
+                    calculate_now(
+                        || $COUNTER.load(Ordering::Relaxed),
+                        || Self::tim().cnt.read().cnt().bits(),
+                    )
+                }
+
+                fn set_compare(instant: u64) {
+                    let now = Self::now();
+                    // The compare value of a later instant is reached on an overflow of the
+                    // counter, after which the timer queue sets it again
+                    let ccr = if instant.wrapping_sub(now) <= u16::MAX as u64 {
+                        instant as u16
+                    } else {
+                        0
+                    };
+                    Self::tim().ccr1().write(|w| w.ccr().bits(ccr));
+                }
+
+                fn clear_compare_flag() {
+                    Self::tim().sr.write(|w| unsafe { w.bits(0xffff) }.cc1if().clear_bit());
+                }
+
+                fn pend_interrupt() {
+                    NVIC::pend(Interrupt::$TIM);
+                }
+
+                fn on_interrupt() {
+                    let tim = Self::tim();
+                    let sr = tim.sr.read();
+                    // The counter is even during the first half period, odd during the second
+                    if sr.uif().bit_is_set() {
+                        tim.sr.write(|w| unsafe { w.bits(0xffff) }.uif().clear_bit());
+                        let prev = $COUNTER.fetch_add(1, Ordering::Relaxed);
+                        assert!(prev % 2 == 1, "monotonic interrupt delayed");
+                    }
+                    if sr.cc2if().bit_is_set() {
+                        tim.sr.write(|w| unsafe { w.bits(0xffff) }.cc2if().clear_bit());
+                        let prev = $COUNTER.fetch_add(1, Ordering::Relaxed);
+                        assert!(prev % 2 == 0, "monotonic interrupt delayed");
+                    }
+                }
+
+                fn enable_timer() {
+                    Self::tim().dier.modify(|_, w| w.cc1ie().set_bit());
+                }
+
+                fn disable_timer() {
+                    Self::tim().dier.modify(|_, w| w.cc1ie().clear_bit());
+                }
+
+                fn timer_queue() -> &'static TimerQueue<Self> {
+                    &$QUEUE
+                }
+            }
+
+            #[doc = concat!("Monotonic of `", stringify!($TIM), "` counting at `FREQ`")]
+            pub struct $Mono<const FREQ: u32>;
+
+            impl<const FREQ: u32> $Mono<FREQ> {
+                /// Starts the monotonic and enables the interrupt of the timer
+                ///
+                /// # Panics
+                ///
+                /// Panics if the timer clock is not a multiple of `FREQ`.
+                pub fn start(tim: pac::$TIM, clocks: &Clocks) {
+                    // Enables the timer and sets the prescaler
+                    let timer = FTimer::<pac::$TIM, FREQ>::new(tim, clocks);
+                    let tim = &timer.tim;
+                    tim.arr.write(|w| w.arr().bits(u16::MAX));
+                    // Trigger update event to load the registers
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.sr.write(|w| unsafe { w.bits(0) });
+                    // Half period interrupt
+                    tim.ccr2().write(|w| w.ccr().bits(0x8000));
+                    tim.dier.write(|w| w.uie().set_bit().cc2ie().set_bit());
+                    $COUNTER.store(0, Ordering::SeqCst);
+                    tim.cr1.write(|w| w.cen().set_bit());
+
+                    $QUEUE.initialize($Backend);
+                    unsafe { NVIC::unmask(Interrupt::$TIM) };
+                }
+
+                /// Handles the interrupt of the timer, to be called from its interrupt handler
+                ///
+                /// # Safety
+                ///
+                /// Only to be called from the interrupt handler of the timer.
+                #[inline]
+                pub unsafe fn on_interrupt() {
+                    $QUEUE.on_monotonic_interrupt();
+                }
+            }
+
+            impl<const FREQ: u32> TimerQueueBasedMonotonic for $Mono<FREQ> {
+                type Backend = $Backend;
+                type Instant = fugit::Instant<u64, 1, FREQ>;
+                type Duration = fugit::Duration<u64, 1, FREQ>;
+            }
+        )+
+    };
+}
+
+mono!(
+    TIM2: (Tim2Mono, Tim2Backend, TIM2_HALF_PERIODS, TIM2_QUEUE),
+    TIM3: (Tim3Mono, Tim3Backend, TIM3_HALF_PERIODS, TIM3_QUEUE),
+);
+
+#[cfg(feature = "medium")]
+mono!(
+    TIM4: (Tim4Mono, Tim4Backend, TIM4_HALF_PERIODS, TIM4_QUEUE),
+);
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+mono!(
+    TIM5: (Tim5Mono, Tim5Backend, TIM5_HALF_PERIODS, TIM5_QUEUE),
+);
+
+static SYST_TICKS: Mutex<Cell<u64>> = Mutex::new(Cell::new(0));
+static SYST_QUEUE: TimerQueue<SysTickBackend> = TimerQueue::new();
+
+/// Timer queue backend of `SysTick`
+pub struct SysTickBackend;
+
+impl SysTickBackend {
+    fn syst() -> &'static cortex_m::peripheral::syst::RegisterBlock {
+        unsafe { &*SYST::PTR }
+    }
+}
+
+impl TimerQueueBackend for SysTickBackend {
+    type Ticks = u64;
+
+    fn now() -> u64 {
+        interrupt::free(|cs| {
+            let ticks = SYST_TICKS.borrow(cs);
+            // COUNTFLAG is cleared by the read, the tick is counted once
+            if Self::syst().csr.read() & (1 << 16) != 0 {
+                ticks.set(ticks.get() + 1);
+            }
+            ticks.get()
+        })
+    }
+
+    // The interrupt is on each tick, the timer queue is checked on each tick
+    fn set_compare(_: u64) {}
+
+    fn clear_compare_flag() {}
+
+    fn pend_interrupt() {
+        SCB::set_pendst();
+    }
+
+    fn on_interrupt() {
+        Self::now();
+    }
+
+    fn timer_queue() -> &'static TimerQueue<Self> {
+        &SYST_QUEUE
+    }
+}
+
+/// Monotonic of `SysTick`, interrupting on each tick at `TICK_HZ`
+pub struct SysTickMono<const TICK_HZ: u32>;
+
+impl<const TICK_HZ: u32> SysTickMono<TICK_HZ> {
+    /// Starts the monotonic, clocked by the core clock, and enables its interrupt
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tick period is above 2^24 core clock periods.
+    pub fn start(mut syst: SYST, clocks: &Clocks) {
+        let reload = clocks.sysclk().raw() / TICK_HZ - 1;
+        assert!(reload < 1 << 24, "tick too long");
+        syst.disable_counter();
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(reload);
+        syst.clear_current();
+        interrupt::free(|cs| SYST_TICKS.borrow(cs).set(0));
+        syst.enable_interrupt();
+        syst.enable_counter();
+
+        SYST_QUEUE.initialize(SysTickBackend);
+    }
+
+    /// Handles the interrupt of `SysTick`, to be called from its exception handler
+    ///
+    /// # Safety
+    ///
+    /// Only to be called from the exception handler of `SysTick`.
+    #[inline]
+    pub unsafe fn on_interrupt() {
+        SYST_QUEUE.on_monotonic_interrupt();
+    }
+}
+
+impl<const TICK_HZ: u32> TimerQueueBasedMonotonic for SysTickMono<TICK_HZ> {
+    type Backend = SysTickBackend;
+    type Instant = fugit::Instant<u64, 1, TICK_HZ>;
+    type Duration = fugit::Duration<u64, 1, TICK_HZ>;
+}
@@ -0,0 +1,144 @@
+//! Timer synchronization
+//!
+//! A master timer outputs a trigger (TRGO), selected by `set_master_mode`, to the slave timers
+//! connected to it, which are reset, gated, started or clocked by it with `set_slave_mode`.
+//! [`InternalTrigger`] gives the valid connections of the device.
+//!
+//! ```ignore
+//! // TIM3 starts on the update event of TIM1
+//! let mut tim1 = Timer::new(dp.TIM1, &clocks);
+//! tim1.set_master_mode(pac::tim1::cr2::MMS_A::Update);
+//! let mut tim3 = Timer::new(dp.TIM3, &clocks);
+//! tim3.set_slave_mode::<pac::TIM1>(SlaveMode::Trigger);
+//! ```
+
+use crate::pac;
+
+use super::{FTimer, Instance, SlaveTimer, Timer};
+
+/// Action of the trigger of the master timer on a slave timer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SlaveMode {
+    /// The rising edge of the trigger resets the counter
+    Reset = 0b100,
+    /// The counter counts while the trigger is high
+    Gated = 0b101,
+    /// The rising edge of the trigger starts the counter
+    Trigger = 0b110,
+    /// The rising edges of the trigger clock the counter
+    ExternalClock = 0b111,
+}
+
+/// Internal trigger input of the timer connected to the trigger output of `MASTER`
+pub trait InternalTrigger<MASTER> {
+    /// Selection of the internal trigger, `ITRx`
+    const ITR: u8;
+}
+
+macro_rules! internal_triggers {
+    ($($SLAVE:ty: $MASTER:ty => $itr:literal,)+) => {
+        $(
+            impl InternalTrigger<$MASTER> for $SLAVE {
+                const ITR: u8 = $itr;
+            }
+        )+
+    };
+}
+
+internal_triggers!(
+    pac::TIM2: pac::TIM3 => 2,
+    pac::TIM3: pac::TIM2 => 1,
+);
+
+#[cfg(any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",))]
+internal_triggers!(
+    pac::TIM1: pac::TIM2 => 1,
+    pac::TIM1: pac::TIM3 => 2,
+    pac::TIM2: pac::TIM1 => 0,
+    pac::TIM3: pac::TIM1 => 0,
+);
+
+#[cfg(feature = "medium")]
+internal_triggers!(
+    pac::TIM2: pac::TIM4 => 3,
+    pac::TIM3: pac::TIM4 => 3,
+    pac::TIM4: pac::TIM2 => 1,
+    pac::TIM4: pac::TIM3 => 2,
+);
+
+#[cfg(all(
+    feature = "medium",
+    any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",)
+))]
+internal_triggers!(
+    pac::TIM1: pac::TIM4 => 3,
+    pac::TIM4: pac::TIM1 => 0,
+);
+
+#[cfg(any(feature = "high", feature = "connectivity"))]
+internal_triggers!(
+    pac::TIM5: pac::TIM2 => 0,
+    pac::TIM5: pac::TIM3 => 1,
+    pac::TIM5: pac::TIM4 => 2,
+);
+
+// ITR2 of TIM3 is TIM15 on the value line
+#[cfg(all(
+    not(feature = "stm32f100"),
+    any(feature = "high", feature = "connectivity")
+))]
+internal_triggers!(
+    pac::TIM3: pac::TIM5 => 2,
+);
+
+#[cfg(all(
+    any(feature = "high", feature = "connectivity"),
+    any(feature = "stm32f100", feature = "stm32f103", feature = "connectivity",)
+))]
+internal_triggers!(
+    pac::TIM1: pac::TIM5 => 0,
+);
+
+#[cfg(all(feature = "stm32f103", feature = "high",))]
+internal_triggers!(
+    pac::TIM2: pac::TIM8 => 1,
+    pac::TIM4: pac::TIM8 => 3,
+    pac::TIM5: pac::TIM8 => 3,
+    pac::TIM8: pac::TIM1 => 0,
+    pac::TIM8: pac::TIM2 => 1,
+    pac::TIM8: pac::TIM4 => 2,
+    pac::TIM8: pac::TIM5 => 3,
+);
+
+impl<TIM: Instance + SlaveTimer> Timer<TIM> {
+    /// Synchronizes the timer in `mode` on the trigger output of `MASTER`
+    pub fn set_slave_mode<MASTER>(&mut self, mode: SlaveMode)
+    where
+        TIM: InternalTrigger<MASTER>,
+    {
+        self.tim
+            .set_slave_mode(<TIM as InternalTrigger<MASTER>>::ITR, mode as u8)
+    }
+
+    /// Disables the synchronization on a master timer
+    pub fn disable_slave_mode(&mut self) {
+        self.tim.set_slave_mode(0, 0)
+    }
+}
+
+impl<TIM: Instance + SlaveTimer, const FREQ: u32> FTimer<TIM, FREQ> {
+    /// Synchronizes the timer in `mode` on the trigger output of `MASTER`
+    pub fn set_slave_mode<MASTER>(&mut self, mode: SlaveMode)
+    where
+        TIM: InternalTrigger<MASTER>,
+    {
+        self.tim
+            .set_slave_mode(<TIM as InternalTrigger<MASTER>>::ITR, mode as u8)
+    }
+
+    /// Disables the synchronization on a master timer
+    pub fn disable_slave_mode(&mut self) {
+        self.tim.set_slave_mode(0, 0)
+    }
+}
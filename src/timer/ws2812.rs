@@ -0,0 +1,137 @@
+//! WS2812 (NeoPixel) LED driver
+//!
+//! The LEDs are driven by a PWM channel at 800 kHz, taking with DMA the duty cycle of each bit
+//! from a buffer: a 0 is high for 0.4 µs, a 1 for 0.8 µs. The buffer ends with low periods
+//! latching the colors into the LEDs.
+//!
+//! ```ignore
+//! static mut BUFFER: [u8; buffer_len(8)] = [0; buffer_len(8)];
+//!
+//! let pa0 = gpioa.pa0.into_alternate_push_pull(&mut gpioa.crl);
+//! let c1 = dp.TIM2.pwm_hz::<Tim2NoRemap, _, _>(pa0, &mut afio.mapr, 800.kHz(), &clocks).split();
+//! let mut leds = Ws2812::new(c1, dma1.2, unsafe { &mut BUFFER });
+//! // red, green and blue LEDs
+//! leds.write(&[[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+//! ```
+//!
+//! The timer clock is to be at least 8 MHz for the timing of the bits.
+
+use crate::dma::Transfer;
+use crate::dma::R;
+
+use super::pwm::{PwmChannel, PwmDma};
+use super::{Instance, UpdateDma, WithPwm};
+
+/// Number of low periods latching the colors, 300 µs
+pub const RESET_PERIODS: usize = 240;
+
+/// Length of the buffer of `leds` LEDs
+pub const fn buffer_len(leds: usize) -> usize {
+    leds * 24 + RESET_PERIODS
+}
+
+enum State<TIM: Instance + WithPwm + UpdateDma, const C: u8, const N: usize> {
+    Idle(PwmDma<TIM, C>, &'static mut [u8; N]),
+    Busy(Transfer<R, &'static mut [u8; N], PwmDma<TIM, C>>),
+}
+
+/// WS2812 LED chain driven by a PWM channel at 800 kHz, with a buffer of `N` bytes
+pub struct Ws2812<TIM: Instance + WithPwm + UpdateDma, const C: u8, const N: usize> {
+    state: Option<State<TIM, C, N>>,
+    zero: u8,
+    one: u8,
+}
+
+impl<TIM: Instance + WithPwm + UpdateDma, const C: u8, const N: usize> Ws2812<TIM, C, N> {
+    /// Creates a driver for the LEDs connected to `pwm`, running at 800 kHz, writing its duty
+    /// cycles with `channel`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is too short for one LED, or if the period of the channel is
+    /// too long for the buffer.
+    pub fn new(
+        mut pwm: PwmChannel<TIM, C>,
+        channel: TIM::Channel,
+        buffer: &'static mut [u8; N],
+    ) -> Self {
+        assert!(N >= buffer_len(1), "buffer too short");
+        let period = match pwm.get_max_duty() {
+            0 => 1 << 16,
+            max => max as u32,
+        };
+        assert!(period < 256, "period too long for the buffer");
+        // The line is low until the first transfer
+        pwm.set_duty(0);
+        pwm.enable();
+        Self {
+            state: Some(State::Idle(pwm.with_dma(channel), buffer)),
+            zero: (period * 8 / 25) as u8,
+            one: (period * 16 / 25) as u8,
+        }
+    }
+
+    /// Number of LEDs of the buffer
+    pub const fn capacity() -> usize {
+        (N - RESET_PERIODS) / 24
+    }
+
+    /// Transmits `colors`, red, green and blue, to the first LEDs of the chain
+    ///
+    /// Waits for the end of the previous transmission, the transmission continues in the
+    /// background.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more colors than the capacity of the buffer.
+    pub fn write(&mut self, colors: &[[u8; 3]]) {
+        assert!(colors.len() <= Self::capacity(), "too many LEDs");
+        let (pwm, buffer) = self.take();
+
+        let mut bits = buffer.iter_mut();
+        for &[r, g, b] in colors {
+            // Sent green first, most significant bit first
+            for byte in [g, r, b] {
+                for i in (0..8).rev() {
+                    *bits.next().unwrap() = if byte & (1 << i) != 0 {
+                        self.one
+                    } else {
+                        self.zero
+                    };
+                }
+            }
+        }
+        // Low until the end of the buffer
+        buffer[colors.len() * 24..].fill(0);
+
+        let transfer = pwm.write_table(buffer);
+        self.state = Some(State::Busy(transfer));
+    }
+
+    /// Returns true while a transmission is in progress
+    pub fn is_busy(&self) -> bool {
+        match &self.state {
+            Some(State::Busy(transfer)) => !transfer.is_done(),
+            _ => false,
+        }
+    }
+
+    /// Waits for the end of the transmission and returns the PWM channel, the DMA channel
+    /// and the buffer
+    pub fn release(mut self) -> (PwmChannel<TIM, C>, TIM::Channel, &'static mut [u8; N]) {
+        let (pwm, buffer) = self.take();
+        let (pwm, channel) = pwm.release();
+        (pwm, channel, buffer)
+    }
+
+    fn take(&mut self) -> (PwmDma<TIM, C>, &'static mut [u8; N]) {
+        match self.state.take() {
+            Some(State::Idle(pwm, buffer)) => (pwm, buffer),
+            Some(State::Busy(transfer)) => {
+                let (buffer, pwm) = transfer.wait();
+                (pwm, buffer)
+            }
+            None => unreachable!(),
+        }
+    }
+}